@@ -5,6 +5,9 @@ fn main() {
             "src/protos/pose.proto",
             "src/protos/laser_scan.proto",
             "src/protos/reset.proto",
+            "src/protos/goal.proto",
+            "src/protos/wheel.proto",
+            "src/protos/collision.proto",
         ],
         &["src/"],
     )
@@ -32,6 +32,35 @@ impl Collidable for Robot {
     }
 }
 
+impl Collidable for Trailer {
+    fn get_pose(&self) -> (f32, f32, f32) {
+        return self.pose;
+    }
+
+    fn get_shape(&self) -> Box<dyn Shape + Send + Sync> {
+        match self.shape {
+            Footprint::Circular(b) => {
+                return Box::new(b.clone());
+            }
+
+            Footprint::Rectangular(p) => {
+                return Box::new(p.clone());
+            }
+        }
+    }
+
+    fn get_max_extent(&self) -> f32 {
+        match self.shape {
+            Footprint::Circular(s) => {
+                return 2.0 * s.radius;
+            }
+            Footprint::Rectangular(s) => {
+                return f32::min(s.half_extents[0] * 2.0, s.half_extents[1] * 2.0);
+            }
+        }
+    }
+}
+
 impl Collidable for Wall {
     fn get_pose(&self) -> (f32, f32, f32) {
         return (0.0, 0.0, 0.0);
@@ -48,7 +77,7 @@ impl Collidable for Wall {
 
 impl Collidable for StaticObj {
     fn get_pose(&self) -> (f32, f32, f32) {
-        return (self.center.0, self.center.1, 0.0);
+        return (self.center.0, self.center.1, self.rotation);
     }
 
     fn get_shape(&self) -> Box<dyn Shape + Send + Sync> {
@@ -1,5 +1,7 @@
 use crate::behaviour::traits::{Collidable, Sensable};
 use crate::object::sensors::*;
+use crate::parameter::LIDAR_MAX_RANGE;
+use crate::utils::SimRng;
 use rand::prelude::*;
 use rayon::prelude::*;
 
@@ -11,18 +13,26 @@ impl Sensable for LiDAR {
     }
 
     fn sense(&self, collidables: &Vec<Box<dyn Collidable>>) -> Self::OutputMsg {
+        return self.sense_with_seed(collidables, thread_rng().next_u64());
+    }
+}
+
+impl LiDAR {
+    /// Same as `sense`, but the noise on the readings is derived from `seed`.
+    pub fn sense_with_seed(&self, collidables: &Vec<Box<dyn Collidable>>, seed: u64) -> LiDARMsg {
         let values: Vec<f32> = self
             .rays
             .par_iter()
-            .map(|ray| {
-                let mut min_dist = 20.0;
+            .enumerate()
+            .map(|(i, ray)| {
+                let mut min_dist = LIDAR_MAX_RANGE;
                 for obj in collidables.iter() {
                     let dist = obj.raycast(ray);
                     if dist < min_dist && dist > 0.0 {
                         min_dist = dist;
                     }
                 }
-                let mut rng = thread_rng(); // Each thread gets its own RNG
+                let mut rng = SimRng::new(seed.wrapping_add(i as u64)); // Each ray gets its own RNG
                 let random_val = rng.gen_range(-0.05..0.05);
                 min_dist + random_val
             })
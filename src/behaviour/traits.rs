@@ -3,18 +3,31 @@ use parry2d::query::contact::contact;
 use parry2d::query::Ray;
 use parry2d::shape::Shape;
 
+use crate::error::{XironError, XironResult};
+
 pub trait Drawable {
     fn draw(&self, tf: fn((f32, f32)) -> (f32, f32));
 
     fn draw_bounds(&self, tf: fn((f32, f32)) -> (f32, f32));
 }
 
+/// Where two shapes touch, seen from the first of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContactPoint {
+    /// Closest point of the first shape in the world frame
+    pub point: (f32, f32),
+    /// Unit normal pointing from the first shape to the second
+    pub normal: (f32, f32),
+    /// Distance between the shapes, negative if they overlap
+    pub distance: f32,
+}
+
 pub trait Collidable: Send + Sync {
     fn get_pose(&self) -> (f32, f32, f32);
     fn get_shape(&self) -> Box<dyn Shape + Send + Sync>;
     fn get_max_extent(&self) -> f32;
 
-    fn collision_check(&self, other: &dyn Collidable) -> bool {
+    fn collision_check(&self, other: &dyn Collidable) -> XironResult<bool> {
         let pos1 = Isometry::new(
             Vector::new(self.get_pose().0, self.get_pose().1),
             self.get_pose().2,
@@ -29,17 +42,13 @@ pub trait Collidable: Send + Sync {
         let result = contact(&pos1, &*c1, &pos2, &*c2, 1.0);
         match result {
             Ok(result) => match result {
-                None => false,
+                None => Ok(false),
                 Some(dist) => {
-                    if dist.dist < 0.1 {
-                        return true;
-                    } else {
-                        return false;
-                    }
+                    return Ok(dist.dist < 0.1);
                 }
             },
             Err(error) => {
-                panic!("{}", error);
+                return Err(XironError::Geometry(error.to_string()));
             }
         }
     }
@@ -49,7 +58,7 @@ pub trait Collidable: Send + Sync {
         other: &dyn Collidable,
         pose: &(f32, f32, f32),
         other_pose: Option<(f32, f32, f32)>,
-    ) -> bool {
+    ) -> XironResult<bool> {
         let pos1 = Isometry::new(Vector::new(pose.0, pose.1), pose.2);
         let mut pos2 = Isometry::new(
             Vector::new(other.get_pose().0, other.get_pose().1),
@@ -69,17 +78,13 @@ pub trait Collidable: Send + Sync {
         let result = contact(&pos1, &*c1, &pos2, &*c2, 1.0);
         match result {
             Ok(result) => match result {
-                None => false,
+                None => Ok(false),
                 Some(dist) => {
-                    if dist.dist < 0.05 {
-                        return true;
-                    } else {
-                        return false;
-                    }
+                    return Ok(dist.dist < 0.05);
                 }
             },
             Err(error) => {
-                panic!("{}", error);
+                return Err(XironError::Geometry(error.to_string()));
             }
         }
     }
@@ -138,6 +143,49 @@ pub trait Collidable: Send + Sync {
         }
     }
 
+    /// Closest points of the two shapes when self is at `pose` and `other` at
+    /// `other_pose`, if they are less than 0.1 apart.
+    fn contact_at(
+        &self,
+        other: &dyn Collidable,
+        pose: &(f32, f32, f32),
+        other_pose: &(f32, f32, f32),
+    ) -> Option<ContactPoint> {
+        let pos1 = Isometry::new(Vector::new(pose.0, pose.1), pose.2);
+        let pos2 = Isometry::new(Vector::new(other_pose.0, other_pose.1), other_pose.2);
+
+        let c1 = self.get_shape();
+        let c2 = other.get_shape();
+
+        match contact(&pos1, &*c1, &pos2, &*c2, 0.1) {
+            Ok(Some(result)) => {
+                return Some(ContactPoint {
+                    point: (result.point1.x, result.point1.y),
+                    normal: (result.normal1.x, result.normal1.y),
+                    distance: result.dist,
+                });
+            }
+            _ => return None,
+        }
+    }
+
+    /// Distance between the two shapes when self is at `pose`. Zero if they intersect.
+    fn distance_at(&self, other: &dyn Collidable, pose: &(f32, f32, f32)) -> f32 {
+        let pos1 = Isometry::new(Vector::new(pose.0, pose.1), pose.2);
+        let pos2 = Isometry::new(
+            Vector::new(other.get_pose().0, other.get_pose().1),
+            other.get_pose().2,
+        );
+
+        let c1 = self.get_shape();
+        let c2 = other.get_shape();
+
+        match parry2d::query::distance(&pos1, &*c1, &pos2, &*c2) {
+            Ok(dist) => dist,
+            Err(_) => f32::INFINITY,
+        }
+    }
+
     fn raycast(&self, ray: &Ray) -> f32 {
         // println!("{:?}", ray);
         let shape = self.get_shape();
@@ -176,4 +224,16 @@ pub trait GuiObject {
 
 pub trait Genericbject: Collidable + Drawable + GuiObject {
     fn get_collidable(&self) -> Box<dyn Collidable>;
+
+    /// Name of the object, if it has one.
+    fn get_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Disabled objects are skipped for collisions and sensing.
+    fn is_enabled(&self) -> bool {
+        true
+    }
+
+    fn set_enabled(&mut self, _enabled: bool) {}
 }
@@ -0,0 +1,64 @@
+use std::time::Instant;
+
+use xiron::prelude::*;
+
+/// Runs a sweep of headless experiments and writes one results table.
+/// Usage: xiron_batch sweep.yaml [--out results.csv]
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let sweep_path = match args.next() {
+        Some(path) => path,
+        None => {
+            println!("Usage: xiron_batch <sweep.yaml> [--out results.csv]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut out = "results.csv".to_string();
+    while let Some(arg) = args.next() {
+        if arg == "--out" {
+            match args.next() {
+                Some(path) => out = path,
+                None => {
+                    println!("--out needs a path");
+                    std::process::exit(2);
+                }
+            }
+        }
+    }
+
+    let sweep = match get_sweep_from_file(sweep_path) {
+        Some(sweep) => sweep,
+        None => std::process::exit(2),
+    };
+
+    let num_runs = expand_sweep(&sweep).len();
+    println!("Running {} experiments", num_runs);
+
+    let start = Instant::now();
+    let results = match run_sweep(&sweep) {
+        Ok(results) => results,
+        Err(e) => {
+            println!("Could not run sweep: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let failed = results.iter().filter(|r| r.passed == Some(false)).count();
+    let errors = results.iter().filter(|r| r.error.is_some()).count();
+    println!(
+        "Finished {} runs in {:.1} s: {} failed, {} errors",
+        results.len(),
+        start.elapsed().as_secs_f32(),
+        failed,
+        errors
+    );
+
+    match std::fs::write(&out, results_to_csv(&results)) {
+        Ok(_) => println!("Wrote results to {}", out),
+        Err(e) => {
+            println!("Could not write results to {}: {}", out, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use xiron::prelude::*;
+
+const USAGE: &str = "Usage: xiron_gen <maze|office|warehouse|clutter> [--seed N] [--count N] [--robots N] [--size W H] [--out PATH]
+
+Generates world configs. With --count > 1, PATH is a directory and one world
+is written per seed as <layout>_<seed>.yaml.";
+
+fn parse<T: std::str::FromStr>(value: Option<String>, flag: &str) -> T {
+    match value.and_then(|v| v.parse().ok()) {
+        Some(v) => v,
+        None => {
+            println!("Invalid value for {}\n{}", flag, USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+
+    let layout = match args.next().as_deref().and_then(Layout::from_name) {
+        Some(layout) => layout,
+        None => {
+            println!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let mut params = GeneratorConfig::new(layout, 0);
+    let mut count: u64 = 1;
+    let mut out: Option<String> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => params.seed = parse(args.next(), "--seed"),
+            "--count" => count = parse(args.next(), "--count"),
+            "--robots" => params.num_robots = parse(args.next(), "--robots"),
+            "--size" => params.size = (parse(args.next(), "--size"), parse(args.next(), "--size")),
+            "--out" => out = args.next(),
+            _ => {
+                println!("Unknown argument: {}\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let first_seed = params.seed;
+    for seed in first_seed..first_seed + count {
+        params.seed = seed;
+        let config = generate_world(&params);
+
+        let path = match &out {
+            Some(out) if count > 1 => {
+                if let Err(e) = std::fs::create_dir_all(out) {
+                    println!("Could not create output directory {}: {}", out, e);
+                    std::process::exit(1);
+                }
+                format!("{}/{}_{}.yaml", out, params.layout.name(), seed)
+            }
+            Some(out) => out.clone(),
+            None => format!("{}_{}.yaml", params.layout.name(), seed),
+        };
+
+        match save_config_to_file(&config, &path) {
+            Ok(_) => println!("Wrote {}", path),
+            Err(e) => {
+                println!("Could not write {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
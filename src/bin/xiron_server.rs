@@ -0,0 +1,83 @@
+use std::path::Path;
+use std::thread;
+
+use xiron::prelude::*;
+
+/// Hosts several worlds without a GUI. Clients select a world by the path they
+/// connect to, e.g. `ws://localhost:9000/warehouse`.
+/// Usage: xiron_server [name=]world.yaml [[name=]world.yaml ...]
+fn main() {
+    let mut worlds: Vec<(String, String)> = Vec::new();
+    for arg in std::env::args().skip(1) {
+        // Worlds without a name are named after their file
+        let (name, path) = match arg.split_once('=') {
+            Some((name, path)) => (name.to_string(), path.to_string()),
+            None => match Path::new(&arg).file_stem() {
+                Some(stem) => (stem.to_string_lossy().to_string(), arg.clone()),
+                None => (arg.clone(), arg.clone()),
+            },
+        };
+
+        if worlds.iter().any(|(n, _)| *n == name) {
+            println!("World {} is given more than once", name);
+            std::process::exit(2);
+        }
+        worlds.push((name, path));
+    }
+
+    if worlds.is_empty() {
+        println!("Usage: xiron_server [name=]world.yaml [[name=]world.yaml ...]");
+        std::process::exit(2);
+    }
+
+    static XIRON_COMM_SERVER_ADDR: &str = "localhost";
+    let s2c_port = 9000;
+    let c2s_port = 9001;
+    let xiron_comm_server = XironCommServer::new(XIRON_COMM_SERVER_ADDR, s2c_port, c2s_port);
+
+    let names: Vec<String> = worlds.iter().map(|(name, _)| name.clone()).collect();
+    let mut channels = match xiron_comm_server.start_worlds(&names) {
+        Ok(channels) => channels,
+        Err(e) => {
+            println!("Could not start the comm server: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut threads = Vec::new();
+    for (name, path) in worlds {
+        let sh = match SimulationHandler::from_file(path.clone()) {
+            Ok((sh, _)) => sh,
+            Err(e) => {
+                println!("Could not load world {} from {}: {}", name, path, e);
+                std::process::exit(1);
+            }
+        };
+        let world_channels = match channels.remove(&name) {
+            Some(world_channels) => world_channels,
+            None => continue,
+        };
+
+        println!(
+            "Serving world {} from {} at ws://{}:{}/{}",
+            name, path, XIRON_COMM_SERVER_ADDR, s2c_port, name
+        );
+
+        // Every world runs on its own thread with its own clock
+        let session = Session::new(name.clone(), sh, world_channels);
+        match thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || session.run())
+        {
+            Ok(handle) => threads.push(handle),
+            Err(e) => {
+                println!("Could not start world {}: {}", name, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    for handle in threads {
+        let _ = handle.join();
+    }
+}
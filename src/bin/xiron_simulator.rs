@@ -1,5 +1,4 @@
 use macroquad::prelude::*;
-use pose_msg::PositionMsg;
 use std::sync::{Arc, Mutex};
 
 use xiron::prelude::*;
@@ -15,19 +14,72 @@ async fn main() {
     let c2s_port = 9001;
     let xiron_comm_server = XironCommServer::new(XIRON_COMM_SERVER_ADDR, s2c_port, c2s_port);
 
-    let (xiron_comm_server_tx, xiron_comm_server_rx) = xiron_comm_server.start();
+    let (xiron_comm_server_tx, xiron_comm_server_rx) = match xiron_comm_server.start() {
+        Ok(channels) => channels,
+        Err(e) => {
+            println!("Could not start the comm server: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     let (open_sender, open_reciever) = std::sync::mpsc::channel();
     let (save_sender, save_reciever) = std::sync::mpsc::channel();
+    let (metrics_sender, metrics_reciever) = std::sync::mpsc::channel();
 
     let sim_handler = SimulationHandler::new();
     let sim_handler_mutex = Arc::new(Mutex::new(sim_handler));
     let sim_handler_mutex_clone = Arc::clone(&sim_handler_mutex);
-    let mut egui_handler = EguiInterface::new(open_sender, save_sender, sim_handler_mutex);
+    let mut egui_handler =
+        EguiInterface::new(open_sender, save_sender, metrics_sender, sim_handler_mutex);
     let mut last_sent_time: Option<f64> = None;
 
-    // Parse the CLI args for file path
-    let file_path_arg = std::env::args().nth(1);
+    // Parse the CLI args for file path and an optional scenario
+    // Usage: xiron_simulator [world.yaml] [--scenario scenario.yaml] [--metrics metrics.json]
+    //                        [--set key=value ...]
+    let mut file_path_arg = None;
+    let mut overrides = match overrides_from_env() {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            println!("Ignoring {}: {}", OVERRIDES_ENV_VAR, e);
+            Vec::new()
+        }
+    };
+    let mut scenario_runner: Option<ScenarioRunner> = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--scenario" {
+            match args.next().map(get_scenario_from_file) {
+                Some(Ok(scenario)) => {
+                    println!("Running scenario on world: {}", scenario.world);
+                    file_path_arg = Some(scenario.world.clone());
+                    scenario_runner = Some(ScenarioRunner::new(scenario));
+                }
+                Some(Err(e)) => {
+                    println!("Could not load scenario: {}. Continuing without it", e);
+                }
+                None => println!("--scenario needs a path"),
+            }
+        } else if arg == "--metrics" {
+            // Metrics are exported to this path when the simulator is closed
+            egui_handler.metrics_export_path = args.next();
+            prevent_quit();
+        } else if arg == "--set" {
+            // Overrides from the command line are applied after the ones from the environment
+            match args.next().map(|s| parse_override(&s)) {
+                Some(Ok(o)) => overrides.push(o),
+                Some(Err(e)) => println!("{}", e),
+                None => println!("--set needs a key=value"),
+            }
+        } else if file_path_arg.is_none() {
+            file_path_arg = Some(arg);
+        }
+    }
+
+    sim_handler_mutex_clone
+        .lock()
+        .unwrap()
+        .set_overrides(overrides);
+
     match file_path_arg {
         Some(file_path) => {
             if file_path == "" {
@@ -36,14 +88,17 @@ async fn main() {
                 println!("Starting simulator with input path: {}", file_path);
                 let mut sh = sim_handler_mutex_clone.lock().unwrap();
                 sh.load_file_path(file_path);
-                let robot_handlers = sh.reset();
-                egui_handler.reset_robot_handlers(robot_handlers);
+                if let Err(e) = sh.reset() {
+                    println!("Could not load world: {}", e);
+                }
             }
         }
         None => {
             println!("No file passed as argument. Continuing without loading file");
         }
     }
+    let mut last_sim_time = 0.0;
+    let mut last_reload_check = get_time();
 
     let mut rate = LoopRateHandler::new(1.0 / DT as f64);
     rate.sleep();
@@ -56,9 +111,13 @@ async fn main() {
             Ok(message) => {
                 println!("Got Open message here: {}", message);
                 let mut sh = sim_handler_mutex_clone.lock().unwrap();
+                // Overrides from the command line were meant for the first world only
+                sh.set_overrides(Vec::new());
                 sh.load_file_path(message);
-                let robot_handlers = sh.reset();
-                egui_handler.reset_robot_handlers(robot_handlers);
+                if let Err(e) = sh.reset() {
+                    println!("Could not load world: {}", e);
+                }
+                egui_handler.clear_history();
             }
             Err(_) => {}
         }
@@ -66,24 +125,105 @@ async fn main() {
         match save_reciever.try_recv() {
             Ok(message) => {
                 println!("Got Save message here: {}", message);
+                let mut sh = sim_handler_mutex_clone.lock().unwrap();
+                match sh.save_config(&message) {
+                    Ok(_) => {}
+                    Err(e) => println!("Could not save world to {}: {}", message, e),
+                }
+            }
+            Err(_) => {}
+        }
+
+        match metrics_reciever.try_recv() {
+            Ok(message) => {
+                println!("Got Export Metrics message here: {}", message);
                 let sh = sim_handler_mutex_clone.lock().unwrap();
-                let config = sh.to_config();
-
-                let f = std::fs::OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .open(message)
-                    .expect("Couldn't open file");
-                serde_yaml::to_writer(f, &config).unwrap();
+                match sh.export_metrics(&message) {
+                    Ok(_) => {}
+                    Err(e) => println!("Could not export metrics: {}", e),
+                }
             }
             Err(_) => {}
         }
 
+        // Check for changes of the world file about once a second
+        if egui_handler.reload_on_change && get_time() - last_reload_check > RELOAD_CHECK_PERIOD {
+            last_reload_check = get_time();
+            let mut sh = sim_handler_mutex_clone.lock().unwrap();
+            match sh.reload_if_changed() {
+                Ok(Some(summary)) => {
+                    println!(
+                        "Reloaded world. Robots added: {:?}, updated: {:?}, removed: {:?}. Objects added: {}, removed: {}",
+                        summary.robots_added,
+                        summary.robots_updated,
+                        summary.robots_removed,
+                        summary.objects_added,
+                        summary.objects_removed
+                    );
+                }
+                Ok(None) => {}
+                Err(e) => println!("Could not reload world: {}", e),
+            }
+        }
+
+        if is_quit_requested() {
+            egui_handler.export_metrics_on_close();
+            break;
+        }
+
         match egui_handler.play {
             PlayMode::Pause => {}
             PlayMode::Play => {
                 let mut sh = sim_handler_mutex_clone.lock().unwrap();
+
+                // Sim time goes back when the world was reset or an earlier moment
+                // was restored from the timeline
+                if sh.get_time() < last_sim_time {
+                    if let Some(runner) = scenario_runner.as_mut() {
+                        runner.rewind_to(sh.get_time(), &sh);
+                    }
+                }
+
+                if let Some(runner) = scenario_runner.as_mut() {
+                    for (action, result) in runner.update(&mut sh) {
+                        if let Err(e) = result {
+                            println!(
+                                "Scenario event at {:.3}s could not be applied: {}",
+                                sh.get_time(),
+                                e
+                            );
+                            continue;
+                        }
+                        match action {
+                            ActionConfig::SendGoal { id, goal, .. } => {
+                                let goal_msg = GoalMsg {
+                                    timestamp: SystemTime::now()
+                                        .duration_since(UNIX_EPOCH)
+                                        .unwrap_or_default()
+                                        .as_secs_f64(),
+                                    robot_id: id,
+                                    position: Some(goal_msg::PositionMsg {
+                                        x: goal.0,
+                                        y: goal.1,
+                                    }),
+                                    orientation: goal.2,
+                                };
+                                match xiron_comm_server_tx.send(Ok(CommResponse::Goal(goal_msg))) {
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        println!("Got error when sending goal via channel {}", e);
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+
                 sh.step();
+                publish_collision_events(&sh, &xiron_comm_server_tx);
+                last_sim_time = sh.get_time();
+                egui_handler.record_history(&sh);
             }
         }
         {
@@ -115,22 +255,15 @@ async fn main() {
                             egui_handler.reset();
                         }
                         CommResponse::Twist(twist_msg) => {
-                            let robot_handler = egui_handler.get_robot_handler(&twist_msg.robot_id);
-                            match robot_handler {
-                                Some(handler) => {
-                                    let mut sh = sim_handler_mutex_clone.lock().unwrap();
-                                    let linear = twist_msg.linear.unwrap();
-                                    let angular = twist_msg.angular;
-
-                                    // Set the control value
-                                    sh.control(&handler, (linear.x, linear.y, angular));
-                                }
-                                None => {
-                                    println!(
-                                        "Robot: {} does not exist in simulation",
-                                        twist_msg.robot_id
-                                    );
-                                }
+                            let mut sh = sim_handler_mutex_clone.lock().unwrap();
+                            if let Err(e) = apply_twist(&mut sh, &twist_msg) {
+                                println!("Could not control robot: {}", e);
+                            }
+                        }
+                        CommResponse::WheelCommand(wheel_msg) => {
+                            let mut sh = sim_handler_mutex_clone.lock().unwrap();
+                            if let Err(e) = apply_wheel_command(&mut sh, &wheel_msg) {
+                                println!("Could not control robot: {}", e);
                             }
                         }
                         _ => {
@@ -162,43 +295,7 @@ async fn main() {
         if send_message {
             last_sent_time = Some(get_time());
             let sh = sim_handler_mutex_clone.lock().unwrap();
-            for robot_name in egui_handler.robot_name_map.keys() {
-                let handler = egui_handler.get_robot_handler(robot_name);
-                match handler {
-                    Some(robot) => {
-                        let pose = sh.get_pose(&robot);
-                        let pose_msg = PoseMsg {
-                            timestamp: SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap()
-                                .as_secs_f64(),
-                            robot_id: robot_name.clone(),
-                            position: Some(PositionMsg {
-                                x: pose.0,
-                                y: pose.1,
-                            }),
-                            orientation: pose.2,
-                        };
-
-                        let resp = CommResponse::Pose(pose_msg);
-                        match xiron_comm_server_tx.send(Ok(resp)) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                println!("Got error when sending pose via channel {}", e);
-                            }
-                        }
-                        let scan = sh.sense(&robot);
-                        let scan_resp = CommResponse::from((scan, robot_name.clone()));
-                        match xiron_comm_server_tx.send(Ok(scan_resp)) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                println!("Got error when sending scan via channel {}", e);
-                            }
-                        }
-                    }
-                    None => {}
-                }
-            }
+            publish_robot_states(&sh, &xiron_comm_server_tx);
         }
 
         next_frame().await;
@@ -0,0 +1,52 @@
+use xiron::prelude::*;
+
+/// Runs scenarios headless and checks their criteria.
+/// Usage: xiron_test scenario1.yaml [scenario2.yaml ...]
+/// Exits with a non-zero code if any scenario fails.
+fn main() {
+    let scenario_paths: Vec<String> = std::env::args().skip(1).collect();
+    if scenario_paths.is_empty() {
+        println!("Usage: xiron_test <scenario.yaml> [<scenario.yaml> ...]");
+        std::process::exit(2);
+    }
+
+    let mut failed = 0;
+    for path in scenario_paths.iter() {
+        let scenario = match get_scenario_from_file(path.clone()) {
+            Ok(scenario) => scenario,
+            Err(e) => {
+                println!("[FAIL] {}: could not load scenario: {}", path, e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        match run_scenario_headless(scenario) {
+            Ok(run) => {
+                if run.evaluator.passed() && run.errors.is_empty() {
+                    println!("[PASS] {} ({:.2} s simulated)", path, run.sim_time);
+                } else {
+                    println!("[FAIL] {} ({:.2} s simulated)", path, run.sim_time);
+                    failed += 1;
+                }
+                print!("{}", run.evaluator.report());
+                for (time, e) in run.errors.iter() {
+                    println!("  Event at {:.3}s could not be applied: {}", time, e);
+                }
+            }
+            Err(e) => {
+                println!("[FAIL] {}: could not load world: {}", path, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "\n{} passed, {} failed",
+        scenario_paths.len() - failed,
+        failed
+    );
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
@@ -2,14 +2,17 @@ use crossbeam::channel::{unbounded, Receiver, Sender};
 use prost::Message;
 use prost_types::Any;
 
+use std::collections::HashMap;
 use std::net::{TcpListener, TcpStream};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use tungstenite::accept_hdr;
-use tungstenite::handshake::server::{Request, Response};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{accept_hdr, WebSocket};
 
+use crate::error::{XironError, XironResult};
 use crate::object::LiDARMsg;
 
 include!(concat!(env!("OUT_DIR"), "/_.rs"));
@@ -24,6 +27,10 @@ pub enum CommResponse {
     Pose(PoseMsg),
     Reset(ResetMsg),
     Twist(TwistMsg),
+    Goal(GoalMsg),
+    WheelCommand(WheelCommandMsg),
+    WheelEncoder(WheelEncoderMsg),
+    Collision(CollisionMsg),
 }
 
 impl From<(LiDARMsg, String)> for CommResponse {
@@ -32,7 +39,7 @@ impl From<(LiDARMsg, String)> for CommResponse {
         let scan_msg = LaserScanMsg {
             timestamp: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
-                .unwrap()
+                .unwrap_or_default()
                 .as_secs_f64(),
             robot_id: robot_name.clone(),
             angle_min: scan.angle_min,
@@ -50,7 +57,7 @@ impl CommResponse {
         let any_message = Any::decode(data.as_slice());
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
-            .unwrap()
+            .unwrap_or_default()
             .as_secs_f64();
         match any_message {
             Ok(msg) => {
@@ -92,6 +99,54 @@ impl CommResponse {
                             });
                         }
                     }
+                } else if msg.type_url == "goal" {
+                    let goal_msg = GoalMsg::decode(msg.value.as_slice());
+                    match goal_msg {
+                        Ok(msg) => {
+                            return Ok(CommResponse::Goal(msg));
+                        }
+                        Err(_) => {
+                            return Err(CommResponseError {
+                                reason: "Could not decode GoalMsg".to_string(),
+                            });
+                        }
+                    }
+                } else if msg.type_url == "wheel_cmd" {
+                    let wheel_msg = WheelCommandMsg::decode(msg.value.as_slice());
+                    match wheel_msg {
+                        Ok(msg) => {
+                            return Ok(CommResponse::WheelCommand(msg));
+                        }
+                        Err(_) => {
+                            return Err(CommResponseError {
+                                reason: "Could not decode WheelCommandMsg".to_string(),
+                            });
+                        }
+                    }
+                } else if msg.type_url == "encoder" {
+                    let encoder_msg = WheelEncoderMsg::decode(msg.value.as_slice());
+                    match encoder_msg {
+                        Ok(msg) => {
+                            return Ok(CommResponse::WheelEncoder(msg));
+                        }
+                        Err(_) => {
+                            return Err(CommResponseError {
+                                reason: "Could not decode WheelEncoderMsg".to_string(),
+                            });
+                        }
+                    }
+                } else if msg.type_url == "collision" {
+                    let collision_msg = CollisionMsg::decode(msg.value.as_slice());
+                    match collision_msg {
+                        Ok(msg) => {
+                            return Ok(CommResponse::Collision(msg));
+                        }
+                        Err(_) => {
+                            return Err(CommResponseError {
+                                reason: "Could not decode CollisionMsg".to_string(),
+                            });
+                        }
+                    }
                 } else {
                     return Err(CommResponseError {
                         reason: format!("Unknown msg_type in Protobuf message: {}", msg.type_url)
@@ -126,6 +181,22 @@ impl CommResponse {
                 current_msg_type = "twist";
                 value_vec = twist_msg.encode_to_vec();
             }
+            CommResponse::Goal(goal_msg) => {
+                current_msg_type = "goal";
+                value_vec = goal_msg.encode_to_vec();
+            }
+            CommResponse::WheelCommand(wheel_msg) => {
+                current_msg_type = "wheel_cmd";
+                value_vec = wheel_msg.encode_to_vec();
+            }
+            CommResponse::WheelEncoder(encoder_msg) => {
+                current_msg_type = "encoder";
+                value_vec = encoder_msg.encode_to_vec();
+            }
+            CommResponse::Collision(collision_msg) => {
+                current_msg_type = "collision";
+                value_vec = collision_msg.encode_to_vec();
+            }
         }
 
         let any_msg = Any {
@@ -137,6 +208,39 @@ impl CommResponse {
     }
 }
 
+/// Name of the only world of a server started with `start`.
+pub const DEFAULT_WORLD: &str = "default";
+
+/// The simulation side of the channels of a world. Messages for its clients are
+/// sent with the sender and messages from them arrive at the receiver.
+pub type CommChannels = (
+    Sender<Result<CommResponse, CommResponseError>>,
+    Receiver<Result<CommResponse, CommResponseError>>,
+);
+
+// Encoded messages for the clients of a world, one channel per client
+type ClientSenders = Arc<Mutex<Vec<Sender<Vec<u8>>>>>;
+
+// The server side of the channels of every world
+struct WorldRoutes {
+    names: Vec<String>,
+    sim_to_client: HashMap<String, ClientSenders>,
+    client_to_sim: HashMap<String, Sender<Result<CommResponse, CommResponseError>>>,
+}
+
+impl WorldRoutes {
+    /// Name of the world a client connecting to `path` selects. With a single world
+    /// every path selects it. Otherwise `/` selects the first world and every other
+    /// path must name a world.
+    fn world_for_path(&self, path: &str) -> Option<String> {
+        let name = path.trim_matches('/');
+        if name.is_empty() || self.names.len() == 1 {
+            return self.names.first().cloned();
+        }
+        return self.names.iter().find(|n| *n == name).cloned();
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct XironCommServer {
     url: &'static str,
@@ -157,186 +261,253 @@ impl XironCommServer {
         }
     }
 
-    pub fn start(
-        self,
-    ) -> (
-        Sender<Result<CommResponse, CommResponseError>>,
-        Receiver<Result<CommResponse, CommResponseError>>,
-    ) {
-        let url = self.url;
-        let s2c_port = self.sim_to_client_port.clone();
-        let c2s_port = self.client_to_sim_port.clone();
-
-        // Websocket incoming message Sender and Reciver. Websocket incoming message reciever should be returned
-        let (sim_to_client_sender, sim_to_client_receiver) = unbounded();
-
-        // Websocket outgoing message Sender and Reciver. Websocket outgoing message sender should be returned
-        let (client_to_sim_sender, client_to_sim_reciever) = unbounded();
+    /// Binds both servers and starts accepting clients in the background. Fails if
+    /// either port can not be bound, e.g. because it is already in use.
+    pub fn start(self) -> XironResult<CommChannels> {
+        let mut channels = self.start_worlds(&[DEFAULT_WORLD.to_string()])?;
+        match channels.remove(DEFAULT_WORLD) {
+            Some(channels) => return Ok(channels),
+            None => {
+                return Err(XironError::Comm(
+                    "Default world was not started".to_string(),
+                ))
+            }
+        }
+    }
 
-        let sim_to_client_receiver_clone = sim_to_client_receiver.clone();
-        let client_to_sim_sender_clone = client_to_sim_sender.clone();
+    /// Like `start`, but hosts several worlds on the same ports. Clients select a
+    /// world by the path they connect to, e.g. `ws://localhost:9000/warehouse`.
+    /// Returns the channels of every world by name.
+    pub fn start_worlds(self, names: &[String]) -> XironResult<HashMap<String, CommChannels>> {
+        let url = self.url;
+        let s2c_port = self.sim_to_client_port;
+        let c2s_port = self.client_to_sim_port;
+
+        let mut channels = HashMap::new();
+        let mut distributors = Vec::new();
+        let mut routes = WorldRoutes {
+            names: names.to_vec(),
+            sim_to_client: HashMap::new(),
+            client_to_sim: HashMap::new(),
+        };
+        for name in names.iter() {
+            // Websocket outgoing message Sender and Reciver. Websocket outgoing message sender should be returned
+            let (sim_to_client_sender, sim_to_client_receiver) = unbounded();
+
+            // Websocket incoming message Sender and Reciver. Websocket incoming message reciever should be returned
+            let (client_to_sim_sender, client_to_sim_reciever) = unbounded();
+
+            let clients: ClientSenders = Arc::new(Mutex::new(Vec::new()));
+            routes.sim_to_client.insert(name.clone(), clients.clone());
+            distributors.push((name.clone(), sim_to_client_receiver, clients));
+            routes
+                .client_to_sim
+                .insert(name.clone(), client_to_sim_sender);
+            channels.insert(name.clone(), (sim_to_client_sender, client_to_sim_reciever));
+        }
 
+        let routes = Arc::new(routes);
+        let routes_clone = Arc::clone(&routes);
         let handler = Arc::new(self);
+        let handler_clone = Arc::clone(&handler);
         let sim_to_client_url = format!("{}:{}", url, s2c_port);
         let client_to_sim_url = format!("{}:{}", url, c2s_port);
 
-        thread::spawn(move || {
-            let handler_clone = Arc::clone(&handler);
-            let sim_to_client_server = TcpListener::bind(&sim_to_client_url).expect(
-                format!(
-                    "Could not bind Sim2Client server at url: {}",
-                    sim_to_client_url
-                )
-                .as_str(),
-            );
-            println!("Successfull binded to {}", sim_to_client_url);
-
-            let client_to_sim_server = TcpListener::bind(&client_to_sim_url).expect(
-                format!(
-                    "Could not bind Client2Sim server at url: {}",
-                    client_to_sim_url
-                )
-                .as_str(),
-            );
-            println!("Successfull binded to {}", client_to_sim_url);
-
-            let sim_to_client_thread_builder =
-                thread::Builder::new().name("sim2clientServer".to_string());
-            let client_to_sim_thread_builder =
-                thread::Builder::new().name("client2simServer".to_string());
-
-            let s2c_thread = sim_to_client_thread_builder
-                .spawn(move || {
-                    for stream in sim_to_client_server.incoming() {
-                        let stream = stream.unwrap();
-
-                        let sim_to_client_receiver_clone = sim_to_client_receiver_clone.clone();
-
-                        // Handle incoming messages from WebSocket
-                        thread::spawn({
-                            let handler = handler.clone();
-                            move || {
-                                handler
-                                    .handle_sending_messages(stream, sim_to_client_receiver_clone);
-                            }
-                        });
+        let sim_to_client_server = match TcpListener::bind(&sim_to_client_url) {
+            Ok(server) => server,
+            Err(e) => return Err(XironError::Bind(sim_to_client_url, e)),
+        };
+        println!("Successfull binded to {}", sim_to_client_url);
+
+        let client_to_sim_server = match TcpListener::bind(&client_to_sim_url) {
+            Ok(server) => server,
+            Err(e) => return Err(XironError::Bind(client_to_sim_url, e)),
+        };
+        println!("Successfull binded to {}", client_to_sim_url);
+
+        // Every client of a world gets every message of that world
+        for (name, reciever, clients) in distributors.into_iter() {
+            thread::Builder::new()
+                .name(format!("sim2client-{}", name))
+                .spawn(move || Self::distribute_messages(reciever, clients))?;
+        }
+
+        let sim_to_client_thread_builder =
+            thread::Builder::new().name("sim2clientServer".to_string());
+        let client_to_sim_thread_builder =
+            thread::Builder::new().name("client2simServer".to_string());
+
+        sim_to_client_thread_builder.spawn(move || {
+            for stream in sim_to_client_server.incoming() {
+                // A client that drops during connect should not take the server down
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        println!("Could not accept Sim2Client connection: {}", e);
+                        continue;
+                    }
+                };
+
+                // Handle incoming messages from WebSocket
+                thread::spawn({
+                    let handler = handler.clone();
+                    let routes = routes.clone();
+                    move || {
+                        handler.handle_sending_messages(stream, &routes);
+                    }
+                });
+            }
+        })?;
+
+        client_to_sim_thread_builder.spawn(move || {
+            for stream in client_to_sim_server.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        println!("Could not accept Client2Sim connection: {}", e);
+                        continue;
                     }
-                })
-                .unwrap();
-
-            let c2s_thread = client_to_sim_thread_builder
-                .spawn(move || {
-                    for stream in client_to_sim_server.incoming() {
-                        let stream = stream.unwrap();
-
-                        let client_to_sim_sender_clone = client_to_sim_sender_clone.clone();
-
-                        // Handle incoming messages from WebSocket
-                        thread::spawn({
-                            let handler = handler_clone.clone();
-                            move || {
-                                handler
-                                    .handle_receiving_messages(stream, client_to_sim_sender_clone);
-                            }
-                        });
+                };
+
+                // Handle incoming messages from WebSocket
+                thread::spawn({
+                    let handler = handler_clone.clone();
+                    let routes = routes_clone.clone();
+                    move || {
+                        handler.handle_receiving_messages(stream, &routes);
                     }
-                })
-                .unwrap();
-
-            // Wait for the threads to join
-            s2c_thread.join().unwrap();
-            c2s_thread.join().unwrap();
-        });
-        return (sim_to_client_sender, client_to_sim_reciever);
+                });
+            }
+        })?;
+
+        return Ok(channels);
     }
 
-    fn handle_receiving_messages(
+    /// Accepts the websocket handshake of a client and returns the world it selected.
+    /// Clients asking for a world that does not exist are rejected.
+    fn accept_world(
         &self,
         stream: TcpStream,
-        sender: Sender<Result<CommResponse, CommResponseError>>,
-    ) {
+        routes: &WorldRoutes,
+        purpose: &str,
+    ) -> Option<(WebSocket<TcpStream>, String)> {
+        let mut world = None;
         let callback = |req: &Request, response: Response| {
-            println!(
-                "New connection: {} for receiving messages",
-                req.uri().path()
-            );
-            Ok(response)
+            let path = req.uri().path();
+            match routes.world_for_path(path) {
+                Some(name) => {
+                    println!("New connection: {} to world {} for {}", path, name, purpose);
+                    world = Some(name);
+                    Ok(response)
+                }
+                None => {
+                    println!("Rejected connection to unknown world: {}", path);
+                    let mut error = ErrorResponse::new(Some(format!("No world at {}", path)));
+                    *error.status_mut() = StatusCode::NOT_FOUND;
+                    Err(error)
+                }
+            }
         };
 
+        match accept_hdr(stream, callback) {
+            Ok(websocket) => return world.map(|world| (websocket, world)),
+            Err(e) => {
+                println!("Recieved Server handshake error for {}: {}", purpose, e);
+                return None;
+            }
+        }
+    }
+
+    fn handle_receiving_messages(&self, stream: TcpStream, routes: &WorldRoutes) {
         println!("Receiving Messages...");
-        let websocket = accept_hdr(stream, callback);
-        match websocket {
-            Ok(mut websocket) => loop {
-                let new_message = websocket.read();
-                match new_message {
-                    Ok(msg) => {
-                        let data = msg.into_data();
-                        let output = CommResponse::from_bytes(data);
-                        let sent_result = sender.send(output);
-                        match sent_result {
-                            Ok(_) => {}
-                            Err(e) => {
-                                println!("Could not send via sender: {}", e);
-                            }
+        let (mut websocket, world) = match self.accept_world(stream, routes, "receiving messages") {
+            Some(accepted) => accepted,
+            None => return,
+        };
+        let sender = match routes.client_to_sim.get(&world) {
+            Some(sender) => sender,
+            None => return,
+        };
+
+        loop {
+            let new_message = websocket.read();
+            match new_message {
+                Ok(msg) => {
+                    let data = msg.into_data();
+                    let output = CommResponse::from_bytes(data);
+                    let sent_result = sender.send(output);
+                    match sent_result {
+                        Ok(_) => {}
+                        Err(e) => {
+                            println!("Could not send via sender: {}", e);
                         }
                     }
-                    Err(error) => {
-                        println!("Error when recieving message: {}", error);
-                        break;
-                    }
                 }
-            },
-            Err(e) => {
-                println!("Recieved Server handshake error while trying to recieve data from Websocket: {}", e);
+                Err(error) => {
+                    println!("Error when recieving message: {}", error);
+                    break;
+                }
             }
         }
     }
 
-    fn handle_sending_messages(
-        &self,
-        stream: TcpStream,
+    /// Encodes every message of a world once and hands it to each of its clients.
+    /// Clients that disconnected are dropped. Runs until the simulation side closes.
+    fn distribute_messages(
         reciever: Receiver<Result<CommResponse, CommResponseError>>,
+        clients: ClientSenders,
     ) {
-        let callback = |req: &Request, response: Response| {
-            println!(
-                "New connection recieved from path: {} for Sending Messages",
-                req.uri().path()
-            );
-            Ok(response)
-        };
+        loop {
+            match reciever.recv() {
+                Ok(Ok(msg)) => {
+                    let data = msg.to_bytes();
+                    let mut clients = match clients.lock() {
+                        Ok(clients) => clients,
+                        Err(poisoned) => poisoned.into_inner(),
+                    };
+                    clients.retain(|client| client.send(data.clone()).is_ok());
+                }
+                Ok(Err(_)) => {}
+                Err(_) => break,
+            }
+        }
+    }
 
+    fn handle_sending_messages(&self, stream: TcpStream, routes: &WorldRoutes) {
         println!("Sending Messages...");
-        let websocket = accept_hdr(stream, callback);
-        match websocket {
-            Ok(mut websocket) => loop {
-                let new_message = reciever.recv();
-                match new_message {
-                    Ok(msg) => match msg {
-                        Ok(msg) => {
-                            let data = msg.to_bytes();
-                            let message = tungstenite::Message::Binary(data);
-                            let sent_result = websocket.send(message);
-                            match sent_result {
-                                Ok(_) => {}
-                                Err(e) => {
-                                    println!("Error in Sending message: {}", e);
-                                    break;
-                                }
-                            }
+        let (mut websocket, world) = match self.accept_world(stream, routes, "sending messages") {
+            Some(accepted) => accepted,
+            None => return,
+        };
+        let clients = match routes.sim_to_client.get(&world) {
+            Some(clients) => clients,
+            None => return,
+        };
+
+        let (sender, reciever) = unbounded();
+        match clients.lock() {
+            Ok(mut clients) => clients.push(sender),
+            Err(poisoned) => poisoned.into_inner().push(sender),
+        }
+
+        loop {
+            let new_message = reciever.recv();
+            match new_message {
+                Ok(data) => {
+                    let message = tungstenite::Message::Binary(data);
+                    let sent_result = websocket.send(message);
+                    match sent_result {
+                        Ok(_) => {}
+                        Err(e) => {
+                            println!("Error in Sending message: {}", e);
+                            break;
                         }
-                        Err(_) => {}
-                    },
-                    Err(error) => {
-                        println!("Error when recieving message: {}", error);
-                        break;
                     }
                 }
-            },
-            Err(e) => {
-                println!(
-                    "Recieved Server handshake error while trying to send data: {}",
-                    e
-                );
+                Err(error) => {
+                    println!("Error when recieving message: {}", error);
+                    break;
+                }
             }
         }
     }
@@ -0,0 +1,523 @@
+//! Storage of everything in the simulated world.
+//!
+//! The store keeps one `Entity` enum value per entity instead of separate
+//! component tables for pose, shape, drawing, sensors and drive. There are only
+//! three kinds of entities and all of them provide those through `Genericbject`,
+//! so collision checks, sensing and drawing query the trait. Only robot-specific
+//! code matches on the kind. A new kind is a new variant.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::behaviour::traits::{Collidable, Genericbject};
+use crate::object::robot::Robot;
+use crate::object::static_obj::StaticObj;
+use crate::object::wall::Wall;
+use crate::parser::{Config, PhysicsConfig, RobotModelConfig, StaticObjConfig, WallConfig};
+
+/// Stable id of an entity in the simulation.
+///
+/// Ids stay valid when other entities are removed. When an entity is removed its
+/// slot is reused with a new generation, so ids of removed entities never refer
+/// to something else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EntityId {
+    pub index: u32,
+    pub generation: u32,
+}
+
+impl Display for EntityId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// Storage that hands out an `EntityId` for everything inserted into it.
+/// Iteration is in slot order, which is insertion order until slots are reused.
+#[derive(Debug, Clone)]
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        return Arena {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        };
+    }
+
+    pub fn insert(&mut self, value: T) -> EntityId {
+        self.len += 1;
+
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(value);
+                return EntityId {
+                    index,
+                    generation: slot.generation,
+                };
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                return EntityId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                };
+            }
+        }
+    }
+
+    /// Removes the entity and returns it. Does nothing for stale ids.
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation || slot.value.is_none() {
+            return None;
+        }
+
+        slot.generation += 1;
+        self.free.push(id.index);
+        self.len -= 1;
+        return slot.value.take();
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        return slot.value.as_ref();
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        return slot.value.as_mut();
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool {
+        return self.get(id).is_some();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        return self.slots.iter().enumerate().filter_map(|(i, slot)| {
+            let id = EntityId {
+                index: i as u32,
+                generation: slot.generation,
+            };
+            slot.value.as_ref().map(|value| (id, value))
+        });
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        return self.slots.iter_mut().enumerate().filter_map(|(i, slot)| {
+            let id = EntityId {
+                index: i as u32,
+                generation: slot.generation,
+            };
+            slot.value.as_mut().map(|value| (id, value))
+        });
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        return self.slots.iter().filter_map(|slot| slot.value.as_ref());
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        return self.slots.iter_mut().filter_map(|slot| slot.value.as_mut());
+    }
+
+    pub fn ids(&self) -> Vec<EntityId> {
+        return self.iter().map(|(id, _)| id).collect();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    /// Removes everything. Ids handed out before stay invalid.
+    pub fn clear(&mut self) {
+        for slot in self.slots.iter_mut() {
+            if slot.value.take().is_some() {
+                slot.generation += 1;
+            }
+        }
+
+        // Free slots are kept last to first so that they are reused in order
+        self.free = (0..self.slots.len() as u32).rev().collect();
+        self.len = 0;
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        return Arena::new();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityKind {
+    Robot,
+    Wall,
+    StaticObj,
+}
+
+/// Anything that is part of the simulated world.
+///
+/// Every entity has a pose, a shape, a drawing and can be edited in the GUI,
+/// all through `Genericbject`. Robots also have sensors and a drive.
+#[derive(Clone)]
+pub enum Entity {
+    Robot(Robot),
+    Wall(Wall),
+    StaticObj(StaticObj),
+}
+
+impl Entity {
+    pub fn kind(&self) -> EntityKind {
+        match self {
+            Entity::Robot(_) => EntityKind::Robot,
+            Entity::Wall(_) => EntityKind::Wall,
+            Entity::StaticObj(_) => EntityKind::StaticObj,
+        }
+    }
+
+    pub fn object(&self) -> &dyn Genericbject {
+        match self {
+            Entity::Robot(robot) => robot,
+            Entity::Wall(wall) => wall,
+            Entity::StaticObj(obj) => obj,
+        }
+    }
+
+    pub fn object_mut(&mut self) -> &mut dyn Genericbject {
+        match self {
+            Entity::Robot(robot) => robot,
+            Entity::Wall(wall) => wall,
+            Entity::StaticObj(obj) => obj,
+        }
+    }
+
+    /// Id of a robot or name of an object, if it has one.
+    pub fn name(&self) -> Option<String> {
+        match self {
+            Entity::Robot(robot) => Some(robot.id.clone()),
+            other => other.object().get_name(),
+        }
+    }
+
+    pub fn as_robot(&self) -> Option<&Robot> {
+        match self {
+            Entity::Robot(robot) => Some(robot),
+            _ => None,
+        }
+    }
+
+    pub fn as_robot_mut(&mut self) -> Option<&mut Robot> {
+        match self {
+            Entity::Robot(robot) => Some(robot),
+            _ => None,
+        }
+    }
+
+    /// Whether robots collide with this entity and sense it. Robots themselves
+    /// are handled separately because they move.
+    pub fn is_obstacle(&self) -> bool {
+        return self.kind() != EntityKind::Robot && self.object().is_enabled();
+    }
+
+    /// Whether this is an obstacle robots can push.
+    pub fn is_movable(&self) -> bool {
+        match self {
+            Entity::StaticObj(obj) => obj.is_movable() && !obj.open,
+            _ => false,
+        }
+    }
+}
+
+/// The single store of everything in the simulation. Config I/O, drawing,
+/// collision checks and sensing all go through it.
+#[derive(Clone, Default)]
+pub struct EntityStore {
+    entities: Arena<Entity>,
+}
+
+impl EntityStore {
+    pub fn new() -> EntityStore {
+        return EntityStore {
+            entities: Arena::new(),
+        };
+    }
+
+    pub fn insert(&mut self, entity: Entity) -> EntityId {
+        return self.entities.insert(entity);
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity> {
+        return self.entities.remove(id);
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        return self.entities.get(id);
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        return self.entities.get_mut(id);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+        return self.entities.iter();
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut Entity)> {
+        return self.entities.iter_mut();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entities.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entities.is_empty();
+    }
+
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    pub fn robot(&self, id: EntityId) -> Option<&Robot> {
+        return self.get(id).and_then(|e| e.as_robot());
+    }
+
+    pub fn robot_mut(&mut self, id: EntityId) -> Option<&mut Robot> {
+        return self.get_mut(id).and_then(|e| e.as_robot_mut());
+    }
+
+    pub fn robots(&self) -> impl Iterator<Item = (EntityId, &Robot)> {
+        return self
+            .entities
+            .iter()
+            .filter_map(|(id, e)| e.as_robot().map(|r| (id, r)));
+    }
+
+    pub fn robots_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut Robot)> {
+        return self
+            .entities
+            .iter_mut()
+            .filter_map(|(id, e)| e.as_robot_mut().map(|r| (id, r)));
+    }
+
+    /// Ids of all robots, in the order `robots` returns them.
+    pub fn robot_ids(&self) -> Vec<EntityId> {
+        return self.robots().map(|(id, _)| id).collect();
+    }
+
+    /// Walls and static objects.
+    pub fn objects(&self) -> impl Iterator<Item = (EntityId, &dyn Genericbject)> {
+        return self
+            .entities
+            .iter()
+            .filter(|(_, e)| e.kind() != EntityKind::Robot)
+            .map(|(id, e)| (id, e.object()));
+    }
+
+    /// Objects robots can collide with, i.e. everything but robots and open doors.
+    pub fn obstacles(&self) -> impl Iterator<Item = &dyn Genericbject> {
+        return self
+            .entities
+            .values()
+            .filter(|e| e.is_obstacle())
+            .map(|e| e.object());
+    }
+
+    /// Obstacles that never move, i.e. all but the rigid bodies.
+    pub fn fixed_obstacles(&self) -> impl Iterator<Item = (EntityId, &dyn Genericbject)> {
+        return self
+            .entities
+            .iter()
+            .filter(|(_, e)| e.is_obstacle() && !e.is_movable())
+            .map(|(id, e)| (id, e.object()));
+    }
+
+    /// Ids of the objects robots can push.
+    pub fn movable_ids(&self) -> Vec<EntityId> {
+        return self
+            .entities
+            .iter()
+            .filter(|(_, e)| e.is_movable())
+            .map(|(id, _)| id)
+            .collect();
+    }
+
+    pub fn static_obj(&self, id: EntityId) -> Option<&StaticObj> {
+        match self.entities.get(id) {
+            Some(Entity::StaticObj(obj)) => Some(obj),
+            _ => None,
+        }
+    }
+
+    pub fn static_obj_mut(&mut self, id: EntityId) -> Option<&mut StaticObj> {
+        match self.entities.get_mut(id) {
+            Some(Entity::StaticObj(obj)) => Some(obj),
+            _ => None,
+        }
+    }
+
+    /// Everything a sensor can see: obstacles, robots and their trailers.
+    pub fn collidables(&self) -> Vec<Box<dyn Collidable>> {
+        let mut collidables = Vec::new();
+        for obj in self.obstacles() {
+            collidables.push(obj.get_collidable());
+        }
+        for (_, robot) in self.robots() {
+            collidables.push(robot.get_collidable());
+            for trailer in robot.trailers.iter() {
+                collidables.push(Box::new(trailer.clone()));
+            }
+        }
+        return collidables;
+    }
+
+    /// Finds the robot or object with the given id or name.
+    pub fn find(&self, name: &str) -> Option<EntityId> {
+        return self
+            .entities
+            .iter()
+            .find(|(_, e)| e.name().as_deref() == Some(name))
+            .map(|(id, _)| id);
+    }
+
+    /// Every wall as a config entry, with its id.
+    pub fn wall_configs(&self) -> Vec<(WallConfig, EntityId)> {
+        return self
+            .entities
+            .iter()
+            .filter_map(|(id, entity)| match entity {
+                Entity::Wall(wall) => Some((
+                    WallConfig {
+                        endpoints: wall.coords.clone(),
+                    },
+                    id,
+                )),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// Every static object as a config entry, with its id.
+    pub fn static_obj_configs(&self) -> Vec<(StaticObjConfig, EntityId)> {
+        return self
+            .entities
+            .iter()
+            .filter_map(|(id, entity)| match entity {
+                Entity::StaticObj(obj) => Some((obj.into_config(), id)),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// Describes everything in the store as a config.
+    pub fn to_config(&self, robot_models: Vec<RobotModelConfig>, physics: PhysicsConfig) -> Config {
+        let mut config = Config {
+            robot_models,
+            robots: Vec::new(),
+            walls: Vec::new(),
+            static_objects: Vec::new(),
+            physics,
+        };
+
+        for entity in self.entities.values() {
+            match entity {
+                Entity::Robot(robot) => config.robots.push(robot.into_config()),
+                Entity::Wall(wall) => config.walls.push(WallConfig {
+                    endpoints: wall.coords.clone(),
+                }),
+                Entity::StaticObj(obj) => config.static_objects.push(obj.into_config()),
+            }
+        }
+
+        return config;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_slots_are_reused_with_a_new_generation() {
+        let mut arena = Arena::new();
+        let a = arena.insert("a");
+        let b = arena.insert("b");
+        assert_eq!(arena.remove(a), Some("a"));
+
+        let c = arena.insert("c");
+        assert_eq!(c.index, a.index);
+        assert_eq!(c.generation, a.generation + 1);
+        assert_eq!(arena.get(c), Some(&"c"));
+        assert_eq!(arena.get(b), Some(&"b"));
+        assert_eq!(arena.len(), 2);
+    }
+
+    #[test]
+    fn stale_ids_do_not_refer_to_new_entities() {
+        let mut arena = Arena::new();
+        let a = arena.insert(1);
+        arena.remove(a);
+        arena.insert(2);
+
+        assert!(!arena.contains(a));
+        assert_eq!(arena.get(a), None);
+        assert_eq!(arena.get_mut(a), None);
+        assert_eq!(arena.remove(a), None);
+        assert_eq!(arena.len(), 1);
+    }
+
+    #[test]
+    fn clear_invalidates_ids_and_reuses_slots_in_order() {
+        let mut arena = Arena::new();
+        let ids: Vec<EntityId> = (0..3).map(|i| arena.insert(i)).collect();
+        arena.clear();
+
+        assert!(arena.is_empty());
+        assert!(ids.iter().all(|id| !arena.contains(*id)));
+
+        let new_ids: Vec<EntityId> = (0..3).map(|i| arena.insert(i)).collect();
+        for (old, new) in ids.iter().zip(new_ids.iter()) {
+            assert_eq!(new.index, old.index);
+            assert_eq!(new.generation, old.generation + 1);
+        }
+    }
+
+    #[test]
+    fn store_finds_robots_by_name() {
+        let mut store = EntityStore::new();
+        let robot = Robot::from_id_and_pose("robot0".to_string(), (0.0, 0.0, 0.0), 0.5);
+        let id = store.insert(Entity::Robot(robot));
+
+        assert_eq!(store.find("robot0"), Some(id));
+        assert_eq!(store.robot_ids(), vec![id]);
+        store.remove(id);
+        assert_eq!(store.find("robot0"), None);
+        assert!(store.robot(id).is_none());
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt::Display;
+
+use crate::comms::CommResponseError;
+use crate::validation::ConfigError;
+
+/// Errors returned by the public APIs of xiron.
+#[derive(Debug)]
+pub enum XironError {
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+    /// A YAML document could not be read or written.
+    Yaml(serde_yaml::Error),
+    /// The config was read but failed validation.
+    InvalidConfig(Vec<ConfigError>),
+    /// The comm server could not listen on the given address.
+    Bind(String, std::io::Error),
+    /// A message could not be decoded, sent or received.
+    Comm(String),
+    /// parry2d could not answer a query, e.g. for an unsupported pair of shapes.
+    Geometry(String),
+    /// No robot with this id exists in the simulation.
+    RobotNotFound(String),
+    /// A robot with this id already exists in the simulation.
+    RobotExists(String),
+    /// No object with this id exists in the simulation.
+    ObjectNotFound(String),
+    /// The robot with this id has no sensor to sense with.
+    NoSensor(String),
+    /// An object was created with parameters it can not be built from.
+    InvalidObject(String),
+    /// The robot with this id has no wheels configured.
+    NoWheels(String),
+    /// A command does not fit the robot it was sent to.
+    InvalidCommand(String),
+}
+
+pub type XironResult<T> = Result<T, XironError>;
+
+impl Display for XironError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XironError::Io(e) => write!(f, "IO error: {}", e),
+            XironError::Yaml(e) => write!(f, "YAML error: {}", e),
+            XironError::InvalidConfig(errors) => {
+                write!(f, "Invalid config with {} error(s)", errors.len())?;
+                for error in errors.iter() {
+                    write!(f, "\n  {}", error)?;
+                }
+                return Ok(());
+            }
+            XironError::Bind(addr, e) => write!(f, "Could not bind to {}: {}", addr, e),
+            XironError::Comm(reason) => write!(f, "Communication error: {}", reason),
+            XironError::Geometry(reason) => write!(f, "Geometry error: {}", reason),
+            XironError::RobotNotFound(id) => write!(f, "Robot {} does not exist", id),
+            XironError::RobotExists(id) => write!(f, "Robot {} already exists", id),
+            XironError::ObjectNotFound(id) => write!(f, "Object {} does not exist", id),
+            XironError::NoSensor(id) => write!(f, "Robot {} has no sensor", id),
+            XironError::InvalidObject(reason) => write!(f, "Invalid object: {}", reason),
+            XironError::NoWheels(id) => write!(f, "Robot {} has no wheels configured", id),
+            XironError::InvalidCommand(reason) => write!(f, "Invalid command: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for XironError {}
+
+impl From<std::io::Error> for XironError {
+    fn from(error: std::io::Error) -> Self {
+        return XironError::Io(error);
+    }
+}
+
+impl From<serde_yaml::Error> for XironError {
+    fn from(error: serde_yaml::Error) -> Self {
+        return XironError::Yaml(error);
+    }
+}
+
+impl From<Vec<ConfigError>> for XironError {
+    fn from(errors: Vec<ConfigError>) -> Self {
+        return XironError::InvalidConfig(errors);
+    }
+}
+
+impl From<CommResponseError> for XironError {
+    fn from(error: CommResponseError) -> Self {
+        return XironError::Comm(error.reason);
+    }
+}
@@ -0,0 +1,272 @@
+//! Batch experiments across worlds, seeds and robot parameters.
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::handler::SimulationHandler;
+use crate::metrics::{robot_csv_row, MetricsCollector, CSV_HEADER};
+use crate::parser::{get_config_from_file, Config, RobotConfig};
+use crate::scenario::{get_scenario_from_file, run_scenario, ScenarioConfig};
+
+/// Definition of a sweep. Every combination of world, seed and parameter values is run once.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SweepConfig {
+    /// Paths to world configs. Relative paths are resolved from the sweep file.
+    pub worlds: Vec<String>,
+    /// Scenario whose events and criteria are run in every world. Its `world` is ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scenario: Option<String>,
+    /// Seeds from `seeds.0` (inclusive) to `seeds.1` (exclusive).
+    pub seeds: (u64, u64),
+    /// Values for robot config fields. A key is either a field name, which is set on
+    /// every robot, or `<robot id>.<field name>`.
+    #[serde(default)]
+    pub params: BTreeMap<String, Vec<Value>>,
+    /// Overrides the duration of the scenario.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f32>,
+    /// Number of worker threads. Defaults to the number of CPUs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+}
+
+/// A single run of a sweep.
+#[derive(Clone, Debug)]
+pub struct RunSpec {
+    pub world: String,
+    pub seed: u64,
+    pub params: Vec<(String, Value)>,
+}
+
+impl RunSpec {
+    /// Parameters as `key=value` pairs separated by `;`. Values are written as JSON.
+    pub fn params_string(&self) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, serde_json::to_string(v).unwrap_or_default()))
+            .collect();
+        return params.join(";");
+    }
+}
+
+pub struct RunResult {
+    pub spec: RunSpec,
+    /// None if the scenario has no criteria or the run could not be started.
+    pub passed: Option<bool>,
+    pub sim_time: f32,
+    pub metrics: MetricsCollector,
+    pub error: Option<String>,
+}
+
+pub fn get_sweep_from_file(path: String) -> Option<SweepConfig> {
+    let file_result = std::fs::File::open(&path);
+    match file_result {
+        Ok(file) => match serde_yaml::from_reader::<_, SweepConfig>(file) {
+            Ok(mut sweep) => {
+                let dir = Path::new(&path).parent().unwrap_or(Path::new(""));
+                let resolve = |p: &String| -> String {
+                    if Path::new(p).is_relative() {
+                        dir.join(p).to_string_lossy().to_string()
+                    } else {
+                        p.clone()
+                    }
+                };
+                sweep.worlds = sweep.worlds.iter().map(resolve).collect();
+                sweep.scenario = sweep.scenario.as_ref().map(resolve);
+                return Some(sweep);
+            }
+            Err(e) => {
+                println!("Error in reading sweep file {}: {}", path, e);
+                return None;
+            }
+        },
+        Err(e) => {
+            println!("Error in opening file: {}", e);
+            return None;
+        }
+    }
+}
+
+/// All runs of the sweep, in a stable order.
+pub fn expand_sweep(sweep: &SweepConfig) -> Vec<RunSpec> {
+    // Cartesian product of the parameter grid
+    let mut grid: Vec<Vec<(String, Value)>> = vec![Vec::new()];
+    for (key, values) in sweep.params.iter() {
+        let mut next = Vec::new();
+        for combination in grid.iter() {
+            for value in values.iter() {
+                let mut combination = combination.clone();
+                combination.push((key.clone(), value.clone()));
+                next.push(combination);
+            }
+        }
+        grid = next;
+    }
+
+    let mut specs = Vec::new();
+    for world in sweep.worlds.iter() {
+        for seed in sweep.seeds.0..sweep.seeds.1 {
+            for params in grid.iter() {
+                specs.push(RunSpec {
+                    world: world.clone(),
+                    seed,
+                    params: params.clone(),
+                });
+            }
+        }
+    }
+    return specs;
+}
+
+/// Sets `field` of the robot config to `value`.
+fn set_robot_field(robot: &mut RobotConfig, field: &str, value: &Value) -> Result<(), String> {
+    let mut robot_value = serde_yaml::to_value(&*robot).map_err(|e| e.to_string())?;
+    match robot_value.as_mapping_mut() {
+        Some(mapping) => {
+            mapping.insert(Value::String(field.to_string()), value.clone());
+        }
+        None => return Err("Robot config is not a mapping".to_string()),
+    }
+    *robot = serde_yaml::from_value(robot_value)
+        .map_err(|e| format!("Invalid value for {}: {}", field, e))?;
+    return Ok(());
+}
+
+/// Applies the parameters of a run to the robots of the config.
+pub fn apply_params(config: &mut Config, params: &Vec<(String, Value)>) -> Result<(), String> {
+    for (key, value) in params.iter() {
+        let (robot_id, field) = match key.split_once('.') {
+            Some((robot_id, field)) => (Some(robot_id), field),
+            None => (None, key.as_str()),
+        };
+
+        for robot in config.robots.iter_mut() {
+            if robot_id.is_none() || robot_id == Some(robot.id.as_str()) {
+                set_robot_field(robot, field, value)?;
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// Executes a single run.
+pub fn run_single(spec: RunSpec, scenario: &ScenarioConfig) -> RunResult {
+    let mut result = RunResult {
+        spec,
+        passed: None,
+        sim_time: 0.0,
+        metrics: MetricsCollector::new(),
+        error: None,
+    };
+
+    let mut config = match get_config_from_file(result.spec.world.clone()) {
+        Ok(config) => config,
+        Err(e) => {
+            result.error = Some(format!("Could not load world: {}", e));
+            return result;
+        }
+    };
+    if let Err(e) = apply_params(&mut config, &result.spec.params) {
+        result.error = Some(e);
+        return result;
+    }
+
+    let mut sh = match SimulationHandler::from_config(&config) {
+        Ok((sh, _)) => sh,
+        Err(e) => {
+            result.error = Some(format!("Could not load world: {}", e));
+            return result;
+        }
+    };
+    sh.set_seed(result.spec.seed);
+
+    let run = run_scenario(&mut sh, scenario.clone());
+    if let Some((time, e)) = run.errors.first() {
+        result.error = Some(format!(
+            "Scenario event at {:.3}s could not be applied: {}",
+            time, e
+        ));
+    }
+    if !run.evaluator.criteria.is_empty() {
+        result.passed = Some(run.evaluator.passed());
+    }
+    result.sim_time = run.sim_time;
+    result.metrics = run.metrics;
+
+    return result;
+}
+
+/// Runs every combination of the sweep in parallel. Each run gets its own `SimulationHandler`.
+pub fn run_sweep(sweep: &SweepConfig) -> Result<Vec<RunResult>, String> {
+    let mut scenario = match &sweep.scenario {
+        Some(path) => match get_scenario_from_file(path.clone()) {
+            Ok(scenario) => scenario,
+            Err(e) => return Err(format!("Could not load scenario {}: {}", path, e)),
+        },
+        None => ScenarioConfig {
+            world: "".to_string(),
+            duration: None,
+            events: Vec::new(),
+            criteria: Vec::new(),
+        },
+    };
+    if sweep.duration.is_some() {
+        scenario.duration = sweep.duration;
+    }
+
+    let mut pool = rayon::ThreadPoolBuilder::new();
+    if let Some(threads) = sweep.threads {
+        pool = pool.num_threads(threads);
+    }
+    let pool = pool.build().map_err(|e| e.to_string())?;
+
+    let specs = expand_sweep(sweep);
+    let results = pool.install(|| {
+        specs
+            .into_par_iter()
+            .map(|spec| run_single(spec, &scenario))
+            .collect()
+    });
+
+    return Ok(results);
+}
+
+/// Results as a table with one row per robot and run.
+pub fn results_to_csv(results: &Vec<RunResult>) -> String {
+    let mut csv = format!("world,seed,params,passed,sim_time,error,{}\n", CSV_HEADER);
+
+    for result in results.iter() {
+        let passed = match result.passed {
+            Some(p) => p.to_string(),
+            None => "".to_string(),
+        };
+        let prefix = format!(
+            "{},{},\"{}\",{},{},\"{}\"",
+            result.spec.world,
+            result.spec.seed,
+            result.spec.params_string().replace('"', "\"\""),
+            passed,
+            result.sim_time,
+            result
+                .error
+                .clone()
+                .unwrap_or_default()
+                .replace('\n', " ")
+                .replace('"', "\"\"")
+        );
+
+        if result.metrics.robots().is_empty() {
+            csv.push_str(&prefix);
+            csv.push_str(&",".repeat(CSV_HEADER.matches(',').count() + 1));
+            csv.push('\n');
+        }
+        for robot in result.metrics.robots().iter() {
+            csv.push_str(&format!("{},{}\n", prefix, robot_csv_row(robot)));
+        }
+    }
+    return csv;
+}
@@ -0,0 +1,222 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::parser::{StaticObjConfig, WallConfig};
+
+/// Walls and objects produced by a layout.
+pub struct LayoutOutput {
+    pub walls: Vec<WallConfig>,
+    pub static_objects: Vec<StaticObjConfig>,
+}
+
+fn segment(p0: (f32, f32), p1: (f32, f32)) -> WallConfig {
+    WallConfig {
+        endpoints: vec![p0, p1],
+    }
+}
+
+fn rect(center: (f32, f32), width: f32, height: f32) -> StaticObjConfig {
+    StaticObjConfig {
+        center,
+        width,
+        height,
+        rotation: 0.0,
+        id: None,
+        body: None,
+    }
+}
+
+/// Closed wall around the area.
+pub fn boundary(size: (f32, f32)) -> WallConfig {
+    let (hw, hh) = (size.0 * 0.5, size.1 * 0.5);
+    WallConfig {
+        endpoints: vec![(-hw, -hh), (hw, -hh), (hw, hh), (-hw, hh), (-hw, -hh)],
+    }
+}
+
+/// Perfect maze on a grid of square cells, carved with a randomised depth first search.
+pub fn maze(rng: &mut StdRng, size: (f32, f32), cell_size: f32) -> LayoutOutput {
+    let cols = ((size.0 / cell_size).floor() as usize).max(1);
+    let rows = ((size.1 / cell_size).floor() as usize).max(1);
+    let origin = (
+        -(cols as f32) * cell_size * 0.5,
+        -(rows as f32) * cell_size * 0.5,
+    );
+
+    // Walls on the east and north side of every cell
+    let mut east = vec![vec![true; cols]; rows];
+    let mut north = vec![vec![true; cols]; rows];
+
+    let mut visited = vec![vec![false; cols]; rows];
+    let mut stack = vec![(0usize, 0usize)];
+    visited[0][0] = true;
+
+    while let Some(&(r, c)) = stack.last() {
+        let mut neighbours = Vec::new();
+        if r > 0 && !visited[r - 1][c] {
+            neighbours.push((r - 1, c));
+        }
+        if r + 1 < rows && !visited[r + 1][c] {
+            neighbours.push((r + 1, c));
+        }
+        if c > 0 && !visited[r][c - 1] {
+            neighbours.push((r, c - 1));
+        }
+        if c + 1 < cols && !visited[r][c + 1] {
+            neighbours.push((r, c + 1));
+        }
+
+        match neighbours.choose(rng) {
+            Some(&(nr, nc)) => {
+                if nr > r {
+                    north[r][c] = false;
+                } else if nr < r {
+                    north[nr][c] = false;
+                } else if nc > c {
+                    east[r][c] = false;
+                } else {
+                    east[r][nc] = false;
+                }
+                visited[nr][nc] = true;
+                stack.push((nr, nc));
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+
+    let mut walls = vec![boundary((cols as f32 * cell_size, rows as f32 * cell_size))];
+    for r in 0..rows {
+        for c in 0..cols {
+            let x0 = origin.0 + c as f32 * cell_size;
+            let y0 = origin.1 + r as f32 * cell_size;
+            let x1 = x0 + cell_size;
+            let y1 = y0 + cell_size;
+
+            // The outer walls are covered by the boundary
+            if east[r][c] && c + 1 < cols {
+                walls.push(segment((x1, y0), (x1, y1)));
+            }
+            if north[r][c] && r + 1 < rows {
+                walls.push(segment((x0, y1), (x1, y1)));
+            }
+        }
+    }
+
+    LayoutOutput {
+        walls,
+        static_objects: Vec::new(),
+    }
+}
+
+/// Grid of rooms. Every interior wall between two rooms gets a door opening at a random position.
+pub fn office(
+    rng: &mut StdRng,
+    size: (f32, f32),
+    rooms: (usize, usize),
+    door_width: f32,
+) -> LayoutOutput {
+    let (nx, ny) = (rooms.0.max(1), rooms.1.max(1));
+    let room_w = size.0 / nx as f32;
+    let room_h = size.1 / ny as f32;
+    let origin = (-size.0 * 0.5, -size.1 * 0.5);
+
+    let mut walls = vec![boundary(size)];
+
+    // Splits the wall from p0 to p1 into two segments with a door in between
+    let mut wall_with_door = |p0: (f32, f32), p1: (f32, f32), length: f32| {
+        let margin = door_width * 0.5 + 0.2;
+        if length <= 2.0 * margin {
+            return;
+        }
+        let t = rng.gen_range(margin..(length - margin)) / length;
+        let d = 0.5 * door_width / length;
+        let lerp = |t: f32| (p0.0 + (p1.0 - p0.0) * t, p0.1 + (p1.1 - p0.1) * t);
+
+        walls.push(segment(p0, lerp(t - d)));
+        walls.push(segment(lerp(t + d), p1));
+    };
+
+    for i in 0..nx {
+        for j in 0..ny {
+            let x0 = origin.0 + i as f32 * room_w;
+            let y0 = origin.1 + j as f32 * room_h;
+            let x1 = x0 + room_w;
+            let y1 = y0 + room_h;
+
+            if i + 1 < nx {
+                wall_with_door((x1, y0), (x1, y1), room_h);
+            }
+            if j + 1 < ny {
+                wall_with_door((x0, y1), (x1, y1), room_w);
+            }
+        }
+    }
+
+    LayoutOutput {
+        walls,
+        static_objects: Vec::new(),
+    }
+}
+
+/// Rows of racks separated by aisles, with a cross aisle at both ends.
+pub fn warehouse(
+    size: (f32, f32),
+    rack_width: f32,
+    aisle_width: f32,
+    end_aisle_width: f32,
+) -> LayoutOutput {
+    let rack_length = size.1 - 2.0 * end_aisle_width;
+    let mut static_objects = Vec::new();
+
+    if rack_length > 0.0 {
+        let pitch = rack_width + aisle_width;
+        let num_racks = ((size.0 - aisle_width) / pitch).floor() as usize;
+        let used = num_racks as f32 * pitch - aisle_width;
+        let x_start = -used * 0.5 + rack_width * 0.5;
+
+        for i in 0..num_racks {
+            let x = x_start + i as f32 * pitch;
+            static_objects.push(rect((x, 0.0), rack_width, rack_length));
+        }
+    }
+
+    LayoutOutput {
+        walls: vec![boundary(size)],
+        static_objects,
+    }
+}
+
+/// Randomly placed and rotated boxes.
+pub fn clutter(
+    rng: &mut StdRng,
+    size: (f32, f32),
+    num_boxes: usize,
+    box_size: (f32, f32),
+) -> LayoutOutput {
+    let (hw, hh) = (size.0 * 0.5, size.1 * 0.5);
+    let mut static_objects = Vec::new();
+
+    for _ in 0..num_boxes {
+        let width = rng.gen_range(box_size.0..=box_size.1);
+        let height = rng.gen_range(box_size.0..=box_size.1);
+        let margin = 0.5 * width.max(height);
+
+        let mut obj = rect(
+            (
+                rng.gen_range((-hw + margin)..(hw - margin)),
+                rng.gen_range((-hh + margin)..(hh - margin)),
+            ),
+            width,
+            height,
+        );
+        obj.rotation = rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI);
+        static_objects.push(obj);
+    }
+
+    LayoutOutput {
+        walls: vec![boundary(size)],
+        static_objects,
+    }
+}
@@ -0,0 +1,194 @@
+//! Procedural generation of world configs from a seed.
+
+pub mod layouts;
+
+pub use layouts::*;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::behaviour::traits::Collidable;
+use crate::object::{DriveType, Robot, StaticObj, Wall};
+use crate::parser::{Config, LimitsConfig, PhysicsConfig, RobotConfig};
+
+// Free space required around a robot at its start pose
+const START_CLEARANCE: f32 = 0.1;
+const MAX_PLACEMENT_ATTEMPTS: usize = 1000;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Layout {
+    Maze {
+        cell_size: f32,
+    },
+    Office {
+        rooms: (usize, usize),
+        door_width: f32,
+    },
+    Warehouse {
+        rack_width: f32,
+        aisle_width: f32,
+        end_aisle_width: f32,
+    },
+    Clutter {
+        num_boxes: usize,
+        box_size: (f32, f32),
+    },
+}
+
+impl Layout {
+    /// Layout with default parameters from its name.
+    pub fn from_name(name: &str) -> Option<Layout> {
+        match name {
+            "maze" => Some(Layout::Maze { cell_size: 2.0 }),
+            "office" => Some(Layout::Office {
+                rooms: (3, 2),
+                door_width: 1.0,
+            }),
+            "warehouse" => Some(Layout::Warehouse {
+                rack_width: 1.0,
+                aisle_width: 2.0,
+                end_aisle_width: 2.5,
+            }),
+            "clutter" => Some(Layout::Clutter {
+                num_boxes: 30,
+                box_size: (0.3, 1.2),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Maze { .. } => "maze",
+            Layout::Office { .. } => "office",
+            Layout::Warehouse { .. } => "warehouse",
+            Layout::Clutter { .. } => "clutter",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GeneratorConfig {
+    pub layout: Layout,
+    pub seed: u64,
+    /// Width and height of the world in metres, centered at the origin.
+    pub size: (f32, f32),
+    pub num_robots: usize,
+    pub robot_radius: f32,
+    pub drive_type: DriveType,
+    pub lidar: bool,
+}
+
+impl GeneratorConfig {
+    pub fn new(layout: Layout, seed: u64) -> GeneratorConfig {
+        GeneratorConfig {
+            layout,
+            seed,
+            size: (20.0, 20.0),
+            num_robots: 1,
+            robot_radius: 0.25,
+            drive_type: DriveType::Differential,
+            lidar: true,
+        }
+    }
+}
+
+/// Generates a world. The same config always generates the same world.
+pub fn generate_world(params: &GeneratorConfig) -> Config {
+    let mut rng = StdRng::seed_from_u64(params.seed);
+
+    let output = match &params.layout {
+        Layout::Maze { cell_size } => maze(&mut rng, params.size, *cell_size),
+        Layout::Office { rooms, door_width } => office(&mut rng, params.size, *rooms, *door_width),
+        Layout::Warehouse {
+            rack_width,
+            aisle_width,
+            end_aisle_width,
+        } => warehouse(params.size, *rack_width, *aisle_width, *end_aisle_width),
+        Layout::Clutter {
+            num_boxes,
+            box_size,
+        } => clutter(&mut rng, params.size, *num_boxes, *box_size),
+    };
+
+    let mut config = Config {
+        robot_models: Vec::new(),
+        robots: Vec::new(),
+        walls: output.walls,
+        static_objects: output.static_objects,
+        physics: PhysicsConfig::default(),
+    };
+    place_robots(&mut rng, params, &mut config);
+
+    return config;
+}
+
+/// Places robots at random collision free poses.
+fn place_robots(rng: &mut StdRng, params: &GeneratorConfig, config: &mut Config) {
+    let mut obstacles: Vec<Box<dyn Collidable>> = Vec::new();
+    for wall in config.walls.iter() {
+        obstacles.push(Box::new(Wall::new(wall.endpoints.clone())));
+    }
+    for obj in config.static_objects.iter() {
+        obstacles.push(Box::new(StaticObj::new(
+            obj.center,
+            obj.width,
+            obj.height,
+            obj.rotation,
+        )));
+    }
+
+    let hw = params.size.0 * 0.5 - params.robot_radius;
+    let hh = params.size.1 * 0.5 - params.robot_radius;
+    if hw <= 0.0 || hh <= 0.0 {
+        println!("World is too small to place robots");
+        return;
+    }
+
+    for i in 0..params.num_robots {
+        let mut placed = false;
+
+        for _ in 0..MAX_PLACEMENT_ATTEMPTS {
+            let pose = (
+                rng.gen_range(-hw..hw),
+                rng.gen_range(-hh..hh),
+                rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI),
+            );
+
+            let robot = Robot::from_id_and_pose(format!("robot{}", i), pose, params.robot_radius);
+            let free = obstacles
+                .iter()
+                .all(|o| robot.distance_at(&**o, &pose) > START_CLEARANCE);
+
+            if free {
+                config.robots.push(RobotConfig {
+                    id: robot.id.clone(),
+                    model: None,
+                    pose,
+                    vel: (0.0, 0.0, 0.0),
+                    lidar: params.lidar,
+                    footprint: vec![params.robot_radius],
+                    drive_type: params.drive_type.clone(),
+                    add_noise: false,
+                    limits: LimitsConfig::default(),
+                    actuator: None,
+                    noise: None,
+                    wheels: None,
+                    skid_steer: None,
+                    trailers: Vec::new(),
+                    body: None,
+                    collision_response: None,
+                });
+                obstacles.push(Box::new(robot));
+                placed = true;
+                break;
+            }
+        }
+
+        if !placed {
+            println!("Could not find a collision free start pose for robot{}", i);
+        }
+    }
+}
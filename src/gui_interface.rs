@@ -1,18 +1,19 @@
+use crate::error::XironResult;
 use egui_macroquad::egui::{self, Button, TopBottomPanel, Window};
 use egui_macroquad::egui::{Context, Visuals};
 use macroquad::prelude::*;
-use std::collections::HashMap;
 use std::future::Future;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
 
 use crate::object::DriveType;
+use crate::parameter::{SNAPSHOT_HISTORY_LENGTH, SNAPSHOT_PERIOD};
 use crate::prelude::{
-    CameraHandler, ObjectParameterType, Robot, RobotHandler, SelectedObjectType, SimulationHandler,
-    StaticObj, Wall,
+    CameraHandler, ObjectParameterType, Robot, RobotHandler, SelectedObject, SimulationHandler,
+    SnapshotHistory, StaticObj, Wall,
 };
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Mode {
     Robot(DriveType),
     StaticObj,
@@ -41,22 +42,34 @@ pub enum WallDrawStatus {
 
 pub struct EguiInterface {
     pub clicked_mode: Mode,
-    pub nearest_object_index: (Option<SelectedObjectType>, i32),
+    pub selected_object: Option<SelectedObject>,
     pub play: PlayMode,
     pub object_select_mode: ObjectSelectMode,
 
     // Sender for filebox
     pub open_file_path_sender: Sender<String>,
     pub save_file_path_sender: Sender<String>,
+    pub metrics_file_path_sender: Sender<String>,
+
+    // Metrics are written here when the simulator is closed
+    pub metrics_export_path: Option<String>,
+
+    // Reload the world whenever its file changes
+    pub reload_on_change: bool,
 
     sim_handler: Arc<Mutex<SimulationHandler>>,
-    pub robot_handlers: Vec<RobotHandler>,
-    pub robot_name_map: HashMap<String, RobotHandler>,
 
     camera_handler: CameraHandler,
 
     follow_mode: bool,
 
+    // Draw the LiDAR scan of the selected robot
+    show_scan: bool,
+
+    // Recent snapshots for the timeline, and the one shown if we scrubbed back
+    history: SnapshotHistory,
+    timeline_index: Option<usize>,
+
     // local variables
     wall_draw_status: WallDrawStatus,
 }
@@ -65,40 +78,62 @@ impl EguiInterface {
     pub fn new(
         open_sender: Sender<String>,
         save_sender: Sender<String>,
+        metrics_sender: Sender<String>,
         sim_handler_mutex: Arc<Mutex<SimulationHandler>>,
     ) -> EguiInterface {
         EguiInterface {
             clicked_mode: Mode::None,
-            nearest_object_index: (None, -1),
+            selected_object: None,
             play: PlayMode::Play,
             sim_handler: sim_handler_mutex,
             camera_handler: CameraHandler::new(),
             open_file_path_sender: open_sender,
             save_file_path_sender: save_sender,
+            metrics_file_path_sender: metrics_sender,
+            metrics_export_path: None,
+            reload_on_change: true,
             object_select_mode: ObjectSelectMode::None,
 
-            robot_handlers: Vec::new(),
-            robot_name_map: HashMap::new(),
-
             follow_mode: false,
+            show_scan: false,
+
+            history: SnapshotHistory::new(SNAPSHOT_HISTORY_LENGTH, SNAPSHOT_PERIOD),
+            timeline_index: None,
 
             wall_draw_status: WallDrawStatus::Idle,
         }
     }
     /// Returns the Robot Handler of a given robot string
     pub fn get_robot_handler(&self, robot_id: &String) -> Option<RobotHandler> {
-        let handler = self.robot_name_map.get(robot_id);
+        let sh = self.sim_handler.lock().unwrap();
+        return sh.get_robot_handler(robot_id);
+    }
+
+    /// Adds the current state of the simulation to the timeline. Should be called
+    /// after every step.
+    pub fn record_history(&mut self, sh: &SimulationHandler) {
+        self.history.record(sh);
+    }
+
+    /// Empties the timeline. Should be called whenever another world is loaded.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+        self.timeline_index = None;
+    }
 
-        match handler {
-            Some(h) => return Some(h.clone()),
-            None => return None,
+    /// Id for a robot added from the GUI that no other robot has.
+    fn next_robot_id(sh: &SimulationHandler) -> String {
+        let mut i = sh.robot_handlers().len();
+        while sh.get_robot_handler(&format!("robot{}", i)).is_some() {
+            i += 1;
         }
+        return format!("robot{}", i);
     }
 
-    pub fn reset_robot_handlers(&mut self, robot_handlers: Vec<(String, RobotHandler)>) {
-        for (name, handler) in robot_handlers.iter() {
-            self.robot_handlers.push(handler.clone());
-            self.robot_name_map.insert(name.clone(), handler.clone());
+    // Adds a robot placed with the mouse. There is nowhere else to report problems to.
+    fn add_robot(sh: &mut SimulationHandler, robot: XironResult<Robot>) {
+        if let Err(e) = robot.and_then(|robot| sh.add_robot(robot)) {
+            println!("Could not add robot: {}", e);
         }
     }
 
@@ -111,6 +146,8 @@ impl EguiInterface {
         TopBottomPanel::bottom("Play-Pause Button")
             .show(ctx, |ui| self.draw_bottom_play_pause_bar(ui));
 
+        self.draw_config_errors(ctx);
+
         Window::new("Object Info")
             .default_open(true)
             .resizable(false)
@@ -127,6 +164,37 @@ impl EguiInterface {
         self.deal_with_click_on_objects(ctx);
     }
 
+    /// Shows the problems found in the loaded config, if there are any.
+    fn draw_config_errors(&mut self, ctx: &egui::Context) {
+        let mut sh = self.sim_handler.lock().unwrap();
+        if sh.get_config_errors().is_empty() {
+            return;
+        }
+
+        let mut dismiss = false;
+        Window::new("Config Errors")
+            .default_open(true)
+            .resizable(true)
+            .min_width(400.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for error in sh.get_config_errors().iter() {
+                            ui.label(
+                                egui::RichText::new(error.to_string()).color(egui::Color32::RED),
+                            );
+                        }
+                    });
+                ui.separator();
+                dismiss = ui.button("Dismiss").clicked();
+            });
+
+        if dismiss {
+            sh.clear_config_errors();
+        }
+    }
+
     /// Draws and handles all Egui elemts for the Top bar containing
     fn draw_file_edit_view_help_bar(&mut self, ctx: &Context, ui: &mut egui::Ui) {
         egui::menu::bar(ui, |ui| {
@@ -134,13 +202,40 @@ impl EguiInterface {
                 let save_config_button = ui.button("Save Config");
                 let open_config_button = ui.button("Open Config");
                 let reset_simulation_button = ui.button("Reset Simulation");
+                let export_metrics_button = ui.button("Export Metrics");
+                ui.checkbox(&mut self.reload_on_change, "Reload on File Change");
                 ui.separator();
                 let close_button = ui.button("Close Simulator");
 
                 if close_button.clicked() {
+                    self.export_metrics_on_close();
                     std::process::exit(0);
                 }
 
+                if export_metrics_button.clicked() {
+                    // Spawn dialog on main thread
+                    let task = rfd::AsyncFileDialog::new()
+                        .add_filter("JSON", &["json"])
+                        .add_filter("CSV", &["csv"])
+                        .save_file();
+                    let sender = self.metrics_file_path_sender.clone();
+                    // Await somewhere else
+                    execute(async move {
+                        let file = task.await;
+
+                        if let Some(file) = file {
+                            let file_path = file.path().to_str();
+
+                            match file_path {
+                                None => {}
+                                Some(path) => {
+                                    sender.send(path.to_string()).ok();
+                                }
+                            }
+                        }
+                    });
+                }
+
                 if reset_simulation_button.clicked() {
                     // Reset all variables
                     self.reset();
@@ -230,21 +325,30 @@ impl EguiInterface {
     }
 
     pub fn reset(&mut self) {
-        self.nearest_object_index = (None, -1);
+        self.selected_object = None;
         self.clicked_mode = Mode::None;
         self.object_select_mode = ObjectSelectMode::None;
-        self.robot_handlers.clear();
-        self.robot_name_map.clear();
+        self.clear_history();
 
         let mut sh = self.sim_handler.lock().unwrap();
-        let robot_handlers = sh.reset();
+        // An invalid config leaves the simulation empty. Its errors are shown in
+        // the Config Errors window.
+        if let Err(e) = sh.reset() {
+            println!("Could not reset the simulation: {}", e);
+        }
 
         self.camera_handler.reset();
+    }
 
-        // Drop sh to make take ownership of self
-        drop(sh);
-
-        self.reset_robot_handlers(robot_handlers);
+    /// Writes the metrics to `metrics_export_path`, if one was given.
+    pub fn export_metrics_on_close(&self) {
+        if let Some(path) = &self.metrics_export_path {
+            let sh = self.sim_handler.lock().unwrap();
+            match sh.export_metrics(path) {
+                Ok(_) => println!("Exported metrics to {}", path),
+                Err(e) => println!("Could not export metrics to {}: {}", path, e),
+            }
+        }
     }
 
     pub fn set_and_update_camera(&mut self) {
@@ -278,6 +382,11 @@ impl EguiInterface {
                     if forklift_button.clicked() {
                         self.clicked_mode = Mode::Robot(DriveType::Forklift);
                     }
+
+                    let skid_steer_button = ui.add(egui::Button::new("SkidSteer"));
+                    if skid_steer_button.clicked() {
+                        self.clicked_mode = Mode::Robot(DriveType::SkidSteer);
+                    }
                 });
 
                 let rectangle_button = ui.add(egui::Button::new("Add Static Obj ▭"));
@@ -348,6 +457,8 @@ impl EguiInterface {
             draw_circle(mx, my, 10.0, BLACK);
         } else if self.clicked_mode == Mode::Robot(DriveType::Forklift) {
             draw_rectangle(mx_off, my_off, default_width, default_height, BLACK);
+        } else if self.clicked_mode == Mode::Robot(DriveType::SkidSteer) {
+            draw_rectangle(mx_off, my_off, default_width, default_height, BLACK);
         } else if self.clicked_mode == Mode::StaticObj {
             draw_rectangle(mx - 12.5, my - 12.5, 25.0, 25.0, GRAY);
         } else if self.clicked_mode == Mode::Wall {
@@ -384,7 +495,9 @@ impl EguiInterface {
                         }
 
                         let wall = Wall::new(tfed_pts);
-                        sh.add_wall(wall);
+                        if let Err(e) = sh.add_wall(wall) {
+                            println!("Could not add wall: {}", e);
+                        }
                         self.wall_draw_status = WallDrawStatus::Idle;
                         self.clicked_mode = Mode::None;
                     } else if is_key_pressed(KeyCode::Escape) {
@@ -398,60 +511,75 @@ impl EguiInterface {
         if is_mouse_button_down(MouseButton::Left) {
             let (x, y) = SimulationHandler::get_world_from_pixel(mx, my);
             if self.clicked_mode == Mode::Robot(DriveType::Differential) {
-                let robot_id = format!("robot{}", self.robot_handlers.len());
-                let (_, robot_handler) = sh.add_robot(Robot::from_id_and_pose(
-                    robot_id.clone(),
-                    (x, y, 0.0),
-                    SimulationHandler::scale_function(10.0),
-                ));
-
-                self.robot_handlers.push(robot_handler);
-                self.robot_name_map.insert(robot_id, robot_handler);
+                let robot_id = Self::next_robot_id(&sh);
+                Self::add_robot(
+                    &mut sh,
+                    Ok(Robot::from_id_and_pose(
+                        robot_id,
+                        (x, y, 0.0),
+                        SimulationHandler::scale_function(10.0),
+                    )),
+                );
                 self.clicked_mode = Mode::None;
             } else if self.clicked_mode == Mode::Robot(DriveType::Omnidrive) {
-                let robot_id = format!("robot{}", self.robot_handlers.len());
-                let (_, robot_handler) = sh.add_robot(Robot::new(
-                    robot_id.clone(),
-                    (x, y, 0.0),
-                    (0.0, 0.0, 0.0),
-                    true,
-                    vec![SimulationHandler::scale_function(10.0)],
-                    DriveType::Omnidrive,
-                    false,
-                ));
-
-                self.robot_handlers.push(robot_handler);
-                self.robot_name_map.insert(robot_id, robot_handler);
+                let robot_id = Self::next_robot_id(&sh);
+                Self::add_robot(
+                    &mut sh,
+                    Robot::new(
+                        robot_id,
+                        (x, y, 0.0),
+                        (0.0, 0.0, 0.0),
+                        true,
+                        vec![SimulationHandler::scale_function(10.0)],
+                        DriveType::Omnidrive,
+                        false,
+                    ),
+                );
                 self.clicked_mode = Mode::None;
             } else if self.clicked_mode == Mode::Robot(DriveType::Ackermann) {
-                let robot_id = format!("robot{}", self.robot_handlers.len());
-                let (_, robot_handler) = sh.add_robot(Robot::new(
-                    robot_id.clone(),
-                    (x, y, 0.0),
-                    (0.0, 0.0, 0.0),
-                    true,
-                    vec![1.0, 0.6],
-                    DriveType::Ackermann,
-                    false,
-                ));
-
-                self.robot_handlers.push(robot_handler);
-                self.robot_name_map.insert(robot_id, robot_handler);
+                let robot_id = Self::next_robot_id(&sh);
+                Self::add_robot(
+                    &mut sh,
+                    Robot::new(
+                        robot_id,
+                        (x, y, 0.0),
+                        (0.0, 0.0, 0.0),
+                        true,
+                        vec![1.0, 0.6],
+                        DriveType::Ackermann,
+                        false,
+                    ),
+                );
                 self.clicked_mode = Mode::None;
             } else if self.clicked_mode == Mode::Robot(DriveType::Forklift) {
-                let robot_id = format!("robot{}", self.robot_handlers.len());
-                let (_, robot_handler) = sh.add_robot(Robot::new(
-                    robot_id.clone(),
-                    (x, y, 0.0),
-                    (0.0, 0.0, 0.0),
-                    true,
-                    vec![1.0, 0.6],
-                    DriveType::Forklift,
-                    false,
-                ));
-
-                self.robot_handlers.push(robot_handler);
-                self.robot_name_map.insert(robot_id, robot_handler);
+                let robot_id = Self::next_robot_id(&sh);
+                Self::add_robot(
+                    &mut sh,
+                    Robot::new(
+                        robot_id,
+                        (x, y, 0.0),
+                        (0.0, 0.0, 0.0),
+                        true,
+                        vec![1.0, 0.6],
+                        DriveType::Forklift,
+                        false,
+                    ),
+                );
+                self.clicked_mode = Mode::None;
+            } else if self.clicked_mode == Mode::Robot(DriveType::SkidSteer) {
+                let robot_id = Self::next_robot_id(&sh);
+                Self::add_robot(
+                    &mut sh,
+                    Robot::new(
+                        robot_id,
+                        (x, y, 0.0),
+                        (0.0, 0.0, 0.0),
+                        true,
+                        vec![1.0, 0.6],
+                        DriveType::SkidSteer,
+                        false,
+                    ),
+                );
                 self.clicked_mode = Mode::None;
             } else if self.clicked_mode == Mode::StaticObj {
                 sh.add_static_obj(StaticObj::new(
@@ -476,25 +604,31 @@ impl EguiInterface {
 
         // We are doing this in order to update the index only when we click near some object.
         let did_we_get_nearest_object = sh.get_nearest_object(x, y);
-        match did_we_get_nearest_object.0 {
+        match did_we_get_nearest_object {
             Some(_r) => {
                 if is_mouse_button_down(MouseButton::Left) {
-                    self.nearest_object_index = did_we_get_nearest_object;
+                    self.selected_object = did_we_get_nearest_object;
                     // println!("Got nearest object");
                 }
             }
             None => {}
         }
 
+        // The selected object may have been removed, e.g. by a reload or a scenario
+        if !sh.selected_object_exists(self.selected_object) {
+            self.selected_object = None;
+        }
+
         /*
         Piece of code to draw a green boundary on the selected object
         */
-        sh.draw_bounds_of_selected_object(self.nearest_object_index);
-
-        let (object_type, index) = (self.nearest_object_index.0, self.nearest_object_index.1);
+        sh.draw_bounds_of_selected_object(self.selected_object);
+        if self.show_scan {
+            sh.draw_scan_of_selected_object(self.selected_object);
+        }
 
-        // Now here we will have the object type and index saved together
-        match object_type {
+        // Now here we will have the object type and id saved together
+        match self.selected_object {
             None => {}
             Some(_obj) => {
                 if is_mouse_button_pressed(MouseButton::Left) {
@@ -504,7 +638,7 @@ impl EguiInterface {
                             let (mx, my) = self.camera_handler.mouse_position();
                             let (wx, wy) = SimulationHandler::get_world_from_pixel(mx, my);
                             let center = sh.get_parameters_of_selected_object(
-                                self.nearest_object_index,
+                                self.selected_object,
                                 ObjectParameterType::Position(0.0, 0.0),
                             );
                             match center {
@@ -513,7 +647,7 @@ impl EguiInterface {
                                     let heigh = (wy - y).abs();
 
                                     sh.change_parameters_of_selected_object(
-                                        self.nearest_object_index,
+                                        self.selected_object,
                                         ObjectParameterType::Bounds(width, heigh),
                                     );
                                 }
@@ -524,7 +658,7 @@ impl EguiInterface {
                             let (mx, my) = self.camera_handler.mouse_position();
                             let (wx, wy) = SimulationHandler::get_world_from_pixel(mx, my);
                             sh.change_parameters_of_selected_object(
-                                (object_type, index),
+                                self.selected_object,
                                 ObjectParameterType::Position(wx, wy),
                             );
                         }
@@ -532,14 +666,14 @@ impl EguiInterface {
                             let (mx, my) = self.camera_handler.mouse_position();
                             let (wx, wy) = SimulationHandler::get_world_from_pixel(mx, my);
                             let center = sh.get_parameters_of_selected_object(
-                                self.nearest_object_index,
+                                self.selected_object,
                                 ObjectParameterType::Position(0.0, 0.0),
                             );
                             match center {
                                 ObjectParameterType::Position(x, y) => {
                                     let rotation = (wy - y).atan2(wx - x);
                                     sh.change_parameters_of_selected_object(
-                                        self.nearest_object_index,
+                                        self.selected_object,
                                         ObjectParameterType::Rotation(rotation),
                                     );
                                 }
@@ -551,18 +685,18 @@ impl EguiInterface {
             }
         }
         if is_key_pressed(KeyCode::Escape) {
-            self.nearest_object_index = (None, -1);
+            self.selected_object = None;
         }
     }
 
     /// Function to draw stuff about selected object on the floating Window
     fn draw_details_of_selected_objects(&mut self, ui: &mut egui::Ui) {
         let sh = self.sim_handler.lock().unwrap();
-        match self.nearest_object_index.0 {
+        match self.selected_object {
             Some(obj) => match obj {
                 _ => {
                     let full_information =
-                        sh.get_full_information_of_selected_object(self.nearest_object_index);
+                        sh.get_full_information_of_selected_object(self.selected_object);
                     egui::Grid::new("my_grid")
                         .num_columns(2)
                         .spacing([40.0, 4.0])
@@ -604,6 +738,10 @@ impl EguiInterface {
                             ui.label("Follow");
                             ui.checkbox(&mut self.follow_mode, "");
                             ui.end_row();
+
+                            ui.label("Show Scan");
+                            ui.checkbox(&mut self.show_scan, "");
+                            ui.end_row();
                         });
 
                     if self.follow_mode {
@@ -630,7 +768,9 @@ impl EguiInterface {
                     PlayMode::Pause => {
                         let button = ui.add(Button::new("Play ▶"));
                         if button.clicked() {
+                            // Resuming from an earlier moment drops the snapshots after it
                             self.play = PlayMode::Play;
+                            self.timeline_index = None;
                         }
                     }
 
@@ -641,6 +781,7 @@ impl EguiInterface {
                         }
                     }
                 };
+                self.draw_timeline(ui);
             });
             ui.with_layout(
                 egui::Layout::centered_and_justified(egui::Direction::LeftToRight),
@@ -662,6 +803,33 @@ impl EguiInterface {
 
         // Put text on the left about the current FPS
     }
+
+    /// Draws a slider over the recent snapshots. Moving it pauses the simulation
+    /// and restores the chosen snapshot.
+    fn draw_timeline(&mut self, ui: &mut egui::Ui) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        let last = self.history.len() - 1;
+        let mut index = self.timeline_index.unwrap_or(last).min(last);
+        let slider = ui.add(egui::Slider::new(&mut index, 0..=last).show_value(false));
+        if let Some(snapshot) = self.history.get(index) {
+            ui.label(format!("Sim Time: {:.1}s", snapshot.time));
+        }
+
+        if slider.changed() {
+            self.play = PlayMode::Pause;
+            self.timeline_index = Some(index);
+
+            if let Some(snapshot) = self.history.get(index) {
+                let mut sh = self.sim_handler.lock().unwrap();
+                if let Err(e) = sh.restore(snapshot) {
+                    println!("Could not restore the simulation: {}", e);
+                }
+            }
+        }
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -1,22 +1,117 @@
-use crate::behaviour::traits::{Collidable, Drawable};
+use crate::behaviour::traits::{Collidable, ContactPoint, Drawable};
+use crate::entity::{Entity, EntityId, EntityKind, EntityStore};
+use crate::error::{XironError, XironResult};
+use crate::include::included_files;
+use crate::metrics::MetricsCollector;
+use crate::object::rigid_body::{contact_impulse, integrate_body, ContactBody};
 use crate::object::robot::Robot;
 use crate::object::sensors::LiDARMsg;
 use crate::object::static_obj::StaticObj;
 use crate::object::wall::Wall;
+use crate::object::wheels::WheelEncoder;
 use crate::parameter::*;
 use crate::parser::*;
 use crate::prelude::traits::{Genericbject, GuiObject};
 use crate::prelude::Footprint;
-use crate::utils::interpolate_pose;
+use crate::snapshot::{RobotSnapshot, Snapshot, StaticObjSnapshot};
+use crate::utils::{hash_str, interpolate_pose, normalise};
+use crate::validation::{load_config_with_overrides, ConfigError};
 use macroquad::prelude::*;
+use std::time::SystemTime;
 
-#[derive(Debug, Clone, Copy)]
+// A body moving during a step, with the pose it starts at and the pose it ends at
+type BodyMotion<'a> = (&'a dyn Collidable, (f32, f32, f32), (f32, f32, f32));
+
+// The first thing a robot hit during a substep
+#[derive(Clone, Copy)]
+struct Hit {
+    toi: f32,
+    other: EntityId,
+    contact: Option<ContactPoint>,
+}
+
+// The earlier of the two hits
+fn first_hit(hit: Option<Hit>, other: Hit) -> Option<Hit> {
+    match hit {
+        Some(hit) if hit.toi <= other.toi => Some(hit),
+        _ => Some(other),
+    }
+}
+
+// The earlier of two times of impact
+fn first_toi(toi: Option<f32>, other: f32) -> Option<f32> {
+    return Some(toi.map_or(other, |t| t.min(other)));
+}
+
+// What robots and rigid bodies ran into during a substep, by index of the robot
+// or body
+struct SubstepContacts {
+    // Robots that hit something immovable stop there
+    collisions: Vec<Option<Hit>>,
+    // Robots that pushed another robot or were pushed by one
+    pushed: Vec<Option<Hit>>,
+    // Robots that pushed a rigid body
+    object_pushes: Vec<Option<Hit>>,
+    // Robots are held up by rigid bodies that did not get out of their way
+    held: Vec<Option<f32>>,
+    // First contact of every rigid body
+    object_contacts: Vec<Option<f32>>,
+    // Velocities of the rigid bodies, which change as contacts are resolved
+    robot_vels: Vec<(f32, f32, f32)>,
+    object_vels: Vec<(f32, f32, f32)>,
+}
+
+impl SubstepContacts {
+    fn new(robot_vels: Vec<(f32, f32, f32)>, object_vels: Vec<(f32, f32, f32)>) -> SubstepContacts {
+        let robots = robot_vels.len();
+        return SubstepContacts {
+            collisions: vec![None; robots],
+            pushed: vec![None; robots],
+            object_pushes: vec![None; robots],
+            held: vec![None; robots],
+            object_contacts: vec![None; object_vels.len()],
+            robot_vels,
+            object_vels,
+        };
+    }
+}
+
+// Resolves a contact between `a` at `pose` and `b` at `other_pose`. Returns the
+// contact point and the impulse on `b`, if they approach each other.
+fn resolve_contact(
+    a: &dyn Collidable,
+    pose: &(f32, f32, f32),
+    body: &ContactBody,
+    b: &dyn Collidable,
+    other_pose: &(f32, f32, f32),
+    other_body: &ContactBody,
+) -> Option<((f32, f32), (f32, f32))> {
+    let contact = a.contact_at(b, pose, other_pose)?;
+    let impulse = contact_impulse(body, other_body, contact.point, contact.normal)?;
+    return Some((contact.point, impulse));
+}
+
+// Moves `pose` by `dt` seconds of the velocity `change`
+fn shift_pose(pose: &mut (f32, f32, f32), change: (f32, f32, f32), dt: f32) {
+    pose.0 += change.0 * dt;
+    pose.1 += change.1 * dt;
+}
+
+fn add_vel(vel: &mut (f32, f32, f32), change: (f32, f32, f32)) {
+    vel.0 += change.0;
+    vel.1 += change.1;
+    vel.2 += change.2;
+}
+
+/// Refers to a robot in the simulation. Stays valid until that robot is removed,
+/// no matter what else is added or removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RobotHandler {
-    pub id: usize,
+    pub id: EntityId,
 }
 
 impl RobotHandler {
-    pub fn new(id: usize) -> RobotHandler {
+    pub fn new(id: EntityId) -> RobotHandler {
         RobotHandler { id }
     }
 }
@@ -27,6 +122,13 @@ pub enum SelectedObjectType {
     Other,
 }
 
+/// An object selected in the GUI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelectedObject {
+    pub object_type: SelectedObjectType,
+    pub id: EntityId,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum ObjectParameterType {
     Position(f32, f32),
@@ -54,464 +156,1464 @@ impl Default for FullInformation {
     }
 }
 
+/// A robot hitting something, reported once per tick for every robot and thing hit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollisionEvent {
+    pub time: f32,
+    pub robot_id: String,
+    /// The robot, wall or object that was hit
+    pub other: EntityId,
+    /// Name of what was hit, if it has one
+    pub other_name: Option<String>,
+    /// Contact point in the world frame
+    pub point: (f32, f32),
+    /// Contact normal, pointing from the robot to what it hit
+    pub normal: (f32, f32),
+    /// Pose of the robot at the contact
+    pub pose: (f32, f32, f32),
+}
+
+/// What changed in the simulation when the config was reloaded.
+#[derive(Debug, Clone, Default)]
+pub struct ReloadSummary {
+    pub robots_added: Vec<String>,
+    pub robots_updated: Vec<String>,
+    pub robots_removed: Vec<String>,
+    pub objects_added: usize,
+    pub objects_removed: usize,
+}
+
 pub struct SimulationHandler {
-    robots: Vec<Robot>,
-    objects: Vec<Box<dyn Genericbject>>,
+    // Every robot, wall and static object
+    entities: EntityStore,
 
-    walls: Vec<Wall>,
-    static_objects: Vec<StaticObj>,
+    // Robot models of the loaded config. Kept so that saving writes them back.
+    robot_models: Vec<RobotModelConfig>,
+
+    // How motion is integrated, from the loaded config
+    physics: PhysicsConfig,
 
     filepath: String,
+
+    // Fields set on top of the config every time it is loaded
+    overrides: Vec<(String, String)>,
+
+    // Robots of the config as it was last loaded. Used to find what changed on reload.
+    loaded_robots: Vec<RobotConfig>,
+
+    // Walls and static objects of the config as it was last loaded, with the
+    // entities they became. Used the same way as `loaded_robots`.
+    loaded_walls: Vec<(WallConfig, EntityId)>,
+    loaded_objects: Vec<(StaticObjConfig, EntityId)>,
+
+    // Every file of the loaded config with the time it was last modified
+    watched_files: Vec<(String, Option<SystemTime>)>,
+
+    // Simulation time in seconds. Advanced by DT every step.
+    time: f32,
+
+    // Robots that collided in the last step along with the pose they stopped at
+    collisions: Vec<(String, (f32, f32, f32))>,
+
+    // Everything robots hit in the last step
+    collision_events: Vec<CollisionEvent>,
+
+    metrics: MetricsCollector,
+
+    // If set, the noise of every robot is derived from this seed
+    seed: Option<u64>,
+
+    // Problems found in the config the last time it was loaded
+    config_errors: Vec<ConfigError>,
 }
 
 impl SimulationHandler {
     pub fn new() -> SimulationHandler {
         return SimulationHandler {
-            robots: Vec::new(),
-            objects: Vec::new(),
-
-            walls: Vec::new(),
-            static_objects: Vec::new(),
+            entities: EntityStore::new(),
+            robot_models: Vec::new(),
+            physics: PhysicsConfig::default(),
             filepath: "".to_string(),
+            overrides: Vec::new(),
+            loaded_robots: Vec::new(),
+            loaded_walls: Vec::new(),
+            loaded_objects: Vec::new(),
+            watched_files: Vec::new(),
+            time: 0.0,
+            collisions: Vec::new(),
+            collision_events: Vec::new(),
+            metrics: MetricsCollector::new(),
+            seed: None,
+            config_errors: Vec::new(),
         };
     }
 
-    pub fn from_file(filepath: String) -> (SimulationHandler, Vec<(String, RobotHandler)>) {
-        let file_path_copy = filepath.clone();
-        let config = get_config_from_file(filepath);
+    pub fn from_file(
+        filepath: String,
+    ) -> XironResult<(SimulationHandler, Vec<(String, RobotHandler)>)> {
+        let mut sim_handle = SimulationHandler::new();
+        sim_handle.load_file_path(filepath);
+
+        let config = sim_handle.read_config()?;
+        let robot_handles = sim_handle.load_config(&config)?;
+
+        return Ok((sim_handle, robot_handles));
+    }
 
+    /// Creates a simulation from a config that is already in memory. The config
+    /// is not validated, robots that can not be built are returned as errors.
+    pub fn from_config(
+        config: &Config,
+    ) -> XironResult<(SimulationHandler, Vec<(String, RobotHandler)>)> {
         let mut sim_handle = SimulationHandler::new();
-        sim_handle.load_file_path(file_path_copy.to_owned());
+        let robot_handles = sim_handle.load_config(config)?;
+
+        return Ok((sim_handle, robot_handles));
+    }
+
+    /// Adds everything in the config to the simulation.
+    fn load_config(&mut self, config: &Config) -> XironResult<Vec<(String, RobotHandler)>> {
+        // Robots are built first so that nothing is added if one of them is invalid
+        let robots: Vec<Robot> = config
+            .robots
+            .iter()
+            .map(Robot::from_config)
+            .collect::<XironResult<_>>()?;
 
         let mut robot_handles = Vec::new();
+        self.robot_models = config.robot_models.clone();
+        self.physics = config.physics;
+        self.loaded_robots = config.robots.clone();
 
-        match config {
-            Some(config) => {
-                for robot in config.robots.iter() {
-                    let handle = sim_handle.add_robot(Robot::new(
-                        robot.id.clone(),
-                        robot.pose,
-                        robot.vel,
-                        robot.lidar,
-                        robot.footprint.clone(),
-                        robot.drive_type,
-                        robot.add_noise,
-                    ));
-                    robot_handles.push(handle);
-                }
+        for robot in robots {
+            robot_handles.push(self.add_robot(robot)?);
+        }
 
-                for wall in config.walls.iter() {
-                    sim_handle.add_wall(Wall::new(wall.endpoints.clone()));
-                }
+        self.loaded_walls.clear();
+        self.loaded_objects.clear();
+        self.add_config_objects(&config.walls, &config.static_objects);
 
-                for obj in config.static_objects.iter() {
-                    sim_handle.add_static_obj(StaticObj::new(
-                        obj.center,
-                        obj.width,
-                        obj.height,
-                        obj.rotation,
-                    ));
-                }
+        return Ok(robot_handles);
+    }
+
+    /// Adds walls and static objects of the config and remembers which entities
+    /// they became.
+    fn add_config_objects(&mut self, walls: &[WallConfig], objects: &[StaticObjConfig]) {
+        for wall in walls.iter() {
+            let id = self
+                .entities
+                .insert(Entity::Wall(Wall::new(wall.endpoints.clone())));
+            self.loaded_walls.push((wall.clone(), id));
+        }
+
+        for obj in objects.iter() {
+            let id = self
+                .entities
+                .insert(Entity::StaticObj(StaticObj::from_config(obj)));
+            self.loaded_objects.push((obj.clone(), id));
+        }
+    }
+
+    /// Loads and validates the config at the file path, with everything it includes.
+    /// The errors, if any, are kept until the next load so that the GUI can show them.
+    fn read_config(&mut self) -> XironResult<Config> {
+        // Broken files are watched too, so that fixing them triggers a reload
+        self.watch_files();
+
+        match load_config_with_overrides(&self.filepath, &self.overrides) {
+            Ok(config) => {
+                self.config_errors.clear();
+                return Ok(config);
+            }
+            Err(errors) => {
+                self.config_errors = errors.clone();
+                return Err(XironError::InvalidConfig(errors));
             }
-            None => {}
         }
+    }
 
-        return (sim_handle, robot_handles);
+    /// Problems found in the config the last time it was loaded.
+    pub fn get_config_errors(&self) -> &Vec<ConfigError> {
+        return &self.config_errors;
     }
+
+    pub fn clear_config_errors(&mut self) {
+        self.config_errors.clear();
+    }
+
     pub fn load_file_path(&mut self, path: String) {
         self.filepath = path.clone();
     }
 
-    pub fn reset(&mut self) -> Vec<(String, RobotHandler)> {
-        self.robots.clear();
-        self.objects.clear();
-
-        self.walls.clear();
-        self.static_objects.clear();
-
-        let config_return = get_config_from_file(self.filepath.to_owned());
-        match config_return {
-            Some(config) => {
-                let mut all_robot_handlers: Vec<(String, RobotHandler)> = Vec::new();
-                for robot in config.robots.iter() {
-                    let handle = self.add_robot(Robot::new(
-                        robot.id.clone(),
-                        robot.pose,
-                        robot.vel,
-                        robot.lidar,
-                        robot.footprint.clone(),
-                        robot.drive_type,
-                        robot.add_noise,
-                    ));
-
-                    all_robot_handlers.push(handle);
-                    for wall in config.walls.iter() {
-                        self.add_wall(Wall::new(wall.endpoints.clone()));
-                    }
+    /// Sets fields of the config every time it is loaded, e.g. `robots.robot0.pose`
+    /// to `[1.0, 2.0, 0.0]`. Takes effect on the next reset.
+    pub fn set_overrides(&mut self, overrides: Vec<(String, String)>) {
+        self.overrides = overrides;
+    }
 
-                    for obj in config.static_objects.iter() {
-                        self.add_static_obj(StaticObj::new(
-                            obj.center,
-                            obj.width,
-                            obj.height,
-                            obj.rotation,
-                        ));
-                    }
+    /// Clears the simulation and loads the config at the file path again. If the
+    /// config is invalid the simulation is left empty.
+    pub fn reset(&mut self) -> XironResult<Vec<(String, RobotHandler)>> {
+        self.entities.clear();
+        self.robot_models.clear();
+
+        self.time = 0.0;
+        self.collisions.clear();
+        self.collision_events.clear();
+        self.metrics.clear();
+
+        let config = self.read_config()?;
+        return self.load_config(&config);
+    }
+
+    /// Remembers when every file of the config was last modified.
+    fn watch_files(&mut self) {
+        self.watched_files.clear();
+        if self.filepath.is_empty() {
+            return;
+        }
+
+        for file in included_files(&self.filepath) {
+            let modified = modified_time(&file);
+            self.watched_files.push((file, modified));
+        }
+    }
+
+    /// Whether any file of the loaded config was modified since it was loaded.
+    pub fn config_changed(&self) -> bool {
+        return self
+            .watched_files
+            .iter()
+            .any(|(file, modified)| modified_time(file) != *modified);
+    }
+
+    /// Reloads the config if any of its files changed. See `reload`.
+    pub fn reload_if_changed(&mut self) -> XironResult<Option<ReloadSummary>> {
+        if !self.config_changed() {
+            return Ok(None);
+        }
+        return self.reload().map(Some);
+    }
+
+    /// Loads the config again and applies the differences without resetting.
+    /// Robots whose entry in the config did not change keep running, changed robots
+    /// are spawned again and robots that are no longer in the config are removed.
+    /// Walls and static objects are handled the same way by comparing their entries,
+    /// so unchanged ones keep their state. Entities spawned at runtime and objects
+    /// deleted in the GUI are left alone. If the config is invalid, nothing changes
+    /// and the errors are kept for the GUI.
+    pub fn reload(&mut self) -> XironResult<ReloadSummary> {
+        let config = self.read_config()?;
+        let robots: Vec<Robot> = config
+            .robots
+            .iter()
+            .map(Robot::from_config)
+            .collect::<XironResult<_>>()?;
+        let mut summary = ReloadSummary::default();
+
+        let (walls, added_walls, removed_walls) =
+            diff_entries(std::mem::take(&mut self.loaded_walls), &config.walls);
+        let (objects, added_objects, removed_objects) = diff_entries(
+            std::mem::take(&mut self.loaded_objects),
+            &config.static_objects,
+        );
+        self.loaded_walls = walls;
+        self.loaded_objects = objects;
+
+        for id in removed_walls.iter().chain(removed_objects.iter()) {
+            if self.entities.remove(*id).is_some() {
+                summary.objects_removed += 1;
+            }
+        }
+        self.add_config_objects(&added_walls, &added_objects);
+        summary.objects_added = added_walls.len() + added_objects.len();
+
+        self.robot_models = config.robot_models.clone();
+        self.physics = config.physics;
+
+        for old in self.loaded_robots.clone().iter() {
+            if !config.robots.iter().any(|r| r.id == old.id) {
+                if self.remove_robot(&old.id).is_ok() {
+                    summary.robots_removed.push(old.id.clone());
                 }
+            }
+        }
+
+        for (robot, robot_obj) in config.robots.iter().zip(robots) {
+            let old = self.loaded_robots.iter().find(|r| r.id == robot.id);
+            if old == Some(robot) {
+                continue;
+            }
+
+            // Replaces robots with the same id, also ones spawned at runtime
+            let existed = self.remove_robot(&robot.id).is_ok();
+            self.add_robot(robot_obj)?;
+            match existed {
+                true => summary.robots_updated.push(robot.id.clone()),
+                false => summary.robots_added.push(robot.id.clone()),
+            }
+        }
+        self.loaded_robots = config.robots.clone();
 
-                return all_robot_handlers;
+        return Ok(summary);
+    }
+
+    /// Saves the current state of the simulation as a config. Saving over the
+    /// loaded file does not trigger a reload.
+    pub fn save_config(&mut self, path: &str) -> XironResult<()> {
+        let config = self.to_config();
+        save_config_to_file(&config, path)?;
+
+        if path == self.filepath {
+            self.loaded_robots = config.robots.clone();
+            self.loaded_walls = self.entities.wall_configs();
+            self.loaded_objects = self.entities.static_obj_configs();
+            self.watch_files();
+        }
+        return Ok(());
+    }
+
+    /// Captures the full state of the simulation, see `Snapshot`.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot {
+            time: self.time,
+            seed: self.seed,
+            robot_models: self.robot_models.clone(),
+            physics: self.physics,
+            robots: Vec::new(),
+            walls: Vec::new(),
+            static_objects: Vec::new(),
+        };
+
+        for (_, entity) in self.entities.iter() {
+            match entity {
+                Entity::Robot(robot) => snapshot.robots.push(RobotSnapshot::from_robot(robot)),
+                Entity::Wall(wall) => snapshot.walls.push(WallConfig {
+                    endpoints: wall.coords.clone(),
+                }),
+                Entity::StaticObj(obj) => snapshot
+                    .static_objects
+                    .push(StaticObjSnapshot::from_static_obj(obj)),
+            }
+        }
+
+        return snapshot;
+    }
+
+    /// Replaces the state of the simulation with the snapshot. Handlers from before
+    /// are invalid afterwards, use the returned ones instead. Metrics are collected
+    /// anew from the restored moment on. If a robot of the snapshot can not be
+    /// built, nothing changes.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> XironResult<Vec<(String, RobotHandler)>> {
+        let robots: Vec<Robot> = snapshot
+            .robots
+            .iter()
+            .map(|robot| robot.to_robot())
+            .collect::<XironResult<_>>()?;
+
+        self.entities.clear();
+        self.collisions.clear();
+        self.collision_events.clear();
+        self.metrics.clear();
+
+        self.time = snapshot.time;
+        self.seed = snapshot.seed;
+        self.robot_models = snapshot.robot_models.clone();
+        self.physics = snapshot.physics;
+
+        let mut robot_handles = Vec::new();
+        for robot in robots {
+            let name = robot.id.clone();
+            let id = self.entities.insert(Entity::Robot(robot));
+            robot_handles.push((name, RobotHandler::new(id)));
+        }
+        for wall in snapshot.walls.iter() {
+            self.entities
+                .insert(Entity::Wall(Wall::new(wall.endpoints.clone())));
+        }
+        for obj in snapshot.static_objects.iter() {
+            self.entities.insert(Entity::StaticObj(obj.to_static_obj()));
+        }
+        self.relink_loaded_objects();
+
+        return Ok(robot_handles);
+    }
+
+    /// Points the loaded walls and static objects at the restored entities with the
+    /// same entry. Entries without one are kept with their old id, like objects
+    /// deleted in the GUI.
+    fn relink_loaded_objects(&mut self) {
+        let mut walls = self.entities.wall_configs();
+        for (wall, id) in self.loaded_walls.iter_mut() {
+            if let Some(i) = walls.iter().position(|(w, _)| w == wall) {
+                *id = walls.swap_remove(i).1;
             }
-            None => {
-                return Vec::new();
+        }
+
+        let mut objects = self.entities.static_obj_configs();
+        for (obj, id) in self.loaded_objects.iter_mut() {
+            if let Some(i) = objects
+                .iter()
+                .position(|(o, _)| o.id == obj.id && o.width == obj.width && o.height == obj.height)
+            {
+                *id = objects.swap_remove(i).1;
             }
         }
     }
 
-    pub fn add_robot(&mut self, robot: Robot) -> (String, RobotHandler) {
+    /// Makes the noise of every robot, current and future, derive from `seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        for (_, robot) in self.entities.robots_mut() {
+            robot.seed(seed ^ hash_str(&robot.id));
+        }
+    }
+
+    /// Adds the robot. Robots are looked up by id, so it has to be unique.
+    pub fn add_robot(&mut self, mut robot: Robot) -> XironResult<(String, RobotHandler)> {
         let name = robot.id.clone();
+        if self.get_robot_handler(&name).is_some() {
+            return Err(XironError::RobotExists(name));
+        }
 
-        println!(
-            "
-        Added robot with name : {}. Radius: {}, Center: {}, {}",
-            name,
-            robot.get_bounds().0,
-            robot.get_center().0,
-            robot.get_center().1
-        );
+        if let Some(seed) = self.seed {
+            robot.seed(seed ^ hash_str(&robot.id));
+        }
+        let id = self.entities.insert(Entity::Robot(robot));
 
-        self.robots.push(robot);
+        return Ok((name, RobotHandler::new(id)));
+    }
 
-        return (
-            name,
-            RobotHandler {
-                id: self.robots.len() - 1,
-            },
-        );
+    /// Adds the wall. A wall needs at least 2 endpoints.
+    pub fn add_wall(&mut self, wall: Wall) -> XironResult<EntityId> {
+        if wall.coords.len() < 2 {
+            return Err(XironError::InvalidObject(format!(
+                "A wall needs at least 2 endpoints, got {}",
+                wall.coords.len()
+            )));
+        }
+
+        return Ok(self.entities.insert(Entity::Wall(wall)));
+    }
+
+    pub fn add_static_obj(&mut self, obj: StaticObj) -> EntityId {
+        return self.entities.insert(Entity::StaticObj(obj));
+    }
+
+    /// Returns the handler of the robot with the given id, if it exists.
+    pub fn get_robot_handler(&self, robot_id: &str) -> Option<RobotHandler> {
+        for (id, robot) in self.entities.robots() {
+            if robot.id == robot_id {
+                return Some(RobotHandler::new(id));
+            }
+        }
+        return None;
+    }
+
+    /// Returns the names and handlers of all robots currently in the simulation.
+    pub fn robot_handlers(&self) -> Vec<(String, RobotHandler)> {
+        let mut handlers = Vec::new();
+        for (id, robot) in self.entities.robots() {
+            handlers.push((robot.id.clone(), RobotHandler::new(id)));
+        }
+        return handlers;
+    }
+
+    /// Removes the robot with the given id. Handlers of other robots stay valid.
+    pub fn remove_robot(&mut self, robot_id: &str) -> XironResult<()> {
+        match self.get_robot_handler(robot_id) {
+            Some(handler) => {
+                self.entities.remove(handler.id);
+                return Ok(());
+            }
+            None => return Err(XironError::RobotNotFound(robot_id.to_string())),
+        }
     }
 
-    pub fn add_wall(&mut self, wall: Wall) {
-        self.objects.push(Box::new(wall.clone()));
+    /// Returns the robot, or None if it was removed.
+    pub fn get_robot(&self, robot: &RobotHandler) -> Option<&Robot> {
+        return self.entities.robot(robot.id);
+    }
 
-        println!("\n{:?}\n added", wall.coords);
+    pub fn get_robot_mut(&mut self, robot: &RobotHandler) -> Option<&mut Robot> {
+        return self.entities.robot_mut(robot.id);
+    }
 
-        self.walls.push(wall.clone());
+    fn robot(&self, robot: &RobotHandler) -> XironResult<&Robot> {
+        match self.entities.robot(robot.id) {
+            Some(r) => return Ok(r),
+            None => return Err(XironError::RobotNotFound(robot.id.to_string())),
+        }
     }
 
-    pub fn add_static_obj(&mut self, obj: StaticObj) {
-        self.objects.push(Box::new(obj.clone()));
+    fn robot_mut(&mut self, robot: &RobotHandler) -> XironResult<&mut Robot> {
+        match self.entities.robot_mut(robot.id) {
+            Some(r) => return Ok(r),
+            None => return Err(XironError::RobotNotFound(robot.id.to_string())),
+        }
+    }
 
-        self.static_objects.push(obj.clone());
+    /// Moves the robot to the given pose without checking for collisions.
+    pub fn teleport_robot(
+        &mut self,
+        robot: &RobotHandler,
+        pose: (f32, f32, f32),
+    ) -> XironResult<()> {
+        self.robot_mut(robot)?.place(&pose);
+        return Ok(());
     }
 
-    pub fn control(&mut self, robot: &RobotHandler, control: (f32, f32, f32)) {
-        self.robots[robot.id].control(control);
+    /// Returns the wall or static object, or None if it was removed.
+    pub fn get_object(&self, id: EntityId) -> Option<&dyn Genericbject> {
+        match self.entities.get(id) {
+            Some(entity) if entity.kind() != EntityKind::Robot => return Some(entity.object()),
+            _ => return None,
+        }
     }
 
-    pub fn sense(&self, robot: &RobotHandler) -> LiDARMsg {
-        let mut collidables_vector = Vec::new();
-        for obj in self.objects.iter() {
-            collidables_vector.push(obj.get_collidable());
+    /// Returns the id of the object with the given name, if it exists.
+    pub fn get_object_id(&self, name: &str) -> Option<EntityId> {
+        let name = Some(name.to_string());
+        for (id, obj) in self.entities.objects() {
+            if obj.get_name() == name {
+                return Some(id);
+            }
         }
-        for obj in self.robots.iter() {
-            collidables_vector.push(obj.get_collidable());
+        return None;
+    }
+
+    /// Returns the ids of all walls and static objects.
+    pub fn object_ids(&self) -> Vec<EntityId> {
+        return self.entities.objects().map(|(id, _)| id).collect();
+    }
+
+    /// Removes the wall or static object. Ids of other objects stay valid.
+    pub fn remove_object(&mut self, id: EntityId) -> XironResult<()> {
+        if self.get_object(id).is_none() {
+            return Err(XironError::ObjectNotFound(id.to_string()));
         }
-        return self.robots[robot.id].sense(&collidables_vector);
+        self.entities.remove(id);
+        return Ok(());
     }
 
-    pub fn get_pose(&self, robot: &RobotHandler) -> (f32, f32, f32) {
-        return self.robots[robot.id].pose;
+    /// Returns everything in the simulation.
+    pub fn entities(&self) -> &EntityStore {
+        return &self.entities;
     }
 
-    pub fn get_nearest_object(&self, x: f32, y: f32) -> (Option<SelectedObjectType>, i32) {
-        // First check all robots
-        let mut selected_object_type = None;
-        let mut nearest_index: i32 = -1;
+    /// Removes the named static object.
+    pub fn remove_static_obj(&mut self, obj_id: &str) -> XironResult<()> {
+        let name = Some(obj_id.to_string());
+        let ids: Vec<EntityId> = self
+            .entities
+            .objects()
+            .filter(|(_, obj)| obj.get_name() == name)
+            .map(|(id, _)| id)
+            .collect();
+
+        for id in ids.iter() {
+            self.entities.remove(*id);
+        }
 
-        for i in 0..self.robots.len() {
-            let robot = self.robots[i].clone();
-            let (rx, ry) = (robot.pose.0, robot.pose.1);
+        if ids.is_empty() {
+            return Err(XironError::ObjectNotFound(obj_id.to_string()));
+        }
+        return Ok(());
+    }
 
-            match robot.shape {
-                Footprint::Circular(r) => {
-                    if (rx - x).abs() < r.radius && (ry - y).abs() < r.radius {
-                        selected_object_type = Some(SelectedObjectType::Robot);
-                        nearest_index = i as i32;
+    /// Moves the named static object.
+    pub fn move_static_obj(
+        &mut self,
+        obj_id: &str,
+        center: (f32, f32),
+        rotation: f32,
+    ) -> XironResult<()> {
+        let name = Some(obj_id.to_string());
+        let mut found = false;
+
+        for (_, entity) in self.entities.iter_mut() {
+            if entity.kind() != EntityKind::Robot && entity.name() == name {
+                let obj = entity.object_mut();
+                obj.modify_position(center.0, center.1);
+                obj.modify_rotation(rotation);
+                found = true;
+            }
+        }
 
-                        return (selected_object_type, nearest_index);
-                    }
-                }
+        if !found {
+            return Err(XironError::ObjectNotFound(obj_id.to_string()));
+        }
+        return Ok(());
+    }
 
-                Footprint::Rectangular(c) => {
-                    if (rx - x).abs() < c.half_extents.x && (ry - y).abs() < c.half_extents.y {
-                        selected_object_type = Some(SelectedObjectType::Robot);
-                        nearest_index = i as i32;
+    /// Opens the named object if it is closed and closes it if it is open.
+    pub fn toggle_door(&mut self, obj_id: &str) -> XironResult<()> {
+        let name = Some(obj_id.to_string());
+        let mut found = false;
+
+        for (_, entity) in self.entities.iter_mut() {
+            if entity.kind() != EntityKind::Robot && entity.name() == name {
+                let obj = entity.object_mut();
+                let enabled = obj.is_enabled();
+                obj.set_enabled(!enabled);
+                found = true;
+            }
+        }
 
-                        return (selected_object_type, nearest_index);
-                    }
+        if !found {
+            return Err(XironError::ObjectNotFound(obj_id.to_string()));
+        }
+        return Ok(());
+    }
+
+    /// Returns the robots that collided in the last step and the pose they stopped at.
+    /// A collision is a hit on something that does not give way: a wall, a closed
+    /// object or a robot without a body. Pushing a robot or a rigid body is not a
+    /// collision, but it is reported in `get_collision_events`.
+    pub fn get_collisions(&self) -> &Vec<(String, (f32, f32, f32))> {
+        return &self.collisions;
+    }
+
+    /// Returns what the robots hit in the last step, with the contact point and normal.
+    pub fn get_collision_events(&self) -> &Vec<CollisionEvent> {
+        return &self.collision_events;
+    }
+
+    /// Distance from the robot to the nearest object or other robot.
+    pub fn clearance(&self, robot: &RobotHandler) -> XironResult<f32> {
+        let r = self.robot(robot)?;
+        let mut min_dist = f32::INFINITY;
+
+        for obj in self.entities.obstacles() {
+            min_dist = min_dist.min(r.distance_at(&*obj.get_collidable(), &r.pose));
+        }
+        for (id, other) in self.entities.robots() {
+            if id != robot.id {
+                min_dist = min_dist.min(r.distance_at(other, &r.pose));
+            }
+        }
+
+        return Ok(min_dist);
+    }
+
+    /// Returns the simulation time in seconds since the last reset.
+    pub fn get_time(&self) -> f32 {
+        return self.time;
+    }
+
+    pub fn control(&mut self, robot: &RobotHandler, control: (f32, f32, f32)) -> XironResult<()> {
+        self.robot_mut(robot)?.control(control);
+        return Ok(());
+    }
+
+    /// Commands the speeds of the wheels of the robot in rad/s, see `Robot::control_wheels`.
+    pub fn control_wheels(&mut self, robot: &RobotHandler, speeds: &[f32]) -> XironResult<()> {
+        return self.robot_mut(robot)?.control_wheels(speeds);
+    }
+
+    /// Ticks counted by the wheel encoders of the robot.
+    pub fn get_wheel_ticks(&self, robot: &RobotHandler) -> XironResult<Vec<i64>> {
+        return self.robot(robot)?.wheel_ticks();
+    }
+
+    /// The wheel encoders of the robot, along with the config of its wheels.
+    pub fn get_wheel_encoder(&self, robot: &RobotHandler) -> XironResult<WheelEncoder> {
+        let robot = self.robot(robot)?;
+        match &robot.wheels {
+            Some(wheels) => return Ok(wheels.clone()),
+            None => return Err(XironError::NoWheels(robot.id.clone())),
+        }
+    }
+
+    pub fn sense(&self, robot: &RobotHandler) -> XironResult<LiDARMsg> {
+        let sensing_robot = self.robot(robot)?;
+
+        return sensing_robot.sense(&self.entities.collidables());
+    }
+
+    pub fn get_pose(&self, robot: &RobotHandler) -> XironResult<(f32, f32, f32)> {
+        return Ok(self.robot(robot)?.pose);
+    }
+
+    /// Velocity the robot moves with, i.e. the commanded velocity after limits.
+    pub fn get_velocity(&self, robot: &RobotHandler) -> XironResult<(f32, f32, f32)> {
+        return Ok(self.robot(robot)?.vel);
+    }
+
+    /// Poses of the centers of the trailers of the robot, front to back.
+    pub fn get_trailer_poses(&self, robot: &RobotHandler) -> XironResult<Vec<(f32, f32, f32)>> {
+        return Ok(self.robot(robot)?.trailers.iter().map(|t| t.pose).collect());
+    }
+
+    /// Angle of every trailer of the robot relative to the vehicle in front of it.
+    pub fn get_trailer_angles(&self, robot: &RobotHandler) -> XironResult<Vec<f32>> {
+        return Ok(self.robot(robot)?.trailer_angles());
+    }
+
+    pub fn get_nearest_object(&self, x: f32, y: f32) -> Option<SelectedObject> {
+        // First check all robots
+        for (id, robot) in self.entities.robots() {
+            let (rx, ry) = (robot.pose.0, robot.pose.1);
+
+            let inside = match &robot.shape {
+                Footprint::Circular(r) => (rx - x).abs() < r.radius && (ry - y).abs() < r.radius,
+                Footprint::Rectangular(c) => {
+                    (rx - x).abs() < c.half_extents.x && (ry - y).abs() < c.half_extents.y
                 }
+            };
+            if inside {
+                return Some(SelectedObject {
+                    object_type: SelectedObjectType::Robot,
+                    id,
+                });
             }
         }
 
         // Now check for generic objects
         // This might not work really well for Walls. We dont what to do anything for walls as of now.
-        for i in 0..self.objects.len() {
-            let object = self.objects[i].as_ref();
-
+        for (id, object) in self.entities.objects() {
             if (x - object.get_pose().0).abs() < object.get_bounds().0
                 && (y - object.get_pose().1).abs() < object.get_bounds().1
             {
-                selected_object_type = Some(SelectedObjectType::Other);
-                nearest_index = i as i32;
-
-                return (selected_object_type, nearest_index);
+                return Some(SelectedObject {
+                    object_type: SelectedObjectType::Other,
+                    id,
+                });
             }
         }
 
-        return (selected_object_type, nearest_index);
+        return None;
+    }
+
+    /// Whether the selected object is still in the simulation.
+    pub fn selected_object_exists(&self, selected_object: Option<SelectedObject>) -> bool {
+        return self.selected_gui_object(selected_object).is_some();
+    }
+
+    /// Returns the selected object, or None if nothing is selected or the selected
+    /// object was removed.
+    fn selected_gui_object(
+        &self,
+        selected_object: Option<SelectedObject>,
+    ) -> Option<&dyn Genericbject> {
+        let selected = selected_object?;
+        return self.entities.get(selected.id).map(|e| e.object());
+    }
+
+    fn selected_gui_object_mut(
+        &mut self,
+        selected_object: Option<SelectedObject>,
+    ) -> Option<&mut dyn Genericbject> {
+        let selected = selected_object?;
+        return self.entities.get_mut(selected.id).map(|e| e.object_mut());
     }
 
     pub fn get_parameters_of_selected_object(
         &self,
-        selected_object: (Option<SelectedObjectType>, i32),
+        selected_object: Option<SelectedObject>,
         parameter_type: ObjectParameterType,
     ) -> ObjectParameterType {
-        let (selected_type, index) = selected_object;
-
-        match selected_type {
-            Some(t) => match t {
-                SelectedObjectType::Robot => match parameter_type {
-                    ObjectParameterType::Bounds(_w, _h) => {
-                        let bounds = self.robots[index as usize].get_bounds();
-                        return ObjectParameterType::Bounds(bounds.0, bounds.1);
-                    }
-                    ObjectParameterType::Rotation(_angle) => {
-                        let angle = self.robots[index as usize].get_rotation();
-                        return ObjectParameterType::Rotation(angle);
-                    }
-                    ObjectParameterType::Position(_x, _y) => {
-                        let position = self.robots[index as usize].get_center();
-
-                        return ObjectParameterType::Position(position.0, position.1);
-                    }
-                },
-                SelectedObjectType::Other => match parameter_type {
-                    ObjectParameterType::Bounds(_w, _h) => {
-                        let bounds = self.objects[index as usize].get_bounds();
+        let object = match self.selected_gui_object(selected_object) {
+            Some(object) => object,
+            None => return ObjectParameterType::Rotation(0.0),
+        };
 
-                        return ObjectParameterType::Bounds(bounds.0, bounds.1);
-                    }
-                    ObjectParameterType::Rotation(_angle) => {
-                        let angle = self.objects[index as usize].get_rotation();
-                        return ObjectParameterType::Rotation(angle);
-                    }
-                    ObjectParameterType::Position(_x, _y) => {
-                        let position = self.objects[index as usize].get_center();
-                        return ObjectParameterType::Position(position.0, position.1);
-                    }
-                },
-            },
-            None => {
-                return ObjectParameterType::Rotation(0.0);
+        match parameter_type {
+            ObjectParameterType::Bounds(_w, _h) => {
+                let bounds = object.get_bounds();
+                return ObjectParameterType::Bounds(bounds.0, bounds.1);
+            }
+            ObjectParameterType::Rotation(_angle) => {
+                return ObjectParameterType::Rotation(object.get_rotation());
+            }
+            ObjectParameterType::Position(_x, _y) => {
+                let position = object.get_center();
+                return ObjectParameterType::Position(position.0, position.1);
             }
         }
     }
 
     pub fn get_full_information_of_selected_object(
         &self,
-        selected_object: (Option<SelectedObjectType>, i32),
+        selected_object: Option<SelectedObject>,
     ) -> FullInformation {
-        let (selected_type, index) = selected_object;
-        match selected_type {
-            Some(obj) => match obj {
-                SelectedObjectType::Robot => {
-                    let robot = self.robots[index as usize].clone();
-                    let bounds = robot.get_bounds();
+        let selected = match selected_object {
+            Some(selected) => selected,
+            None => return FullInformation::default(),
+        };
 
+        match selected.object_type {
+            SelectedObjectType::Robot => match self.entities.robot(selected.id) {
+                Some(robot) => {
                     return FullInformation {
-                        id: robot.id,
+                        id: robot.id.clone(),
                         pose: robot.pose,
                         velocity: robot.vel,
-                        bounds: bounds,
+                        bounds: robot.get_bounds(),
                         drive_type: robot.drive_type.to_string(),
                     };
                 }
-                SelectedObjectType::Other => {
-                    let obj = &self.objects[index as usize];
+                None => return FullInformation::default(),
+            },
+            SelectedObjectType::Other => match self.get_object(selected.id) {
+                Some(obj) => {
                     return FullInformation {
-                        id: "Object".to_string(),
+                        id: obj.get_name().unwrap_or("Object".to_string()),
                         pose: obj.get_pose(),
                         velocity: (0.0, 0.0, 0.0),
                         bounds: obj.get_bounds(),
                         drive_type: "NA".to_string(),
                     };
                 }
+                None => return FullInformation::default(),
             },
-            None => {
-                return FullInformation::default();
-            }
         }
     }
 
     pub fn change_parameters_of_selected_object(
         &mut self,
-        selected_object: (Option<SelectedObjectType>, i32),
+        selected_object: Option<SelectedObject>,
         parameter_type: ObjectParameterType,
     ) {
-        let (selected_type, index) = selected_object;
+        let object = match self.selected_gui_object_mut(selected_object) {
+            Some(object) => object,
+            None => return,
+        };
 
-        match selected_type {
-            Some(t) => match t {
-                SelectedObjectType::Robot => match parameter_type {
-                    ObjectParameterType::Bounds(w, h) => {
-                        self.robots[index as usize].modify_bounds(w, h)
-                    }
-                    ObjectParameterType::Rotation(angle) => {
-                        self.robots[index as usize].modify_rotation(angle)
-                    }
-                    ObjectParameterType::Position(x, y) => {
-                        self.robots[index as usize].modify_position(x, y)
-                    }
-                },
-                SelectedObjectType::Other => match parameter_type {
-                    ObjectParameterType::Bounds(w, h) => {
-                        self.objects[index as usize].modify_bounds(w, h)
-                    }
-                    ObjectParameterType::Rotation(angle) => {
-                        self.objects[index as usize].modify_rotation(angle)
-                    }
-                    ObjectParameterType::Position(x, y) => {
-                        self.objects[index as usize].modify_position(x, y)
-                    }
-                },
-            },
-            None => {}
+        match parameter_type {
+            ObjectParameterType::Bounds(w, h) => object.modify_bounds(w, h),
+            ObjectParameterType::Rotation(angle) => object.modify_rotation(angle),
+            ObjectParameterType::Position(x, y) => object.modify_position(x, y),
+        }
+    }
+
+    /// Removes the selected object. Handlers and ids of everything else stay valid.
+    pub fn delete_selected_object(&mut self, selected_object: Option<SelectedObject>) {
+        if let Some(selected) = selected_object {
+            let _val = self.entities.remove(selected.id);
+        }
+    }
+
+    /// Integration steps per tick, see `PhysicsConfig::substeps`.
+    pub fn get_substeps(&self) -> usize {
+        return self.physics.substeps;
+    }
+
+    /// Sets the integration steps per tick. Values below 1 are treated as 1.
+    pub fn set_substeps(&mut self, substeps: usize) {
+        self.physics.substeps = substeps.max(1);
+    }
+
+    /// Advances the simulation by one tick of `DT`, split into the configured
+    /// number of substeps. Every substep moves the robots and resolves collisions.
+    pub fn step(&mut self) {
+        let substeps = self.physics.substeps.max(1);
+        let dt = DT / substeps as f32;
+
+        self.collisions.clear();
+        self.collision_events.clear();
+        for _ in 0..substeps {
+            self.substep(dt);
         }
+
+        self.time += DT;
+        self.update_metrics();
     }
 
-    pub fn delete_selected_object(&mut self, selected_object: (Option<SelectedObjectType>, i32)) {
-        let (selected_type, index) = selected_object;
+    // Moves the robots and rigid bodies by `dt` seconds and resolves their contacts
+    fn substep(&mut self, dt: f32) {
+        // Every robot with the pose it would end at
+        let mut next_poses: Vec<(EntityId, (f32, f32, f32))> = Vec::new();
+        let mut robot_vels: Vec<(f32, f32, f32)> = Vec::new();
+        for (id, robot) in self.entities.robots_mut() {
+            next_poses.push((id, robot.next_by(dt)));
+            robot_vels.push(robot.body.map_or((0.0, 0.0, 0.0), |b| b.vel));
+        }
+
+        // Every rigid body with the pose it starts at and the pose it would end at
+        let mut objects: Vec<(EntityId, BodyMotion)> = Vec::new();
+        let mut object_vels: Vec<(f32, f32, f32)> = Vec::new();
+        for id in self.entities.movable_ids() {
+            let Some(obj) = self.entities.static_obj(id) else {
+                continue;
+            };
+            let Some(body) = obj.body else {
+                continue;
+            };
+            let start_pose = obj.get_pose();
+            let end_pose = integrate_body(start_pose, body.vel, dt);
+            objects.push((id, (obj, start_pose, end_pose)));
+            object_vels.push(body.vel);
+        }
+        let mut contacts = SubstepContacts::new(robot_vels, object_vels);
+
+        self.push_robots(&mut next_poses, &mut contacts, dt);
+
+        let bodies = self.robot_motions(&next_poses);
+        self.hit_fixed_obstacles(&bodies, &mut contacts);
+        self.push_objects(&next_poses, &bodies, &mut objects, &mut contacts, dt);
+        self.resolve_object_contacts(&objects, &mut contacts);
 
-        match selected_type {
-            Some(t) => match t {
-                SelectedObjectType::Other => {
-                    let _val = self.objects.remove(index as usize);
+        // Rigid bodies move up to their first contact
+        for (k, toi) in contacts.object_contacts.iter().enumerate() {
+            let (_, (_, start_pose, end_pose)) = objects[k];
+            objects[k].1 .2 = interpolate_pose(&start_pose, &end_pose, toi.unwrap_or(1.0));
+        }
+        self.hold_robots(&next_poses, &bodies, &objects, &mut contacts, dt);
+
+        let object_poses: Vec<(EntityId, (f32, f32, f32))> =
+            objects.iter().map(|(id, motion)| (*id, motion.2)).collect();
+        let mut events: Vec<CollisionEvent> = Vec::new();
+
+        // Update robot positions and handle inelastic collisions
+        let restitution = self.physics.restitution;
+        for (i, (id, next_pose)) in next_poses.iter().enumerate() {
+            let Some((end_pose, response)) =
+                self.respond(i, *id, next_pose, &contacts, &mut events)
+            else {
+                continue;
+            };
+            let Some(robot) = self.entities.robot_mut(*id) else {
+                continue;
+            };
+            if let Some(body) = robot.body.as_mut() {
+                body.vel = contacts.robot_vels[i];
+            }
+            robot.step(&end_pose);
+
+            if let Some(hit) = contacts.collisions[i] {
+                let normal = hit.contact.map(|c| c.normal);
+                match (response, normal) {
+                    (CollisionResponse::Slide, Some(_)) => {}
+                    (CollisionResponse::Bounce, Some(normal)) => robot.bounce(normal, restitution),
+                    _ => robot.stop(),
                 }
-                SelectedObjectType::Robot => {
-                    let _val = self.robots.remove(index as usize);
+
+                // Pushing robots or rigid bodies is not a collision. Only the last
+                // collision of the robot in this tick is reported.
+                self.collisions.retain(|(id, _)| *id != robot.id);
+                self.collisions.push((robot.id.clone(), end_pose));
+            }
+        }
+
+        // A robot that keeps touching something is reported once per tick
+        for event in events {
+            let reported = self
+                .collision_events
+                .iter()
+                .any(|e| e.robot_id == event.robot_id && e.other == event.other);
+            if !reported {
+                self.collision_events.push(event);
+            }
+        }
+
+        // The rigid bodies slow down on the floor
+        for (k, (id, pose)) in object_poses.iter().enumerate() {
+            let Some(obj) = self.entities.static_obj_mut(*id) else {
+                continue;
+            };
+            obj.center = (pose.0, pose.1);
+            obj.rotation = pose.2;
+
+            let radius = obj.radius();
+            if let Some(body) = obj.body.as_mut() {
+                body.vel = contacts.object_vels[k];
+                body.apply_friction(dt, radius);
+            }
+        }
+    }
+
+    // Robots that can be pushed push each other apart, the others stop each other.
+    // A robot does not collide with its own trailers.
+    fn push_robots(
+        &self,
+        next_poses: &mut [(EntityId, (f32, f32, f32))],
+        contacts: &mut SubstepContacts,
+        dt: f32,
+    ) {
+        let bodies = self.robot_motions(next_poses);
+        for i in 0..next_poses.len() {
+            for (body, start_pose, end_pose) in bodies[i].iter() {
+                for j in (i + 1)..next_poses.len() {
+                    for (body2, start_pose2, end_pose2) in bodies[j].iter() {
+                        let toi = match body.collision_check_at_toi(
+                            *body2,
+                            start_pose,
+                            end_pose,
+                            Some(*start_pose2),
+                            Some(*end_pose2),
+                        ) {
+                            Some(toi) => toi,
+                            None => continue,
+                        };
+
+                        let pose = interpolate_pose(start_pose, end_pose, toi);
+                        let pose2 = interpolate_pose(start_pose2, end_pose2, toi);
+                        let (id, id2) = (next_poses[i].0, next_poses[j].0);
+                        let Some(robot_body) =
+                            self.robot_contact_body(id, &pose, start_pose, end_pose, dt)
+                        else {
+                            continue;
+                        };
+                        let Some(robot_body2) =
+                            self.robot_contact_body(id2, &pose2, start_pose2, end_pose2, dt)
+                        else {
+                            continue;
+                        };
+
+                        let hit = Hit {
+                            toi,
+                            other: id2,
+                            contact: body.contact_at(*body2, &pose, &pose2),
+                        };
+                        let hit2 = Hit {
+                            toi,
+                            other: id,
+                            contact: body2.contact_at(*body, &pose2, &pose),
+                        };
+
+                        // Robots that can not be pushed stop each other
+                        if robot_body.inverse_mass == 0.0 && robot_body2.inverse_mass == 0.0 {
+                            contacts.collisions[i] = first_hit(contacts.collisions[i], hit);
+                            contacts.collisions[j] = first_hit(contacts.collisions[j], hit2);
+                            continue;
+                        }
+
+                        if let Some((point, impulse)) =
+                            resolve_contact(*body, &pose, &robot_body, *body2, &pose2, &robot_body2)
+                        {
+                            let response = robot_body.response((-impulse.0, -impulse.1), point);
+                            add_vel(&mut contacts.robot_vels[i], (response.0, response.1, 0.0));
+                            let response2 = robot_body2.response(impulse, point);
+                            add_vel(&mut contacts.robot_vels[j], (response2.0, response2.1, 0.0));
+                            // They move on with their new velocities for the rest of the substep
+                            shift_pose(&mut next_poses[i].1, response, (1.0 - toi) * dt);
+                            shift_pose(&mut next_poses[j].1, response2, (1.0 - toi) * dt);
+                            contacts.pushed[i] = first_hit(contacts.pushed[i], hit);
+                            contacts.pushed[j] = first_hit(contacts.pushed[j], hit2);
+                        }
+                    }
                 }
-            },
-            None => {}
+            }
         }
     }
 
-    // TODO: Check if this can be simplified
-    pub fn step(&mut self) {
-        let mut next_poses: Vec<(f32, f32, f32)> = Vec::with_capacity(self.robots.len());
+    // Robots hit walls and closed objects, which do not give way
+    fn hit_fixed_obstacles(&self, bodies: &[Vec<BodyMotion>], contacts: &mut SubstepContacts) {
+        for (i, robot_bodies) in bodies.iter().enumerate() {
+            for (body, start_pose, end_pose) in robot_bodies.iter() {
+                for (id, object) in self.entities.fixed_obstacles() {
+                    let other = object.get_collidable();
+                    if let Some(toi) =
+                        body.collision_check_at_toi(&*other, start_pose, end_pose, None, None)
+                    {
+                        let pose = interpolate_pose(start_pose, end_pose, toi);
+                        let hit = Hit {
+                            toi,
+                            other: id,
+                            contact: body.contact_at(&*other, &pose, &other.get_pose()),
+                        };
+                        contacts.collisions[i] = first_hit(contacts.collisions[i], hit);
+                    }
+                }
+            }
+        }
+    }
+
+    // Robots push rigid bodies out of their way. Whether the bodies get away is
+    // only known once they hit what is behind them.
+    fn push_objects(
+        &self,
+        next_poses: &[(EntityId, (f32, f32, f32))],
+        bodies: &[Vec<BodyMotion>],
+        objects: &mut [(EntityId, BodyMotion)],
+        contacts: &mut SubstepContacts,
+        dt: f32,
+    ) {
+        for i in 0..next_poses.len() {
+            for (body, start_pose, end_pose) in bodies[i].iter() {
+                for (k, (obj_id, motion)) in objects.iter_mut().enumerate() {
+                    let (obj, obj_start_pose, obj_end_pose) = *motion;
+                    let toi = match body.collision_check_at_toi(
+                        obj,
+                        start_pose,
+                        end_pose,
+                        Some(obj_start_pose),
+                        Some(obj_end_pose),
+                    ) {
+                        Some(toi) => toi,
+                        None => continue,
+                    };
 
-        for robot in &mut self.robots {
-            let next_pose = robot.next();
-            next_poses.push(next_pose);
+                    let pose = interpolate_pose(start_pose, end_pose, toi);
+                    let obj_pose = interpolate_pose(&obj_start_pose, &obj_end_pose, toi);
+                    let Some(robot_body) =
+                        self.robot_contact_body(next_poses[i].0, &pose, start_pose, end_pose, dt)
+                    else {
+                        continue;
+                    };
+                    let Some(obj_body) =
+                        self.object_contact_body(*obj_id, &obj_pose, contacts.object_vels[k])
+                    else {
+                        continue;
+                    };
+                    if let Some((point, impulse)) =
+                        resolve_contact(*body, &pose, &robot_body, obj, &obj_pose, &obj_body)
+                    {
+                        let hit = Hit {
+                            toi,
+                            other: *obj_id,
+                            contact: body.contact_at(obj, &pose, &obj_pose),
+                        };
+                        contacts.object_pushes[i] = first_hit(contacts.object_pushes[i], hit);
+                        let response = robot_body.response((-impulse.0, -impulse.1), point);
+                        add_vel(&mut contacts.robot_vels[i], (response.0, response.1, 0.0));
+                        add_vel(
+                            &mut contacts.object_vels[k],
+                            obj_body.response(impulse, point),
+                        );
+                        // The body moves on with its new velocity for the rest of the substep
+                        motion.2 =
+                            integrate_body(obj_pose, contacts.object_vels[k], (1.0 - toi) * dt);
+                    }
+                }
+            }
         }
-        let mut collisions: Vec<Option<f32>> = vec![None; self.robots.len()];
+    }
+
+    // The rigid bodies hit walls and closed objects, and bounce off each other
+    fn resolve_object_contacts(
+        &self,
+        objects: &[(EntityId, BodyMotion)],
+        contacts: &mut SubstepContacts,
+    ) {
+        for (k, &(id, (obj, start_pose, end_pose))) in objects.iter().enumerate() {
+            for (_, object) in self.entities.fixed_obstacles() {
+                let other = object.get_collidable();
+                let toi =
+                    match obj.collision_check_at_toi(&*other, &start_pose, &end_pose, None, None) {
+                        Some(toi) => toi,
+                        None => continue,
+                    };
 
-        // Check collisions with objects and other robots
-        for i in 0..self.robots.len() {
-            let robot = &self.robots[i];
-            let start_pose = robot.get_pose();
-            let end_pose = next_poses[i];
+                let pose = interpolate_pose(&start_pose, &end_pose, toi);
+                let Some(body) = self.object_contact_body(id, &pose, contacts.object_vels[k])
+                else {
+                    continue;
+                };
+                let fixed = ContactBody::fixed((0.0, 0.0), (0.0, 0.0, 0.0));
+                if let Some((point, impulse)) =
+                    resolve_contact(obj, &pose, &body, &*other, &other.get_pose(), &fixed)
+                {
+                    add_vel(
+                        &mut contacts.object_vels[k],
+                        body.response((-impulse.0, -impulse.1), point),
+                    );
+                    contacts.object_contacts[k] = first_toi(contacts.object_contacts[k], toi);
+                }
+            }
+        }
 
-            // Object Collisions
-            for object in &self.objects {
-                if let Some(toi) = robot.collision_check_at_toi(
-                    &*object.get_collidable(),
+        for k in 0..objects.len() {
+            for l in (k + 1)..objects.len() {
+                let (id, (obj, start_pose, end_pose)) = objects[k];
+                let (other_id, (other, other_start_pose, other_end_pose)) = objects[l];
+                let toi = match obj.collision_check_at_toi(
+                    other,
                     &start_pose,
                     &end_pose,
-                    None,
-                    None,
+                    Some(other_start_pose),
+                    Some(other_end_pose),
                 ) {
-                    collisions[i] = Some(collisions[i].map_or(toi, |t| t.min(toi)));
+                    Some(toi) => toi,
+                    None => continue,
+                };
+
+                let pose = interpolate_pose(&start_pose, &end_pose, toi);
+                let other_pose = interpolate_pose(&other_start_pose, &other_end_pose, toi);
+                let Some(body) = self.object_contact_body(id, &pose, contacts.object_vels[k])
+                else {
+                    continue;
+                };
+                let Some(other_body) =
+                    self.object_contact_body(other_id, &other_pose, contacts.object_vels[l])
+                else {
+                    continue;
+                };
+                if let Some((point, impulse)) =
+                    resolve_contact(obj, &pose, &body, other, &other_pose, &other_body)
+                {
+                    add_vel(
+                        &mut contacts.object_vels[k],
+                        body.response((-impulse.0, -impulse.1), point),
+                    );
+                    add_vel(
+                        &mut contacts.object_vels[l],
+                        other_body.response(impulse, point),
+                    );
+                    contacts.object_contacts[k] = first_toi(contacts.object_contacts[k], toi);
+                    contacts.object_contacts[l] = first_toi(contacts.object_contacts[l], toi);
                 }
             }
+        }
+    }
 
-            // Robot Collisions
-            for j in 0..self.robots.len() {
-                if i != j {
-                    let robot2 = &self.robots[j];
-                    let start_pose2 = robot2.get_pose();
-                    let end_pose2 = next_poses[j];
-                    if let Some(toi) = robot.collision_check_at_toi(
-                        robot2,
-                        &start_pose,
-                        &end_pose,
-                        Some(start_pose2),
-                        Some(end_pose2),
+    // Robots are held up by rigid bodies that did not get out of their way
+    fn hold_robots(
+        &self,
+        next_poses: &[(EntityId, (f32, f32, f32))],
+        bodies: &[Vec<BodyMotion>],
+        objects: &[(EntityId, BodyMotion)],
+        contacts: &mut SubstepContacts,
+        dt: f32,
+    ) {
+        for i in 0..next_poses.len() {
+            for (body, start_pose, end_pose) in bodies[i].iter() {
+                for (k, &(obj_id, (obj, obj_start_pose, obj_end_pose))) in
+                    objects.iter().enumerate()
+                {
+                    let toi = match body.collision_check_at_toi(
+                        obj,
+                        start_pose,
+                        end_pose,
+                        Some(obj_start_pose),
+                        Some(obj_end_pose),
                     ) {
-                        collisions[i] = Some(collisions[i].map_or(toi, |t| t.min(toi)));
-                        collisions[j] = Some(collisions[j].map_or(toi, |t| t.min(toi)));
+                        Some(toi) => toi,
+                        None => continue,
+                    };
+
+                    let pose = interpolate_pose(start_pose, end_pose, toi);
+                    let obj_pose = interpolate_pose(&obj_start_pose, &obj_end_pose, toi);
+                    let Some(robot_body) =
+                        self.robot_contact_body(next_poses[i].0, &pose, start_pose, end_pose, dt)
+                    else {
+                        continue;
+                    };
+                    let Some(obj_body) =
+                        self.object_contact_body(obj_id, &obj_pose, contacts.object_vels[k])
+                    else {
+                        continue;
+                    };
+                    if resolve_contact(*body, &pose, &robot_body, obj, &obj_pose, &obj_body)
+                        .is_some()
+                    {
+                        contacts.held[i] = first_toi(contacts.held[i], toi);
                     }
                 }
             }
         }
+    }
 
-        // Update robot positions and handle inelastic collisions
-        for i in 0..self.robots.len() {
-            let robot = &mut self.robots[i];
-            let start_pose = robot.get_pose();
-            let end_pose = next_poses[i];
+    // Where the `i`th robot of the substep ends up and how it responds to what it
+    // hit. Adds an event for everything it hit to `events`.
+    fn respond(
+        &self,
+        i: usize,
+        id: EntityId,
+        next_pose: &(f32, f32, f32),
+        contacts: &SubstepContacts,
+        events: &mut Vec<CollisionEvent>,
+    ) -> Option<((f32, f32, f32), CollisionResponse)> {
+        let time = self.time + DT;
+        let robot = self.entities.robot(id)?;
+        let start_pose = robot.get_pose();
+        let response = robot
+            .collision_response
+            .unwrap_or(self.physics.collision_response);
+
+        // Pushing something holds the robot up at the contact, but it keeps driving
+        let toi = contacts.held[i].unwrap_or(1.0);
+        let mut end_pose = interpolate_pose(&start_pose, next_pose, toi);
+
+        let hits = [
+            contacts.collisions[i],
+            contacts.pushed[i],
+            contacts.object_pushes[i],
+        ];
+        for hit in hits.into_iter().flatten() {
+            if let Some(contact) = hit.contact {
+                events.push(CollisionEvent {
+                    time,
+                    robot_id: robot.id.clone(),
+                    other: hit.other,
+                    other_name: self.entities.get(hit.other).and_then(|e| e.name()),
+                    point: contact.point,
+                    normal: contact.normal,
+                    pose: interpolate_pose(&start_pose, next_pose, hit.toi),
+                });
+            }
+        }
 
-            if let Some(toi) = collisions[i] {
-                // Collision occurred, move robot to collision point and stop
-                let collision_pose = interpolate_pose(&start_pose, &end_pose, toi);
-                robot.step(&collision_pose);
-                robot.control((0.0, 0.0, 0.0));
-            } else {
-                // No collision, move to next pose
-                robot.step(&end_pose);
+        // Hitting something that does not give way ends the motion at the contact
+        if let Some(hit) = contacts.collisions[i] {
+            end_pose = interpolate_pose(&start_pose, next_pose, toi.min(hit.toi));
+            if let (CollisionResponse::Slide, Some(contact)) = (response, hit.contact) {
+                end_pose = self.slide(robot, hit.other, &end_pose, next_pose, contact.normal);
             }
         }
+        return Some((end_pose, response));
     }
 
-    pub fn collision_status_at(&self, roboth: &RobotHandler, pose: &(f32, f32, f32)) -> bool {
-        let robot = self.robots[roboth.id].clone();
-        for object in self.objects.iter() {
-            let collision = robot.collision_check_at(&*object.get_collidable(), pose, None);
-            if collision {
-                return true;
+    // Every body of every robot, i.e. the robot and its trailers, with the pose it
+    // starts the substep at and the pose it ends at
+    fn robot_motions(
+        &self,
+        next_poses: &[(EntityId, (f32, f32, f32))],
+    ) -> Vec<Vec<BodyMotion<'_>>> {
+        let mut bodies: Vec<Vec<BodyMotion>> = Vec::with_capacity(next_poses.len());
+        for (id, next_pose) in next_poses.iter() {
+            // A robot that is gone has no bodies, so the indices still match
+            let Some(robot) = self.entities.robot(*id) else {
+                bodies.push(Vec::new());
+                continue;
+            };
+            let mut robot_bodies: Vec<BodyMotion> = vec![(robot, robot.get_pose(), *next_pose)];
+            let trailer_poses = robot.trailer_poses_at(next_pose);
+            for (trailer, (_, end_pose)) in robot.trailers.iter().zip(trailer_poses) {
+                robot_bodies.push((trailer, trailer.pose, end_pose));
             }
+            bodies.push(robot_bodies);
         }
-        return false;
+        return bodies;
     }
 
-    pub fn to_config(&self) -> Config {
-        let mut robot_config_vectors: Vec<RobotConfig> = Vec::new();
-        for robot in self.robots.iter() {
-            robot_config_vectors.push(robot.into_config());
+    // Where a robot that hit `hit` with the normal `normal` at `contact_pose` ends
+    // up when it slides along it with what is left of its motion to `end_pose`
+    fn slide(
+        &self,
+        robot: &Robot,
+        hit: EntityId,
+        contact_pose: &(f32, f32, f32),
+        end_pose: &(f32, f32, f32),
+        normal: (f32, f32),
+    ) -> (f32, f32, f32) {
+        let mut rest = (end_pose.0 - contact_pose.0, end_pose.1 - contact_pose.1);
+        let into = rest.0 * normal.0 + rest.1 * normal.1;
+        if into > 0.0 {
+            rest.0 -= into * normal.0;
+            rest.1 -= into * normal.1;
         }
+        let slide_pose = (contact_pose.0 + rest.0, contact_pose.1 + rest.1, end_pose.2);
 
-        let mut wall_config_vector: Vec<WallConfig> = Vec::new();
-
-        for wall in self.walls.iter() {
-            wall_config_vector.push(WallConfig {
-                endpoints: wall.coords.clone(),
-            });
+        // The slide ends at the next obstacle
+        let mut toi: f32 = 1.0;
+        for (id, object) in self.entities.fixed_obstacles() {
+            if id == hit {
+                continue;
+            }
+            if let Some(t) = robot.collision_check_at_toi(
+                &*object.get_collidable(),
+                contact_pose,
+                &slide_pose,
+                None,
+                None,
+            ) {
+                toi = toi.min(t);
+            }
         }
+        return interpolate_pose(contact_pose, &slide_pose, toi);
+    }
 
-        let mut static_objects_config: Vec<StaticObjConfig> = Vec::new();
+    // A rigid body at `pose` moving with `vel`, as the contact solver sees it
+    fn object_contact_body(
+        &self,
+        id: EntityId,
+        pose: &(f32, f32, f32),
+        vel: (f32, f32, f32),
+    ) -> Option<ContactBody> {
+        let obj = self.entities.static_obj(id)?;
+        let body = obj.body?;
+        return Some(ContactBody {
+            inverse_mass: body.inverse_mass(),
+            inverse_inertia: body.inverse_inertia(obj.width, obj.height),
+            vel,
+            center: (pose.0, pose.1),
+            restitution: body.config.restitution,
+        });
+    }
+
+    // A body of a robot at `pose`, while it moves from `start_pose` to `end_pose`.
+    // Robots are not turned by pushes and robots without a body are not moved at all.
+    fn robot_contact_body(
+        &self,
+        id: EntityId,
+        pose: &(f32, f32, f32),
+        start_pose: &(f32, f32, f32),
+        end_pose: &(f32, f32, f32),
+        dt: f32,
+    ) -> Option<ContactBody> {
+        let robot = self.entities.robot(id)?;
+        let vel = (
+            (end_pose.0 - start_pose.0) / dt,
+            (end_pose.1 - start_pose.1) / dt,
+            normalise(end_pose.2 - start_pose.2) / dt,
+        );
 
-        for obj in self.static_objects.iter() {
-            static_objects_config.push(StaticObjConfig {
-                center: obj.center.clone(),
-                width: obj.width,
-                height: obj.height,
-                rotation: obj.rotation,
-            });
+        let mut body = ContactBody::fixed((pose.0, pose.1), vel);
+        if let Some(rigid_body) = robot.body {
+            body.inverse_mass = rigid_body.inverse_mass();
+            body.restitution = rigid_body.config.restitution;
         }
+        return Some(body);
+    }
 
-        Config {
-            robots: robot_config_vectors,
-            walls: wall_config_vector,
-            static_objects: static_objects_config,
+    fn update_metrics(&mut self) {
+        for id in self.entities.robot_ids() {
+            let clearance = self
+                .clearance(&RobotHandler::new(id))
+                .unwrap_or(f32::INFINITY);
+            let Some(robot) = self.entities.robot(id) else {
+                continue;
+            };
+            let collision = self
+                .collisions
+                .iter()
+                .find(|(name, _)| *name == robot.id)
+                .map(|(_, pose)| *pose);
+
+            // The commanded velocity, not the one after limits and actuator dynamics
+            self.metrics.record(
+                &robot.id,
+                self.time,
+                robot.pose,
+                robot.cmd_vel,
+                collision,
+                clearance,
+            );
+        }
+    }
+
+    /// Metrics collected for every robot since the last reset.
+    pub fn get_metrics(&self) -> &MetricsCollector {
+        return &self.metrics;
+    }
+
+    /// Writes the collected metrics to `path` as CSV or JSON, based on the extension.
+    pub fn export_metrics(&self, path: &str) -> XironResult<()> {
+        self.metrics.export(path)?;
+        return Ok(());
+    }
+
+    pub fn collision_status_at(
+        &self,
+        roboth: &RobotHandler,
+        pose: &(f32, f32, f32),
+    ) -> XironResult<bool> {
+        let robot = self.robot(roboth)?;
+        for object in self.entities.obstacles() {
+            let collision = robot.collision_check_at(&*object.get_collidable(), pose, None)?;
+            if collision {
+                return Ok(true);
+            }
         }
+        return Ok(false);
+    }
+
+    pub fn to_config(&self) -> Config {
+        return self
+            .entities
+            .to_config(self.robot_models.clone(), self.physics);
     }
 
     pub fn draw_lines(&self) {
@@ -570,30 +1672,48 @@ impl SimulationHandler {
     }
 
     pub fn draw(&self) {
-        for robot in self.robots.iter() {
+        for (_, robot) in self.entities.robots() {
             robot.draw(Self::tf_function);
         }
 
-        for object in self.objects.iter() {
+        for (_, object) in self.entities.objects() {
             object.draw(Self::tf_function);
         }
     }
 
-    pub fn draw_bounds_of_selected_object(
-        &self,
-        selected_object: (Option<SelectedObjectType>, i32),
-    ) {
-        let (selected_object_type, index) = selected_object;
-        match selected_object_type {
-            Some(object) => match object {
-                SelectedObjectType::Robot => {
-                    self.robots[index as usize].draw_bounds(Self::tf_function);
-                }
-                SelectedObjectType::Other => {
-                    self.objects[index as usize].draw_bounds(Self::tf_function);
-                }
-            },
-            None => {}
+    pub fn draw_bounds_of_selected_object(&self, selected_object: Option<SelectedObject>) {
+        if let Some(object) = self.selected_gui_object(selected_object) {
+            object.draw_bounds(Self::tf_function);
+        }
+    }
+
+    /// Draws the hits of the LiDAR of the selected robot.
+    pub fn draw_scan_of_selected_object(&self, selected_object: Option<SelectedObject>) {
+        let selected = match selected_object {
+            Some(selected) => selected,
+            None => return,
+        };
+        let robot = match self.entities.robot(selected.id) {
+            Some(robot) => robot,
+            None => return,
+        };
+        let scan = match self.sense(&RobotHandler::new(selected.id)) {
+            Ok(scan) => scan,
+            Err(_) => return,
+        };
+
+        let dtheta = (scan.angle_max - scan.angle_min) / scan.num_readings as f32;
+        for (i, range) in scan.values.iter().enumerate() {
+            // Readings at max range did not hit anything
+            if *range >= LIDAR_MAX_RANGE - 0.1 {
+                continue;
+            }
+            let angle = robot.pose.2 + scan.angle_min + i as f32 * dtheta;
+            let hit = Self::tf_function((
+                robot.pose.0 + range * angle.cos(),
+                robot.pose.1 + range * angle.sin(),
+            ));
+            draw_circle(hit.0, hit.1, 2.0, RED);
         }
     }
 
@@ -627,3 +1747,118 @@ impl SimulationHandler {
 unsafe impl Sync for SimulationHandler {}
 
 unsafe impl Send for SimulationHandler {}
+
+/// Time the file was last modified, if it exists.
+/// Matches the loaded entries one to one with the entries of the new config.
+/// Returns the entries that are kept with their entities, the new entries to add
+/// and the entities to remove.
+fn diff_entries<T: PartialEq + Clone>(
+    loaded: Vec<(T, EntityId)>,
+    entries: &[T],
+) -> (Vec<(T, EntityId)>, Vec<T>, Vec<EntityId>) {
+    let mut unmatched = loaded;
+    let mut kept = Vec::new();
+    let mut added = Vec::new();
+
+    for entry in entries.iter() {
+        match unmatched.iter().position(|(e, _)| e == entry) {
+            Some(i) => kept.push(unmatched.remove(i)),
+            None => added.push(entry.clone()),
+        }
+    }
+    let removed = unmatched.into_iter().map(|(_, id)| id).collect();
+
+    return (kept, added, removed);
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    return std::fs::metadata(path).and_then(|m| m.modified()).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::DriveType;
+
+    const WORLD: &str = "robots: []\nwalls:\n- endpoints: [[0, 0], [0, 5]]\n- endpoints: [[5, 0], [5, 5]]\nstatic_objects:\n- center: [2, 2]\n  width: 0.5\n  height: 0.5\n  rotation: 0.0\n  id: box0\n- center: [3, 3]\n  width: 0.5\n  height: 0.5\n  rotation: 0.0\n  id: box1\n";
+
+    fn write_world(test: &str, text: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("xiron_{}_{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("world.yaml");
+        std::fs::write(&path, text).unwrap();
+        return path.to_string_lossy().to_string();
+    }
+
+    #[test]
+    fn metrics_export_the_commanded_velocity() {
+        let mut sim = SimulationHandler::new();
+        let robot = |id: &str, y: f32, drive_type| {
+            Robot::new(
+                id.to_string(),
+                (0.0, y, 0.0),
+                (0.0, 0.0, 0.0),
+                false,
+                vec![0.2],
+                drive_type,
+                false,
+            )
+            .unwrap()
+        };
+        let (_, diff) = sim
+            .add_robot(robot("diff", 0.0, DriveType::Differential))
+            .unwrap();
+        let (_, omni) = sim
+            .add_robot(robot("omni", 5.0, DriveType::Omnidrive))
+            .unwrap();
+
+        sim.control(&diff, (0.5, 0.0, 0.25)).unwrap();
+        sim.control(&omni, (0.3, 0.4, 0.0)).unwrap();
+        for _ in 0..10 {
+            sim.step();
+        }
+
+        let metrics: serde_json::Value =
+            serde_json::from_str(&sim.get_metrics().to_json()).unwrap();
+        let value = |i: usize, field: &str| metrics[i][field].as_f64().unwrap();
+        assert_eq!(metrics[0]["id"], "diff");
+        assert!((value(0, "avg_linear_vel") - 0.5).abs() < 1e-6);
+        assert!((value(0, "avg_angular_vel") - 0.25).abs() < 1e-6);
+        assert_eq!(metrics[1]["id"], "omni");
+        assert!((value(1, "avg_linear_vel") - 0.5).abs() < 1e-6);
+        assert!((value(1, "max_linear_vel") - 0.5).abs() < 1e-6);
+        assert_eq!(value(1, "avg_angular_vel"), 0.0);
+    }
+
+    #[test]
+    fn reload_only_touches_changed_objects() {
+        let path = write_world("reload", WORLD);
+        let (mut sim, _) = SimulationHandler::from_file(path.clone()).unwrap();
+
+        let box0 = sim.get_object_id("box0").unwrap();
+        sim.move_static_obj("box0", (2.5, 2.0), 0.0).unwrap();
+        sim.remove_static_obj("box1").unwrap();
+        let spawned = sim.add_static_obj(StaticObj::new((4.0, 1.0), 0.5, 0.5, 0.0));
+
+        std::fs::write(&path, WORLD.replace("[[5, 0], [5, 5]]", "[[6, 0], [6, 5]]")).unwrap();
+        let summary = sim.reload().unwrap();
+
+        assert_eq!(summary.objects_added, 1);
+        assert_eq!(summary.objects_removed, 1);
+        assert_eq!(sim.get_object_id("box0"), Some(box0));
+        assert_eq!(sim.get_object(box0).unwrap().get_pose().0, 2.5);
+        assert_eq!(sim.get_object_id("box1"), None);
+        assert!(sim.entities().get(spawned).is_some());
+
+        let walls: Vec<Vec<(f32, f32)>> = sim
+            .entities()
+            .wall_configs()
+            .into_iter()
+            .map(|(wall, _)| wall.endpoints)
+            .collect();
+        assert_eq!(
+            walls,
+            vec![vec![(0.0, 0.0), (0.0, 5.0)], vec![(6.0, 0.0), (6.0, 5.0)]]
+        );
+    }
+}
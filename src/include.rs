@@ -0,0 +1,549 @@
+//! Composition of world configs from several files.
+//!
+//! A config can `include` other configs. Included files are merged first, in
+//! order, and the including file is merged on top of them:
+//! - `robots` are merged by `id` and `robot_models` by `name`. An entry with the
+//!   same id as an earlier one only overrides the fields it sets.
+//! - `static_objects` with an `id` replace the earlier object with that id.
+//!   Everything else, and all `walls`, are appended.
+//!
+//! A file that is included more than once, e.g. by two included files, is only
+//! merged the first time.
+//!
+//! Overrides set single fields after everything is merged. Their key is a dot
+//! separated path where list entries are picked by id, name or index, e.g.
+//! `robots.robot0.pose` or `walls.0.endpoints`.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde_yaml::{Mapping, Value};
+
+use crate::validation::{locate, ConfigError, PathSegment};
+
+/// Environment variable with overrides, separated by `;`, e.g.
+/// `XIRON_OVERRIDES="robots.robot0.pose=[1.0, 2.0, 0.0];robots.robot1.add_noise=true"`.
+pub const OVERRIDES_ENV_VAR: &str = "XIRON_OVERRIDES";
+
+/// Lists that are merged entry by entry, with the field that identifies an entry.
+const MERGED_LISTS: [(&str, Option<&str>); 4] = [
+    ("robot_models", Some("name")),
+    ("robots", Some("id")),
+    ("static_objects", Some("id")),
+    ("walls", None),
+];
+
+/// The merged document of a config and all the files it includes.
+#[derive(Clone, Debug)]
+pub struct ConfigSource {
+    pub value: Value,
+
+    // Path and text of every file of the tree. The first one is the root.
+    files: Vec<(String, String)>,
+
+    // File and index that every entry of the merged lists was last defined at
+    origins: BTreeMap<String, Vec<(usize, usize)>>,
+}
+
+impl ConfigSource {
+    /// Reads the config at `path` and everything it includes.
+    pub fn from_file(path: &str) -> Result<ConfigSource, Vec<ConfigError>> {
+        let mut source = ConfigSource::new();
+
+        let mut stack = Vec::new();
+        let mut merged = HashSet::new();
+        source.merge_file(Path::new(path), &mut stack, &mut merged, None)?;
+
+        return Ok(source);
+    }
+
+    fn new() -> ConfigSource {
+        ConfigSource {
+            value: Value::Mapping(Mapping::new()),
+            files: Vec::new(),
+            origins: BTreeMap::new(),
+        }
+    }
+
+    /// Paths of every file of the include tree, starting with the root.
+    pub fn files(&self) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        for (path, _) in self.files.iter() {
+            if !files.contains(path) {
+                files.push(path.clone());
+            }
+        }
+        return files;
+    }
+
+    /// Text of the root file, if nothing else was merged into it. Parsing it
+    /// directly gives better error locations.
+    pub fn single_source(&self) -> Option<&str> {
+        if self.files.len() == 1 {
+            return Some(&self.files[0].1);
+        }
+        return None;
+    }
+
+    /// Finds the file, line and column of the entry at `path`.
+    pub fn locate(&self, path: &[PathSegment]) -> (String, Option<(usize, usize)>) {
+        let mut path = path.to_vec();
+        let mut file = 0;
+
+        if let (Some(PathSegment::Key(list)), Some(PathSegment::Index(i))) =
+            (path.first(), path.get(1))
+        {
+            if let Some(&(origin_file, origin_index)) =
+                self.origins.get(list).and_then(|o| o.get(*i))
+            {
+                file = origin_file;
+                path[1] = PathSegment::Index(origin_index);
+            }
+        }
+
+        let (name, text) = &self.files[file];
+        return (name.clone(), locate(text, &path));
+    }
+
+    /// Sets the field at the dot separated `key` to `value`, which is parsed as YAML.
+    pub fn apply_override(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value: Value =
+            serde_yaml::from_str(value).map_err(|e| format!("Invalid value: {}", e))?;
+
+        let segments: Vec<&str> = key.split('.').collect();
+        let mut current = &mut self.value;
+        for (i, segment) in segments.iter().enumerate() {
+            let last = i == segments.len() - 1;
+            current = match current {
+                Value::Mapping(mapping) => {
+                    let name = Value::String(segment.to_string());
+                    if last {
+                        mapping.insert(name, value);
+                        return Ok(());
+                    }
+                    match mapping.get_mut(&name) {
+                        Some(child) => child,
+                        None => return Err(format!("No field {}", segment)),
+                    }
+                }
+                Value::Sequence(entries) => {
+                    let index = match segment.parse::<usize>() {
+                        Ok(index) => Some(index),
+                        Err(_) => entries.iter().position(|e| entry_name(e) == Some(*segment)),
+                    };
+                    let entry = match index.and_then(|index| entries.get_mut(index)) {
+                        Some(entry) => entry,
+                        None => return Err(format!("No entry {}", segment)),
+                    };
+                    if last {
+                        *entry = value;
+                        return Ok(());
+                    }
+                    entry
+                }
+                _ => {
+                    return Err(format!(
+                        "Can not set {} on a value that is not a list or mapping",
+                        segment
+                    ))
+                }
+            };
+        }
+
+        return Err("Empty override key".to_string());
+    }
+
+    /// Error for a file that could not be included, located at its entry in the
+    /// `include` list of the including file.
+    fn include_error(
+        &self,
+        name: &str,
+        included_at: Option<(usize, usize)>,
+        message: String,
+    ) -> Vec<ConfigError> {
+        let (file, location) = match included_at {
+            Some((file, index)) => {
+                let (parent, text) = &self.files[file];
+                let path = [key("include"), PathSegment::Index(index)];
+                (
+                    parent.clone(),
+                    locate(text, &path).or(locate(text, &path[..1])),
+                )
+            }
+            None => (name.to_string(), None),
+        };
+
+        return vec![ConfigError {
+            file,
+            line: location.map(|l| l.0),
+            column: location.map(|l| l.1),
+            path: "include".to_string(),
+            message,
+        }];
+    }
+
+    fn merge_file(
+        &mut self,
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+        merged: &mut HashSet<PathBuf>,
+        included_at: Option<(usize, usize)>,
+    ) -> Result<(), Vec<ConfigError>> {
+        let name = path.to_string_lossy().to_string();
+
+        let canonical = path.canonicalize().unwrap_or(path.to_path_buf());
+        if stack.contains(&canonical) {
+            return Err(self.include_error(
+                &name,
+                included_at,
+                format!("Include cycle: {} includes itself", name),
+            ));
+        }
+
+        // A file included by several files is only merged once
+        if !merged.insert(canonical.clone()) {
+            return Ok(());
+        }
+
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                let message = format!("Could not open file {}: {}", name, e);
+                return Err(self.include_error(&name, included_at, message));
+            }
+        };
+
+        // Files are kept even if they are broken, so that they can be watched
+        let file = self.files.len();
+        self.files.push((name.clone(), text.clone()));
+
+        let mut value: Value = match serde_yaml::from_str(&text) {
+            Ok(value) => value,
+            Err(e) => {
+                let location = e.location();
+                return Err(vec![ConfigError {
+                    file: name,
+                    line: location.as_ref().map(|l| l.line()),
+                    column: location.as_ref().map(|l| l.column()),
+                    path: "".to_string(),
+                    message: e.to_string(),
+                }]);
+            }
+        };
+
+        let mapping = match &mut value {
+            Value::Mapping(mapping) => mapping,
+            Value::Null => return Ok(()),
+            _ => {
+                return Err(vec![ConfigError {
+                    file: name,
+                    line: Some(1),
+                    column: Some(1),
+                    path: "".to_string(),
+                    message: "A config has to be a mapping".to_string(),
+                }]);
+            }
+        };
+
+        // Included files are merged first so that this file overrides them
+        let includes = match mapping.remove("include") {
+            Some(Value::String(include)) => vec![include],
+            Some(Value::Sequence(includes)) if includes.iter().all(|i| i.is_string()) => includes
+                .iter()
+                .filter_map(|i| i.as_str())
+                .map(|i| i.to_string())
+                .collect(),
+            Some(_) => {
+                let location = locate(&self.files[file].1, &[key("include")]);
+                return Err(vec![ConfigError {
+                    file: name,
+                    line: location.map(|l| l.0),
+                    column: location.map(|l| l.1),
+                    path: "include".to_string(),
+                    message: "include has to be a path or a list of paths".to_string(),
+                }]);
+            }
+            None => Vec::new(),
+        };
+
+        stack.push(canonical);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut errors = Vec::new();
+        for (i, include) in includes.iter().enumerate() {
+            // Includes are relative to the including file
+            let include_path = match Path::new(include).is_relative() {
+                true => dir.join(include),
+                false => PathBuf::from(include),
+            };
+            match self.merge_file(&include_path, stack, merged, Some((file, i))) {
+                Ok(_) => {}
+                Err(e) => errors.extend(e),
+            }
+        }
+        stack.pop();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        self.merge_value(value, file);
+        return Ok(());
+    }
+
+    fn merge_value(&mut self, value: Value, file: usize) {
+        let mapping = match value {
+            Value::Mapping(mapping) => mapping,
+            _ => return,
+        };
+        let target = match &mut self.value {
+            Value::Mapping(target) => target,
+            _ => return,
+        };
+
+        for (name, value) in mapping.into_iter() {
+            let list = MERGED_LISTS
+                .iter()
+                .find(|(list, _)| name.as_str() == Some(*list));
+
+            match (list, value) {
+                (Some((list, id_field)), Value::Sequence(entries)) => {
+                    let origins = self.origins.entry(list.to_string()).or_default();
+                    let merged = target.entry(name).or_insert(Value::Sequence(Vec::new()));
+                    if !merged.is_sequence() {
+                        *merged = Value::Sequence(Vec::new());
+                    }
+                    let merged = match merged {
+                        Value::Sequence(merged) => merged,
+                        _ => continue,
+                    };
+
+                    for (index, entry) in entries.into_iter().enumerate() {
+                        let existing = id_field.and_then(|field| {
+                            let id = entry.get(field)?.as_str()?;
+                            merged
+                                .iter()
+                                .position(|e| e.get(field).and_then(|v| v.as_str()) == Some(id))
+                        });
+
+                        match existing {
+                            Some(i) => {
+                                merge_entry(&mut merged[i], entry);
+                                origins[i] = (file, index);
+                            }
+                            None => {
+                                merged.push(entry);
+                                origins.push((file, index));
+                            }
+                        }
+                    }
+                }
+                // Anything else is replaced
+                (_, value) => {
+                    target.insert(name, value);
+                }
+            }
+        }
+    }
+}
+
+fn key(name: &str) -> PathSegment {
+    PathSegment::Key(name.to_string())
+}
+
+/// The id or name of a list entry.
+fn entry_name(entry: &Value) -> Option<&str> {
+    return entry
+        .get("id")
+        .or(entry.get("name"))
+        .and_then(|v| v.as_str());
+}
+
+/// Overrides the fields of `entry` with the fields of `other`.
+fn merge_entry(entry: &mut Value, other: Value) {
+    match (entry.as_mapping_mut(), other) {
+        (Some(fields), Value::Mapping(other)) => {
+            for (key, value) in other.into_iter() {
+                fields.insert(key, value);
+            }
+        }
+        (_, other) => *entry = other,
+    }
+}
+
+/// Paths of every file of the include tree of the config at `path`, as far as
+/// they can be read. Always contains `path` itself.
+pub fn included_files(path: &str) -> Vec<String> {
+    let mut source = ConfigSource::new();
+    let _ = source.merge_file(Path::new(path), &mut Vec::new(), &mut HashSet::new(), None);
+
+    let mut files = source.files();
+    if files.is_empty() {
+        files.push(path.to_string());
+    }
+    return files;
+}
+
+/// Parses overrides of the form `key=value`.
+pub fn parse_override(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) => return Ok((key.trim().to_string(), value.trim().to_string())),
+        None => return Err(format!("Override {} is not of the form key=value", s)),
+    }
+}
+
+/// Overrides set in the `XIRON_OVERRIDES` environment variable.
+pub fn overrides_from_env() -> Result<Vec<(String, String)>, String> {
+    let mut overrides = Vec::new();
+    if let Ok(value) = std::env::var(OVERRIDES_ENV_VAR) {
+        for s in value.split(';').filter(|s| !s.trim().is_empty()) {
+            overrides.push(parse_override(s)?);
+        }
+    }
+    return Ok(overrides);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes `files` to a fresh directory and returns the path of the first one
+    fn write_files(test: &str, files: &[(&str, &str)]) -> String {
+        let dir = std::env::temp_dir().join(format!("xiron_{}_{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, text) in files.iter() {
+            std::fs::write(dir.join(name), text).unwrap();
+        }
+        return dir.join(files[0].0).to_string_lossy().to_string();
+    }
+
+    #[test]
+    fn override_splits_at_the_first_equals_sign() {
+        assert_eq!(
+            parse_override(" robots.robot0.pose = [1.0, 2.0, 0.0] "),
+            Ok((
+                "robots.robot0.pose".to_string(),
+                "[1.0, 2.0, 0.0]".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_override("a.b=x=y"),
+            Ok(("a.b".to_string(), "x=y".to_string()))
+        );
+    }
+
+    #[test]
+    fn override_without_value_is_rejected() {
+        assert!(parse_override("robots.robot0.pose").is_err());
+    }
+
+    #[test]
+    fn override_sets_entries_by_name_and_index() {
+        let mut source = ConfigSource {
+            value: serde_yaml::from_str(
+                "robots:\n- id: robot0\n  radius: 0.5\nwalls:\n- endpoints: []\n",
+            )
+            .unwrap(),
+            files: Vec::new(),
+            origins: BTreeMap::new(),
+        };
+
+        source
+            .apply_override("robots.robot0.radius", "0.3")
+            .unwrap();
+        source
+            .apply_override("walls.0.endpoints", "[[0, 0], [1, 0]]")
+            .unwrap();
+        assert_eq!(source.value["robots"][0]["radius"], Value::from(0.3));
+        assert_eq!(source.value["walls"][0]["endpoints"][1][0], Value::from(1));
+
+        assert!(source
+            .apply_override("robots.robot1.radius", "0.3")
+            .is_err());
+    }
+
+    #[test]
+    fn included_entries_are_merged_by_id() {
+        let path = write_files(
+            "merge",
+            &[
+                (
+                    "main.yaml",
+                    "include: base.yaml\nrobots:\n- id: robot0\n  add_noise: true\n- id: robot1\nwalls:\n- endpoints: [[1, 0], [1, 1]]\n",
+                ),
+                (
+                    "base.yaml",
+                    "robots:\n- id: robot0\n  add_noise: false\n  lidar: true\nwalls:\n- endpoints: [[0, 0], [0, 1]]\n",
+                ),
+            ],
+        );
+        let source = ConfigSource::from_file(&path).unwrap();
+
+        let robots = source.value["robots"].as_sequence().unwrap();
+        assert_eq!(robots.len(), 2);
+        assert_eq!(robots[0]["add_noise"], Value::from(true));
+        assert_eq!(robots[0]["lidar"], Value::from(true));
+        assert_eq!(source.value["walls"].as_sequence().unwrap().len(), 2);
+        assert_eq!(source.files().len(), 2);
+    }
+
+    #[test]
+    fn include_cycles_are_rejected() {
+        let path = write_files(
+            "cycle",
+            &[
+                ("a.yaml", "include: b.yaml\n"),
+                ("b.yaml", "include: a.yaml\n"),
+            ],
+        );
+        let errors = ConfigSource::from_file(&path).unwrap_err();
+        assert!(errors[0].message.contains("cycle"));
+        assert_eq!(errors[0].line, Some(1));
+    }
+
+    #[test]
+    fn files_included_twice_are_merged_once() {
+        let path = write_files(
+            "diamond",
+            &[
+                (
+                    "main.yaml",
+                    "include: [a.yaml, b.yaml]
+",
+                ),
+                (
+                    "a.yaml",
+                    "include: base.yaml
+robots:
+- id: robot0
+",
+                ),
+                (
+                    "b.yaml",
+                    "include: base.yaml
+robots:
+- id: robot1
+",
+                ),
+                (
+                    "base.yaml",
+                    "walls:
+- endpoints: [[0, 0], [0, 1]]
+static_objects:
+- center: [1, 1]
+  width: 0.5
+  height: 0.5
+  rotation: 0.0
+",
+                ),
+            ],
+        );
+        let source = ConfigSource::from_file(&path).unwrap();
+
+        assert_eq!(source.value["walls"].as_sequence().unwrap().len(), 1);
+        assert_eq!(
+            source.value["static_objects"].as_sequence().unwrap().len(),
+            1
+        );
+        assert_eq!(source.value["robots"].as_sequence().unwrap().len(), 2);
+        assert_eq!(source.files().len(), 4);
+    }
+}
@@ -12,10 +12,20 @@ pub mod algorithms;
 pub mod behaviour;
 pub mod camera_handler;
 pub mod comms;
+pub mod entity;
+pub mod error;
+pub mod experiment;
+pub mod generator;
 pub mod gui_interface;
 pub mod handler;
+pub mod include;
+pub mod metrics;
 pub mod object;
 pub mod parameter;
 pub mod parser;
 pub mod prelude;
+pub mod scenario;
+pub mod session;
+pub mod snapshot;
 pub mod utils;
+pub mod validation;
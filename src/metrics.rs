@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::parameter::DT;
+
+// Robots moving less than this per step are counted as stopped
+const STOPPED_THRESHOLD: f32 = 1e-4;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollisionRecord {
+    pub time: f32,
+    pub position: (f32, f32),
+}
+
+/// Navigation metrics of a single robot over a run.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RobotMetrics {
+    pub id: String,
+    pub path_length: f32,
+    pub time_moving: f32,
+    pub time_stopped: f32,
+    pub collisions: Vec<CollisionRecord>,
+    pub min_clearance: f32,
+    pub avg_linear_vel: f32,
+    pub max_linear_vel: f32,
+    pub avg_angular_vel: f32,
+    pub max_angular_vel: f32,
+    pub avg_jerk: f32,
+    pub max_jerk: f32,
+
+    // Running state needed to update the metrics
+    #[serde(skip)]
+    samples: u32,
+    #[serde(skip)]
+    jerk_samples: u32,
+    #[serde(skip)]
+    last_pose: Option<(f32, f32, f32)>,
+    #[serde(skip)]
+    last_speed: Option<f32>,
+    #[serde(skip)]
+    last_acc: Option<f32>,
+}
+
+impl RobotMetrics {
+    pub fn new(id: String) -> RobotMetrics {
+        RobotMetrics {
+            id,
+            path_length: 0.0,
+            time_moving: 0.0,
+            time_stopped: 0.0,
+            collisions: Vec::new(),
+            min_clearance: f32::INFINITY,
+            avg_linear_vel: 0.0,
+            max_linear_vel: 0.0,
+            avg_angular_vel: 0.0,
+            max_angular_vel: 0.0,
+            avg_jerk: 0.0,
+            max_jerk: 0.0,
+            samples: 0,
+            jerk_samples: 0,
+            last_pose: None,
+            last_speed: None,
+            last_acc: None,
+        }
+    }
+
+    fn record(
+        &mut self,
+        time: f32,
+        pose: (f32, f32, f32),
+        cmd_vel: (f32, f32, f32),
+        collision: Option<(f32, f32, f32)>,
+        clearance: f32,
+    ) {
+        if let Some(last_pose) = self.last_pose {
+            let dx = pose.0 - last_pose.0;
+            let dy = pose.1 - last_pose.1;
+            let dist = (dx * dx + dy * dy).sqrt();
+
+            self.path_length += dist;
+            if dist > STOPPED_THRESHOLD {
+                self.time_moving += DT;
+            } else {
+                self.time_stopped += DT;
+            }
+
+            // Jerk is taken from the actual motion, not from the commands
+            let speed = dist / DT;
+            if let Some(last_speed) = self.last_speed {
+                let acc = (speed - last_speed) / DT;
+                if let Some(last_acc) = self.last_acc {
+                    let jerk = ((acc - last_acc) / DT).abs();
+                    self.jerk_samples += 1;
+                    self.avg_jerk += (jerk - self.avg_jerk) / self.jerk_samples as f32;
+                    self.max_jerk = self.max_jerk.max(jerk);
+                }
+                self.last_acc = Some(acc);
+            }
+            self.last_speed = Some(speed);
+        }
+        self.last_pose = Some(pose);
+
+        let linear = cmd_vel.0.hypot(cmd_vel.1);
+        let angular = cmd_vel.2.abs();
+        self.samples += 1;
+        self.avg_linear_vel += (linear - self.avg_linear_vel) / self.samples as f32;
+        self.avg_angular_vel += (angular - self.avg_angular_vel) / self.samples as f32;
+        self.max_linear_vel = self.max_linear_vel.max(linear);
+        self.max_angular_vel = self.max_angular_vel.max(angular);
+
+        if let Some(c) = collision {
+            self.collisions.push(CollisionRecord {
+                time,
+                position: (c.0, c.1),
+            });
+        }
+
+        self.min_clearance = self.min_clearance.min(clearance);
+    }
+}
+
+/// Collects `RobotMetrics` for every robot in the simulation.
+#[derive(Clone, Debug, Default)]
+pub struct MetricsCollector {
+    robots: Vec<RobotMetrics>,
+    index: HashMap<String, usize>,
+}
+
+impl MetricsCollector {
+    pub fn new() -> MetricsCollector {
+        MetricsCollector {
+            robots: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.robots.clear();
+        self.index.clear();
+    }
+
+    /// Records one step of the robot with the given id.
+    pub fn record(
+        &mut self,
+        id: &str,
+        time: f32,
+        pose: (f32, f32, f32),
+        cmd_vel: (f32, f32, f32),
+        collision: Option<(f32, f32, f32)>,
+        clearance: f32,
+    ) {
+        let i = match self.index.get(id) {
+            Some(i) => *i,
+            None => {
+                self.robots.push(RobotMetrics::new(id.to_string()));
+                self.index.insert(id.to_string(), self.robots.len() - 1);
+                self.robots.len() - 1
+            }
+        };
+        self.robots[i].record(time, pose, cmd_vel, collision, clearance);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&RobotMetrics> {
+        return self.index.get(id).map(|i| &self.robots[*i]);
+    }
+
+    pub fn robots(&self) -> &Vec<RobotMetrics> {
+        return &self.robots;
+    }
+
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string_pretty(&self.robots).unwrap_or_default();
+    }
+
+    /// One row per robot. Collisions are written as `time:x:y` separated by `;`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for robot in self.robots.iter() {
+            csv.push_str(&robot_csv_row(robot));
+            csv.push('\n');
+        }
+        return csv;
+    }
+
+    /// Writes the metrics to `path`. The format is CSV if the file ends in `.csv`
+    /// and JSON otherwise.
+    pub fn export(&self, path: &str) -> std::io::Result<()> {
+        let data = if path.ends_with(".csv") {
+            self.to_csv()
+        } else {
+            self.to_json()
+        };
+
+        let mut file = std::fs::File::create(path)?;
+        file.write_all(data.as_bytes())?;
+        return Ok(());
+    }
+}
+
+pub const CSV_HEADER: &str = "id,path_length,time_moving,time_stopped,num_collisions,collisions,min_clearance,avg_linear_vel,max_linear_vel,avg_angular_vel,max_angular_vel,avg_jerk,max_jerk";
+
+pub fn robot_csv_row(robot: &RobotMetrics) -> String {
+    let collisions: Vec<String> = robot
+        .collisions
+        .iter()
+        .map(|c| format!("{:.3}:{:.3}:{:.3}", c.time, c.position.0, c.position.1))
+        .collect();
+
+    return format!(
+        "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+        robot.id,
+        robot.path_length,
+        robot.time_moving,
+        robot.time_stopped,
+        robot.collisions.len(),
+        collisions.join(";"),
+        robot.min_clearance,
+        robot.avg_linear_vel,
+        robot.max_linear_vel,
+        robot.avg_angular_vel,
+        robot.max_angular_vel,
+        robot.avg_jerk,
+        robot.max_jerk
+    );
+}
@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::parameter::DT;
+use crate::parser::{ActuatorConfig, ActuatorOrder};
+
+/// Motors of a robot, which turn the commanded velocity into the velocity the
+/// robot moves with. Applies the latency, deadband and response of its config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Actuator {
+    pub config: ActuatorConfig,
+
+    // Commands waiting for the latency to pass, oldest first
+    pending: VecDeque<(f32, f32, f32)>,
+
+    // Command that left the queue this tick
+    #[serde(default)]
+    delayed: (f32, f32, f32),
+
+    // Time since the start of the current tick, for updates split into substeps
+    #[serde(default)]
+    elapsed: f32,
+
+    // Rate of change of the output of a second order response
+    rate: (f32, f32, f32),
+}
+
+impl Actuator {
+    /// Motors that have been receiving `command` for a long time.
+    pub fn new(config: ActuatorConfig, command: (f32, f32, f32)) -> Actuator {
+        return Actuator {
+            config,
+            pending: VecDeque::from(vec![command; config.latency]),
+            delayed: command,
+            elapsed: 0.0,
+            rate: (0.0, 0.0, 0.0),
+        };
+    }
+
+    /// Feeds the command of this tick and returns the velocity after `dt`,
+    /// starting at `current`.
+    pub fn update(
+        &mut self,
+        command: (f32, f32, f32),
+        current: (f32, f32, f32),
+        dt: f32,
+    ) -> (f32, f32, f32) {
+        // The queue moves on once per tick, however many substeps the tick has
+        if self.elapsed <= 0.0 {
+            self.pending.push_back(command);
+            self.delayed = self.pending.pop_front().unwrap_or(command);
+        }
+        self.elapsed += dt;
+        if self.elapsed + 0.5 * dt >= DT {
+            self.elapsed = 0.0;
+        }
+        let command = self.delayed;
+
+        let deadband = |c: f32| {
+            if c.abs() < self.config.deadband {
+                0.0
+            } else {
+                c
+            }
+        };
+        let target = (
+            deadband(command.0),
+            deadband(command.1),
+            deadband(command.2),
+        );
+
+        let tau = self.config.time_constant;
+        if tau.is_nan() || tau <= 0.0 {
+            self.rate = (0.0, 0.0, 0.0);
+            return target;
+        }
+
+        match self.config.order {
+            ActuatorOrder::First => {
+                // Exact step response, so that it is stable for any time constant
+                let alpha = 1.0 - (-dt / tau).exp();
+                let response = |x: f32, u: f32| x + (u - x) * alpha;
+                return (
+                    response(current.0, target.0),
+                    response(current.1, target.1),
+                    response(current.2, target.2),
+                );
+            }
+            ActuatorOrder::Second => {
+                // x'' = (u - x) / tau^2 - 2 * damping * x' / tau, integrated with implicit
+                // Euler, which is stable even for time constants shorter than a tick
+                let h = dt / tau;
+                let damping = self.config.damping;
+                let response = |x: f32, v: f32, u: f32| {
+                    let v = (v + h * (u - x) / tau) / (1.0 + h * h + 2.0 * damping * h);
+                    (x + v * dt, v)
+                };
+
+                let (x0, v0) = response(current.0, self.rate.0, target.0);
+                let (x1, v1) = response(current.1, self.rate.1, target.1);
+                let (x2, v2) = response(current.2, self.rate.2, target.2);
+                self.rate = (v0, v1, v2);
+                return (x0, x1, x2);
+            }
+        }
+    }
+
+    /// Replaces pending commands with `command` and keeps the motors steady at it.
+    pub fn hold(&mut self, command: (f32, f32, f32)) {
+        for pending in self.pending.iter_mut() {
+            *pending = command;
+        }
+        self.delayed = command;
+        self.rate = (0.0, 0.0, 0.0);
+    }
+
+    /// Drops pending commands and brings the motors to rest.
+    pub fn reset(&mut self) {
+        for command in self.pending.iter_mut() {
+            *command = (0.0, 0.0, 0.0);
+        }
+        self.delayed = (0.0, 0.0, 0.0);
+        self.rate = (0.0, 0.0, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(order: ActuatorOrder, time_constant: f32) -> ActuatorConfig {
+        return ActuatorConfig {
+            order,
+            time_constant,
+            damping: 1.0,
+            deadband: 0.0,
+            latency: 0,
+        };
+    }
+
+    #[test]
+    fn zero_time_constant_follows_instantly() {
+        let mut actuator = Actuator::new(config(ActuatorOrder::First, 0.0), (0.0, 0.0, 0.0));
+        let output = actuator.update((1.0, -0.5, 2.0), (0.0, 0.0, 0.0), DT);
+        assert_eq!(output, (1.0, -0.5, 2.0));
+    }
+
+    #[test]
+    fn deadband_drops_small_commands() {
+        let mut config = config(ActuatorOrder::First, 0.0);
+        config.deadband = 0.1;
+        let mut actuator = Actuator::new(config, (0.0, 0.0, 0.0));
+        let output = actuator.update((0.05, -0.2, -0.09), (0.0, 0.0, 0.0), DT);
+        assert_eq!(output, (0.0, -0.2, 0.0));
+    }
+
+    #[test]
+    fn latency_delays_commands_by_whole_ticks() {
+        let mut config = config(ActuatorOrder::First, 0.0);
+        config.latency = 2;
+        let mut actuator = Actuator::new(config, (0.0, 0.0, 0.0));
+
+        let mut outputs = Vec::new();
+        for _ in 0..3 {
+            // Two substeps make one tick
+            let first = actuator.update((1.0, 0.0, 0.0), (0.0, 0.0, 0.0), 0.5 * DT);
+            let second = actuator.update((1.0, 0.0, 0.0), first, 0.5 * DT);
+            outputs.push(second.0);
+        }
+        assert_eq!(outputs, vec![0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn first_order_reaches_63_percent_after_the_time_constant() {
+        let tau = 0.5;
+        let mut actuator = Actuator::new(config(ActuatorOrder::First, tau), (0.0, 0.0, 0.0));
+
+        let mut output = (0.0, 0.0, 0.0);
+        for _ in 0..(tau / DT).round() as usize {
+            output = actuator.update((1.0, 0.0, 0.0), output, DT);
+        }
+        assert!((output.0 - (1.0 - (-1.0f32).exp())).abs() < 1e-3);
+    }
+
+    #[test]
+    fn critically_damped_second_order_settles_without_overshoot() {
+        let mut actuator = Actuator::new(config(ActuatorOrder::Second, 0.2), (0.0, 0.0, 0.0));
+
+        let mut output = (0.0, 0.0, 0.0);
+        for _ in 0..300 {
+            output = actuator.update((1.0, 0.0, 0.0), output, DT);
+            assert!(output.0 <= 1.0);
+        }
+        assert!((output.0 - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn reset_drops_pending_commands() {
+        let mut config = config(ActuatorOrder::First, 0.0);
+        config.latency = 3;
+        let mut actuator = Actuator::new(config, (1.0, 0.0, 0.0));
+        actuator.reset();
+
+        let output = actuator.update((0.0, 0.0, 0.0), (1.0, 0.0, 0.0), DT);
+        assert_eq!(output, (0.0, 0.0, 0.0));
+    }
+}
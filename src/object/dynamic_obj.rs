@@ -2,6 +2,7 @@ use macroquad::prelude::*;
 use parry2d::shape::Ball;
 
 use crate::behaviour::traits::Drawable;
+use crate::error::{XironError, XironResult};
 use crate::parameter::RESOLUTION;
 
 #[derive(Debug, Clone)]
@@ -14,13 +15,22 @@ pub struct DynamicObj {
 }
 
 impl DynamicObj {
-    pub fn new(radius: f32, path_to_trace: Vec<(f32, f32, f32)>) -> DynamicObj {
-        DynamicObj {
-            current_pose: path_to_trace.clone()[0],
+    pub fn new(radius: f32, path_to_trace: Vec<(f32, f32, f32)>) -> XironResult<DynamicObj> {
+        let current_pose = match path_to_trace.first() {
+            Some(pose) => *pose,
+            None => {
+                return Err(XironError::InvalidObject(
+                    "Dynamic object needs at least one pose to trace".to_string(),
+                ));
+            }
+        };
+
+        return Ok(DynamicObj {
+            current_pose,
             radius,
             path_to_trace,
             shape: Ball::new(radius),
-        }
+        });
     }
 }
 
@@ -0,0 +1,410 @@
+//! Kinematic models that turn the velocity command of a robot into motion.
+//!
+//! The built-in drive types are registered under their names. Other models can be
+//! registered with `register_kinematic_model` and are then used by robots with
+//! `drive_type: <name>` in the config.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::parser::SkidSteerConfig;
+use crate::utils::normalise;
+
+/// What a kinematic model knows about the robot it moves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KinematicState {
+    /// Pose of the center of the robot
+    pub pose: (f32, f32, f32),
+    /// Distance between the axles. Robots take it from the second value of a
+    /// rectangular footprint (its extent across the direction of travel) or the
+    /// radius of a circular one, as steered drives always have.
+    pub wheelbase: f32,
+    /// Width of the footprint across the direction of travel
+    pub width: f32,
+}
+
+/// Moves a robot according to its velocity command.
+///
+/// What the components of the command mean is up to the model, e.g. `(vx, vy, w)`
+/// for Omnidrive or `(v, steering angle, _)` for Ackermann.
+pub trait KinematicModel: Debug + Send + Sync {
+    /// Pose of the robot after following `command` for `dt` seconds.
+    fn integrate(
+        &self,
+        state: &KinematicState,
+        command: (f32, f32, f32),
+        dt: f32,
+    ) -> (f32, f32, f32);
+
+    /// Whether the first two components of the command are a velocity in the
+    /// plane. Velocity limits then apply to its magnitude.
+    fn is_holonomic(&self) -> bool {
+        return false;
+    }
+
+    /// Whether the second component of the command is a steering angle.
+    /// Steering limits then apply to it.
+    fn is_steered(&self) -> bool {
+        return false;
+    }
+}
+
+/// Unicycle model. The command is `(v, _, w)`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DifferentialModel;
+
+impl KinematicModel for DifferentialModel {
+    fn integrate(
+        &self,
+        state: &KinematicState,
+        command: (f32, f32, f32),
+        dt: f32,
+    ) -> (f32, f32, f32) {
+        return arc_step(state.pose, (command.0, 0.0, command.2), dt);
+    }
+}
+
+/// Holonomic model. The command is `(vx, vy, w)` in the robot frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OmnidriveModel;
+
+impl KinematicModel for OmnidriveModel {
+    fn integrate(
+        &self,
+        state: &KinematicState,
+        command: (f32, f32, f32),
+        dt: f32,
+    ) -> (f32, f32, f32) {
+        return arc_step(state.pose, command, dt);
+    }
+
+    fn is_holonomic(&self) -> bool {
+        return true;
+    }
+}
+
+/// Bicycle model driven at the rear axle. The command is `(v, steering angle, _)`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AckermannModel;
+
+impl KinematicModel for AckermannModel {
+    fn integrate(
+        &self,
+        state: &KinematicState,
+        command: (f32, f32, f32),
+        dt: f32,
+    ) -> (f32, f32, f32) {
+        // Control input is (vd, delta, 0.0)
+        // We have to convert this to CoM velocities.
+        // In most of the literature, Bicycle Kinematics is derived using the Rear axle.
+        // We continue the same derivation, but apply the resultant control to the CoM.
+        let vd = command.0;
+        let steer = command.1;
+
+        return rear_axle_step(state, vd, steer, dt);
+    }
+
+    fn is_steered(&self) -> bool {
+        return true;
+    }
+}
+
+/// Bicycle model driven and steered at the front wheel. The command is
+/// `(v, steering angle, _)`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ForkliftModel;
+
+impl KinematicModel for ForkliftModel {
+    fn integrate(
+        &self,
+        state: &KinematicState,
+        command: (f32, f32, f32),
+        dt: f32,
+    ) -> (f32, f32, f32) {
+        // Control input is (vd, delta, 0.0)
+        // Get the velocity of driving wheel and steering angle
+        let vd = command.0;
+        let steer = command.1;
+
+        // Velocity of rear axle = vd * cos(delta). We get this by projecting
+        // the velocity of front axle onto the real axle
+        let vr = vd * steer.cos();
+
+        return rear_axle_step(state, vr, steer, dt);
+    }
+
+    fn is_steered(&self) -> bool {
+        return true;
+    }
+}
+
+/// Skid-steer or tracked robot. The command is `(v, _, w)` like for Differential,
+/// and the tracks are driven as if they would not slip. How the robot actually
+/// moves depends on the instantaneous centers of rotation (ICR) of its tracks and
+/// their slip.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SkidSteerModel {
+    pub config: SkidSteerConfig,
+}
+
+impl KinematicModel for SkidSteerModel {
+    fn integrate(
+        &self,
+        state: &KinematicState,
+        command: (f32, f32, f32),
+        dt: f32,
+    ) -> (f32, f32, f32) {
+        let config = &self.config;
+        let half_width = 0.5 * state.width;
+        let (v, w) = (command.0, command.2);
+
+        // Speeds of the tracks over the ground
+        let left = (v - w * half_width) * (1.0 - config.slip_left);
+        let right = (v + w * half_width) * (1.0 - config.slip_right);
+
+        // Lateral distances of the ICRs of the tracks. Without slip they are under
+        // the tracks, sliding sideways while turning moves them outwards.
+        let icr_left = config.icr_left.unwrap_or(half_width);
+        let icr_right = config.icr_right.unwrap_or(half_width);
+        let span = icr_left + icr_right;
+        if span.is_nan() || span <= 0.0 {
+            return state.pose;
+        }
+
+        let omega = (right - left) / span;
+        let vx = (right * icr_left + left * icr_right) / span;
+        // The robot turns about a point ahead of or behind its center
+        let vy = -config.icr_offset * omega;
+
+        return arc_step(state.pose, (vx, vy, omega), dt);
+    }
+}
+
+/// Moves a robot with constant velocity `(vx, vy, w)` in its own frame for `dt`
+/// seconds. The robot follows an arc, which is integrated exactly.
+fn arc_step(pose: (f32, f32, f32), vel: (f32, f32, f32), dt: f32) -> (f32, f32, f32) {
+    let (vx, vy, w) = vel;
+
+    // The integrals of cos(theta) and sin(theta) over the step are those at the
+    // middle heading, scaled by sinc of half the turn
+    let half_turn = 0.5 * w * dt;
+    let sinc = if half_turn.abs() < 1e-4 {
+        1.0 - half_turn * half_turn / 6.0
+    } else {
+        half_turn.sin() / half_turn
+    };
+    let theta_mid = pose.2 + half_turn;
+    let c = theta_mid.cos() * sinc * dt;
+    let s = theta_mid.sin() * sinc * dt;
+
+    let x = pose.0 + vx * c - vy * s;
+    let y = pose.1 + vx * s + vy * c;
+    let theta = normalise(pose.2 + w * dt);
+
+    return (x, y, theta);
+}
+
+/// Moves a bicycle whose rear axle moves at `vr`, projected to the center of the
+/// robot. Integrated with fourth order Runge-Kutta.
+fn rear_axle_step(state: &KinematicState, vr: f32, steer: f32, dt: f32) -> (f32, f32, f32) {
+    let l = state.wheelbase;
+
+    // Velocity of the center of the robot at heading theta
+    let derivative = |theta: f32| {
+        // Calculations of the Rear axle
+        let theta_dot = vr * steer.tan() / l; // theta_dot = v * tan(delta) / L
+        let x_dot = vr * theta.cos(); // x_dot = vcos(theta)
+        let y_dot = vr * theta.sin(); // y_dot = vsin(theta)
+
+        // Project them to the CoM. We get them by differentiating the following equations
+        /*
+         *  x_com = x + dcos(theta)
+         *  y_com = y + dsin(theta)
+         *
+         *  x_dot_com = d(x_com)/dt = x_dot - d * sin(theta) * theta_dot
+         *  y_dot_com = d(y_com)/dt = y_dot + d * cos(theta) * theta_dot
+         */
+        let d = 0.5 * l;
+        let xdot_com = x_dot - d * theta.sin() * theta_dot;
+        let ydot_com = y_dot + d * theta.cos() * theta_dot;
+        (xdot_com, ydot_com, theta_dot)
+    };
+
+    let pose = state.pose;
+    let k1 = derivative(pose.2);
+    let k2 = derivative(pose.2 + 0.5 * dt * k1.2);
+    let k3 = derivative(pose.2 + 0.5 * dt * k2.2);
+    let k4 = derivative(pose.2 + dt * k3.2);
+    let average = |a: f32, b: f32, c: f32, d: f32| (a + 2.0 * b + 2.0 * c + d) / 6.0;
+
+    let x = pose.0 + average(k1.0, k2.0, k3.0, k4.0) * dt;
+    let y = pose.1 + average(k1.1, k2.1, k3.1, k4.1) * dt;
+    let theta = normalise(pose.2 + average(k1.2, k2.2, k3.2, k4.2) * dt);
+
+    return (x, y, theta);
+}
+
+type Registry = RwLock<HashMap<String, Arc<dyn KinematicModel>>>;
+
+static KINEMATIC_MODELS: OnceLock<Registry> = OnceLock::new();
+
+fn registry() -> &'static Registry {
+    return KINEMATIC_MODELS.get_or_init(|| {
+        let mut models: HashMap<String, Arc<dyn KinematicModel>> = HashMap::new();
+        models.insert("Differential".to_string(), Arc::new(DifferentialModel));
+        models.insert("Omnidrive".to_string(), Arc::new(OmnidriveModel));
+        models.insert("Ackermann".to_string(), Arc::new(AckermannModel));
+        models.insert("Forklift".to_string(), Arc::new(ForkliftModel));
+        models.insert("SkidSteer".to_string(), Arc::new(SkidSteerModel::default()));
+        RwLock::new(models)
+    });
+}
+
+/// Registers `model` under `name`, so that robots can use it with
+/// `drive_type: <name>`. Replaces the model registered under that name before,
+/// which may be a built-in one. Robots that were already created keep their model.
+pub fn register_kinematic_model(name: &str, model: impl KinematicModel + 'static) {
+    let mut models = registry().write().unwrap_or_else(|e| e.into_inner());
+    models.insert(name.to_string(), Arc::new(model));
+}
+
+/// The model registered under `name`, if any.
+pub fn get_kinematic_model(name: &str) -> Option<Arc<dyn KinematicModel>> {
+    let models = registry().read().unwrap_or_else(|e| e.into_inner());
+    return models.get(name).cloned();
+}
+
+/// Names of all registered models, sorted.
+pub fn kinematic_model_names() -> Vec<String> {
+    let models = registry().read().unwrap_or_else(|e| e.into_inner());
+    let mut names: Vec<String> = models.keys().cloned().collect();
+    names.sort();
+    return names;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const DT_STEP: f32 = 0.1;
+
+    #[derive(Debug)]
+    struct Stationary;
+
+    impl KinematicModel for Stationary {
+        fn integrate(
+            &self,
+            state: &KinematicState,
+            _command: (f32, f32, f32),
+            _dt: f32,
+        ) -> (f32, f32, f32) {
+            return state.pose;
+        }
+    }
+
+    fn assert_close(a: (f32, f32, f32), b: (f32, f32, f32), tolerance: f32) {
+        let close = (a.0 - b.0).abs() < tolerance
+            && (a.1 - b.1).abs() < tolerance
+            && normalise(a.2 - b.2).abs() < tolerance;
+        assert!(close, "{:?} is not close to {:?}", a, b);
+    }
+
+    #[test]
+    fn arc_step_moves_straight_without_turning() {
+        let pose = arc_step((1.0, 2.0, 0.5 * PI), (2.0, 0.0, 0.0), 0.5);
+        assert_close(pose, (1.0, 3.0, 0.5 * PI), 1e-6);
+
+        let pose = arc_step((0.0, 0.0, 0.0), (0.0, 1.0, 0.0), 1.0);
+        assert_close(pose, (0.0, 1.0, 0.0), 1e-6);
+    }
+
+    #[test]
+    fn arc_step_follows_the_circle_exactly() {
+        // A quarter turn of radius 1 about (0, 1)
+        let pose = arc_step((0.0, 0.0, 0.0), (0.5 * PI, 0.0, 0.5 * PI), 1.0);
+        assert_close(pose, (1.0, 1.0, 0.5 * PI), 1e-5);
+
+        // One large step ends where many small ones do
+        let mut small = (0.0, 0.0, 0.3);
+        for _ in 0..1000 {
+            small = arc_step(small, (1.0, 0.2, 2.0), 1e-3);
+        }
+        assert_close(arc_step((0.0, 0.0, 0.3), (1.0, 0.2, 2.0), 1.0), small, 1e-4);
+    }
+
+    #[test]
+    fn arc_step_handles_tiny_turns() {
+        let pose = arc_step((0.0, 0.0, 0.0), (1.0, 0.0, 1e-6), 1.0);
+        assert_close(pose, (1.0, 0.0, 1e-6), 1e-6);
+    }
+
+    #[test]
+    fn rear_axle_step_matches_the_exact_circle() {
+        let state = KinematicState {
+            pose: (0.5, -1.0, 0.2),
+            wheelbase: 1.2,
+            width: 0.6,
+        };
+        let (vr, steer, dt): (f32, f32, f32) = (1.5, 0.3, DT_STEP);
+        let d = 0.5 * state.wheelbase;
+        let w = vr * steer.tan() / state.wheelbase;
+
+        // The rear axle moves on a circle and the center stays ahead of it
+        let theta = state.pose.2;
+        let rear = (
+            state.pose.0 - d * theta.cos(),
+            state.pose.1 - d * theta.sin(),
+            theta,
+        );
+        let rear = arc_step(rear, (vr, 0.0, w), dt);
+        let expected = (rear.0 + d * rear.2.cos(), rear.1 + d * rear.2.sin(), rear.2);
+
+        assert_close(rear_axle_step(&state, vr, steer, dt), expected, 1e-5);
+    }
+
+    #[test]
+    fn forklift_drives_the_rear_axle_slower_while_steering() {
+        let state = KinematicState {
+            pose: (0.0, 0.0, 0.0),
+            wheelbase: 1.0,
+            width: 0.5,
+        };
+        let forklift = ForkliftModel.integrate(&state, (1.0, 0.4, 0.0), DT_STEP);
+        let ackermann = AckermannModel.integrate(&state, (0.4f32.cos(), 0.4, 0.0), DT_STEP);
+        assert_close(forklift, ackermann, 1e-6);
+    }
+
+    #[test]
+    fn skid_steer_without_slip_is_differential() {
+        let state = KinematicState {
+            pose: (1.0, 1.0, 1.0),
+            wheelbase: 0.8,
+            width: 0.6,
+        };
+        let command = (0.8, 0.0, 0.5);
+        let skid = SkidSteerModel::default().integrate(&state, command, DT_STEP);
+        let differential = DifferentialModel.integrate(&state, command, DT_STEP);
+        assert_close(skid, differential, 1e-6);
+    }
+
+    #[test]
+    fn registered_models_are_found_by_name() {
+        register_kinematic_model("Stationary", Stationary);
+        let model = get_kinematic_model("Stationary").unwrap();
+        let state = KinematicState {
+            pose: (1.0, 2.0, 0.5),
+            wheelbase: 1.0,
+            width: 0.5,
+        };
+
+        assert_eq!(
+            model.integrate(&state, (1.0, 0.0, 1.0), DT_STEP),
+            state.pose
+        );
+        assert!(kinematic_model_names().contains(&"Stationary".to_string()));
+        assert!(get_kinematic_model("Differential").is_some());
+        assert!(get_kinematic_model("Unknown").is_none());
+    }
+}
@@ -1,11 +1,21 @@
+pub mod actuator;
 pub mod dynamic_obj;
+pub mod kinematics;
+pub mod rigid_body;
 pub mod robot;
 pub mod sensors;
 pub mod static_obj;
+pub mod trailer;
 pub mod wall;
+pub mod wheels;
 
+pub use actuator::*;
 pub use dynamic_obj::*;
+pub use kinematics::*;
+pub use rigid_body::*;
 pub use robot::*;
 pub use sensors::*;
 pub use static_obj::*;
+pub use trailer::*;
 pub use wall::*;
+pub use wheels::*;
@@ -0,0 +1,138 @@
+//! Rigid bodies that can be pushed around.
+//!
+//! Contacts are resolved with impulses along the contact normal, so bodies keep
+//! the momentum they were given and lose it to friction with the floor. Walls,
+//! closed objects and robots without a body are infinitely heavy.
+
+use serde::{Deserialize, Serialize};
+
+use crate::parameter::GRAVITY;
+use crate::parser::BodyConfig;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RigidBody {
+    pub config: BodyConfig,
+    /// Velocity in the world frame: (vx, vy, angular)
+    pub vel: (f32, f32, f32),
+}
+
+impl RigidBody {
+    pub fn new(config: BodyConfig) -> RigidBody {
+        return RigidBody {
+            config,
+            vel: (0.0, 0.0, 0.0),
+        };
+    }
+
+    pub fn inverse_mass(&self) -> f32 {
+        return 1.0 / self.config.mass;
+    }
+
+    /// Inverse moment of inertia of a box of the given size with this mass.
+    pub fn inverse_inertia(&self, width: f32, height: f32) -> f32 {
+        return 12.0 / (self.config.mass * (width * width + height * height));
+    }
+
+    /// Slows the body down by `dt` seconds of friction with the floor. `radius` is
+    /// the mean distance of the body from its center, which the spin slows with.
+    pub fn apply_friction(&mut self, dt: f32, radius: f32) {
+        let decel = self.config.friction * GRAVITY * dt;
+
+        let speed = (self.vel.0 * self.vel.0 + self.vel.1 * self.vel.1).sqrt();
+        let scale = if speed > decel {
+            (speed - decel) / speed
+        } else {
+            0.0
+        };
+        self.vel.0 *= scale;
+        self.vel.1 *= scale;
+
+        let spin_decel = if radius > 0.0 { decel / radius } else { 0.0 };
+        self.vel.2 = self.vel.2.signum() * (self.vel.2.abs() - spin_decel).max(0.0);
+    }
+}
+
+/// Pose of a body moving with `vel` for `dt` seconds.
+pub fn integrate_body(pose: (f32, f32, f32), vel: (f32, f32, f32), dt: f32) -> (f32, f32, f32) {
+    return (
+        pose.0 + vel.0 * dt,
+        pose.1 + vel.1 * dt,
+        pose.2 + vel.2 * dt,
+    );
+}
+
+/// One side of a contact, as the impulse solver sees it.
+#[derive(Clone, Copy, Debug)]
+pub struct ContactBody {
+    /// Zero for things that can not be pushed
+    pub inverse_mass: f32,
+    pub inverse_inertia: f32,
+    /// Velocity in the world frame: (vx, vy, angular)
+    pub vel: (f32, f32, f32),
+    /// Center of rotation in the world frame
+    pub center: (f32, f32),
+    pub restitution: f32,
+}
+
+impl ContactBody {
+    /// Something infinitely heavy at `center`, moving with `vel`.
+    pub fn fixed(center: (f32, f32), vel: (f32, f32, f32)) -> ContactBody {
+        return ContactBody {
+            inverse_mass: 0.0,
+            inverse_inertia: 0.0,
+            vel,
+            center,
+            restitution: 0.0,
+        };
+    }
+
+    fn arm(&self, point: (f32, f32)) -> (f32, f32) {
+        return (point.0 - self.center.0, point.1 - self.center.1);
+    }
+
+    /// Velocity of the point of the body at `point`.
+    pub fn velocity_at(&self, point: (f32, f32)) -> (f32, f32) {
+        let r = self.arm(point);
+        return (self.vel.0 - self.vel.2 * r.1, self.vel.1 + self.vel.2 * r.0);
+    }
+
+    /// Change of velocity from `impulse` applied at `point`.
+    pub fn response(&self, impulse: (f32, f32), point: (f32, f32)) -> (f32, f32, f32) {
+        let r = self.arm(point);
+        return (
+            impulse.0 * self.inverse_mass,
+            impulse.1 * self.inverse_mass,
+            (r.0 * impulse.1 - r.1 * impulse.0) * self.inverse_inertia,
+        );
+    }
+}
+
+/// Impulse on `b` at the contact `point`, where `normal` points from `a` to `b`.
+/// `a` receives the opposite. None if the bodies do not approach each other, or if
+/// neither of them can be pushed.
+pub fn contact_impulse(
+    a: &ContactBody,
+    b: &ContactBody,
+    point: (f32, f32),
+    normal: (f32, f32),
+) -> Option<(f32, f32)> {
+    let va = a.velocity_at(point);
+    let vb = b.velocity_at(point);
+    let approach = (vb.0 - va.0) * normal.0 + (vb.1 - va.1) * normal.1;
+    if approach >= 0.0 {
+        return None;
+    }
+
+    let cross = |r: (f32, f32)| r.0 * normal.1 - r.1 * normal.0;
+    let ra = cross(a.arm(point));
+    let rb = cross(b.arm(point));
+    let resistance =
+        a.inverse_mass + b.inverse_mass + ra * ra * a.inverse_inertia + rb * rb * b.inverse_inertia;
+    if resistance <= 0.0 {
+        return None;
+    }
+
+    let restitution = a.restitution.max(b.restitution);
+    let magnitude = -(1.0 + restitution) * approach / resistance;
+    return Some((magnitude * normal.0, magnitude * normal.1));
+}
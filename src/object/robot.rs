@@ -1,6 +1,7 @@
 extern crate rand;
 
 use std::fmt::Display;
+use std::sync::Arc;
 
 use macroquad::prelude::*;
 use parry2d::math::Vector;
@@ -9,12 +10,32 @@ use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::behaviour::traits::{Drawable, GuiObject};
+use crate::error::{XironError, XironResult};
 use crate::parameter::{DT, RESOLUTION};
-use crate::parser::RobotConfig;
-use crate::prelude::traits::{Collidable, Genericbject, Sensable};
-use crate::utils::{draw_rotated_rectangle, normalise};
-
+use crate::parser::{
+    ActuatorConfig, BodyConfig, CollisionResponse, LimitsConfig, NoiseConfig, RobotConfig,
+    SkidSteerConfig, TrailerConfig, WheelConfig,
+};
+use crate::prelude::traits::{Collidable, Genericbject};
+use crate::utils::{draw_rotated_rectangle, normalise, SimRng};
+
+use super::actuator::Actuator;
+use super::kinematics::{
+    get_kinematic_model, DifferentialModel, KinematicModel, KinematicState, SkidSteerModel,
+};
+use super::rigid_body::{integrate_body, RigidBody};
 use super::sensors::{LiDAR, LiDARMsg};
+use super::trailer::{pulled_headings, trailer_poses, Trailer, TrailerPlacement};
+use super::wheels::{WheelEncoder, WheelLayout};
+
+/// Radius used when a trailer is configured without a valid footprint.
+pub const DEFAULT_ROBOT_RADIUS: f32 = 0.25;
+
+// A limit that can be applied. Negative and NaN limits, e.g. from configs that
+// were not validated, are ignored instead of making the clamps panic.
+fn usable_limit(limit: Option<f32>) -> Option<f32> {
+    return limit.filter(|max| *max >= 0.0);
+}
 
 #[derive(Debug, Clone)]
 pub enum Footprint {
@@ -22,12 +43,17 @@ pub enum Footprint {
     Rectangular(Cuboid),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq)]
+/// Name of the kinematic model of a robot. Configs write it as a plain string.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(from = "String", into = "String")]
 pub enum DriveType {
     Differential,
     Ackermann,
     Omnidrive,
     Forklift,
+    SkidSteer,
+    /// A model registered with `register_kinematic_model`
+    Custom(String),
 }
 
 impl Default for DriveType {
@@ -43,6 +69,52 @@ impl Display for DriveType {
             DriveType::Ackermann => write!(f, "Ackermann"),
             DriveType::Omnidrive => write!(f, "Omnidrive"),
             DriveType::Forklift => write!(f, "Forklift"),
+            DriveType::SkidSteer => write!(f, "SkidSteer"),
+            DriveType::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl From<String> for DriveType {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "Differential" => DriveType::Differential,
+            "Ackermann" => DriveType::Ackermann,
+            "Omnidrive" => DriveType::Omnidrive,
+            "Forklift" => DriveType::Forklift,
+            "SkidSteer" => DriveType::SkidSteer,
+            _ => DriveType::Custom(name),
+        }
+    }
+}
+
+impl From<DriveType> for String {
+    fn from(drive_type: DriveType) -> Self {
+        return drive_type.to_string();
+    }
+}
+
+impl DriveType {
+    /// The registered kinematic model with this name, if any.
+    pub fn kinematic_model(&self) -> Option<Arc<dyn KinematicModel>> {
+        return get_kinematic_model(&self.to_string());
+    }
+}
+
+/// Faults that can be injected into a robot at runtime, e.g. from a scenario.
+#[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq)]
+pub enum Fault {
+    /// Motors stop responding. All velocity commands are ignored.
+    MotorFailure,
+    /// LiDAR stops returning hits. Every reading is reported at max range.
+    LidarFailure,
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fault::MotorFailure => write!(f, "MotorFailure"),
+            Fault::LidarFailure => write!(f, "LidarFailure"),
         }
     }
 }
@@ -60,7 +132,44 @@ pub struct Robot {
     pub lidar: Vec<LiDAR>,
 
     pub drive_type: DriveType,
+    // Model of the drive type, looked up when the robot is created
+    pub kinematics: Arc<dyn KinematicModel>,
     pub add_noise: bool,
+
+    // Faults currently active on this robot
+    pub faults: Vec<Fault>,
+
+    // Source of all noise of this robot
+    pub rng: SimRng,
+
+    // Velocity last commanded. `vel` follows it within the limits.
+    pub cmd_vel: (f32, f32, f32),
+    pub limits: LimitsConfig,
+
+    // Motors between the command and `vel`, if they do not respond instantly
+    pub actuator: Option<Actuator>,
+
+    // Noise on the velocity the robot moves with, drawn every tick
+    pub noise: Option<NoiseConfig>,
+
+    // Encoders of the wheels, if the robot has wheels configured
+    pub wheels: Option<WheelEncoder>,
+
+    // Tracks of a SkidSteer robot, if they differ from the defaults
+    pub skid_steer: Option<SkidSteerConfig>,
+
+    // Trailers hitched behind the robot, front to back
+    pub trailers: Vec<Trailer>,
+
+    // Mass of the robot, if other robots can push it. Its velocity is the one the
+    // robot was pushed with, on top of the velocity it drives with.
+    pub body: Option<RigidBody>,
+
+    // What the robot does when it hits something, if not what the world says
+    pub collision_response: Option<CollisionResponse>,
+
+    // Name of the robot model this robot was created from
+    pub model: Option<String>,
 }
 
 impl Robot {
@@ -72,208 +181,551 @@ impl Robot {
         footprint: Vec<f32>,
         drive_type: DriveType,
         add_noise: bool,
-    ) -> Robot {
-        if footprint.len() == 1 {
-            let fshape = Footprint::Circular(Ball {
-                radius: (footprint[0]),
-            });
-
-            if lidar_present {
-                return Robot {
-                    id: id,
-                    pose: pose,
-                    vel: vel,
-                    shape: fshape,
-                    lidar: vec![LiDAR::new(pose)],
-                    drive_type: drive_type,
-                    add_noise: add_noise,
-                };
+    ) -> XironResult<Robot> {
+        let kinematics = match drive_type.kinematic_model() {
+            Some(kinematics) => kinematics,
+            None => {
+                return Err(XironError::InvalidObject(format!(
+                    "Robot {} has the unknown drive type {}",
+                    id, drive_type
+                )))
             }
+        };
+
+        // Only a footprint of exactly two values is rectangular
+        let shape = match footprint.len() {
+            2 => Footprint::Rectangular(Cuboid {
+                half_extents: Vector::new(footprint[0] * 0.5, footprint[1] * 0.5),
+            }),
+            1 => Footprint::Circular(Ball {
+                radius: footprint[0],
+            }),
+            _ => {
+                return Err(XironError::InvalidObject(format!(
+                    "Robot {} has a footprint with {} values, it needs 1 or 2",
+                    id,
+                    footprint.len()
+                )))
+            }
+        };
+
+        let mut robot = Robot::with_shape(
+            id,
+            pose,
+            lidar_present,
+            shape,
+            drive_type,
+            kinematics,
+            add_noise,
+        );
+        robot.vel = vel;
+        robot.cmd_vel = vel;
+        return Ok(robot);
+    }
 
-            return Robot {
-                id: id,
-                pose: pose,
-                vel: vel,
-                shape: fshape,
-                lidar: Vec::new(),
-                drive_type: drive_type,
-                add_noise: add_noise,
-            };
+    // A robot at rest, with a shape and kinematics that are known to be valid
+    fn with_shape(
+        id: String,
+        pose: (f32, f32, f32),
+        lidar_present: bool,
+        shape: Footprint,
+        drive_type: DriveType,
+        kinematics: Arc<dyn KinematicModel>,
+        add_noise: bool,
+    ) -> Robot {
+        let lidar = if lidar_present {
+            vec![LiDAR::new(pose)]
         } else {
-            let width = footprint[0] * 0.5;
-            let height = footprint[1] * 0.5;
-            let fshape = Footprint::Rectangular(Cuboid {
-                half_extents: Vector::new(width, height),
-            });
-
-            if lidar_present {
-                return Robot {
-                    id: id,
-                    pose: pose,
-                    vel: vel,
-                    shape: fshape,
-                    lidar: vec![LiDAR::new(pose)],
-                    drive_type: drive_type,
-                    add_noise: add_noise,
-                };
+            Vec::new()
+        };
+
+        return Robot {
+            id: id,
+            pose: pose,
+            vel: (0.0, 0.0, 0.0),
+            shape: shape,
+            lidar: lidar,
+            drive_type: drive_type,
+            kinematics: kinematics,
+            add_noise: add_noise,
+            faults: Vec::new(),
+            rng: SimRng::from_entropy(),
+            cmd_vel: (0.0, 0.0, 0.0),
+            limits: LimitsConfig::default(),
+            actuator: None,
+            noise: None,
+            wheels: None,
+            skid_steer: None,
+            trailers: Vec::new(),
+            body: None,
+            collision_response: None,
+            model: None,
+        };
+    }
+
+    pub fn from_config(config: &RobotConfig) -> XironResult<Robot> {
+        let mut robot = Robot::new(
+            config.id.clone(),
+            config.pose,
+            config.vel,
+            config.lidar,
+            config.footprint.clone(),
+            config.drive_type.clone(),
+            config.add_noise,
+        )?
+        .with_limits(config.limits)
+        .with_actuator(config.actuator)
+        .with_noise(config.noise)
+        .with_skid_steer(config.skid_steer)
+        .with_wheels(config.wheels)
+        .with_trailers(config.trailers.clone())
+        .with_body(config.body)
+        .with_collision_response(config.collision_response);
+        robot.model = config.model.clone();
+
+        return Ok(robot);
+    }
+
+    pub fn with_limits(mut self, limits: LimitsConfig) -> Robot {
+        self.limits = limits;
+        self.cmd_vel = self.clamp_velocity(self.cmd_vel);
+        self.vel = self.cmd_vel;
+        return self;
+    }
+
+    pub fn with_noise(mut self, noise: Option<NoiseConfig>) -> Robot {
+        self.noise = noise;
+        return self;
+    }
+
+    pub fn with_actuator(mut self, actuator: Option<ActuatorConfig>) -> Robot {
+        self.actuator = actuator.map(|config| Actuator::new(config, self.cmd_vel));
+        return self;
+    }
+
+    /// Sets the tracks of a SkidSteer robot. Other drive types have no tracks.
+    pub fn with_skid_steer(mut self, skid_steer: Option<SkidSteerConfig>) -> Robot {
+        if self.drive_type != DriveType::SkidSteer {
+            return self;
+        }
+
+        self.skid_steer = skid_steer;
+        if let Some(config) = skid_steer {
+            self.kinematics = Arc::new(SkidSteerModel { config });
+        }
+        return self;
+    }
+
+    /// Gives the robot wheels. Steered robots can not have any.
+    pub fn with_wheels(mut self, wheels: Option<WheelConfig>) -> Robot {
+        self.wheels = wheels.and_then(|config| {
+            let layout = WheelLayout::for_model(&*self.kinematics);
+            if layout.is_none() {
+                println!(
+                    "Robot {} has the steered drive type {}. Ignoring its wheels",
+                    self.id, self.drive_type
+                );
             }
+            layout.map(|layout| WheelEncoder::new(config, layout))
+        });
+        return self;
+    }
 
-            return Robot {
-                id: id,
-                pose: pose,
-                vel: vel,
-                shape: fshape,
-                lidar: Vec::new(),
-                drive_type: drive_type,
-                add_noise: add_noise,
-            };
+    /// Hitches trailers behind the robot, at the angles of their configs.
+    pub fn with_trailers(mut self, trailers: Vec<TrailerConfig>) -> Robot {
+        let mut heading = self.pose.2;
+        self.trailers = trailers
+            .into_iter()
+            .map(|config| {
+                heading = normalise(heading + config.angle);
+                Trailer::new(config, heading)
+            })
+            .collect();
+        self.attach_trailers();
+        return self;
+    }
+
+    /// Lets other robots push the robot.
+    pub fn with_body(mut self, body: Option<BodyConfig>) -> Robot {
+        self.body = body.map(RigidBody::new);
+        return self;
+    }
+
+    pub fn with_collision_response(mut self, response: Option<CollisionResponse>) -> Robot {
+        self.collision_response = response;
+        return self;
+    }
+
+    /// Hitch points and center poses of the trailers when the robot is at `pose`,
+    /// after pulling them there from the current pose.
+    pub fn trailer_poses_at(&self, pose: &(f32, f32, f32)) -> Vec<TrailerPlacement> {
+        let headings = pulled_headings(&self.trailers, self.pose, *pose);
+        return trailer_poses(&self.trailers, *pose, &headings);
+    }
+
+    /// Angle of every trailer relative to the vehicle in front of it.
+    pub fn trailer_angles(&self) -> Vec<f32> {
+        let mut front = self.pose.2;
+        let mut angles = Vec::with_capacity(self.trailers.len());
+        for trailer in self.trailers.iter() {
+            angles.push(normalise(trailer.heading - front));
+            front = trailer.heading;
         }
+        return angles;
     }
 
-    pub fn from_id_and_pose(id: String, pose: (f32, f32, f32), radius: f32) -> Self {
-        Robot {
-            id: id,
-            pose: pose,
-            vel: (0.0, 0.0, 0.0),
-            shape: Footprint::Circular(Ball { radius: radius }),
-            lidar: vec![LiDAR::new(pose)],
-            drive_type: DriveType::Differential,
-            add_noise: false,
+    // Moves the trailers to their hitches, keeping their headings
+    fn attach_trailers(&mut self) {
+        let headings: Vec<f32> = self.trailers.iter().map(|t| t.heading).collect();
+        let poses = trailer_poses(&self.trailers, self.pose, &headings);
+        for (trailer, (_, pose)) in self.trailers.iter_mut().zip(poses) {
+            trailer.pose = pose;
         }
     }
 
+    pub fn from_id_and_pose(id: String, pose: (f32, f32, f32), radius: f32) -> Self {
+        return Robot::with_shape(
+            id,
+            pose,
+            true,
+            Footprint::Circular(Ball { radius }),
+            DriveType::Differential,
+            Arc::new(DifferentialModel),
+            false,
+        );
+    }
+
     pub fn control(&mut self, vel: (f32, f32, f32)) {
-        self.vel = vel;
+        if self.has_fault(Fault::MotorFailure) {
+            self.stop();
+            return;
+        }
+
+        let mut vel = self.clamp_velocity(vel);
 
         if self.add_noise {
             // Add some noise to the velocities
-            let mut rand_gen = rand::thread_rng();
+            let rand_gen = &mut self.rng;
             let vx_noise = rand_gen.gen_range(-0.01..0.01);
             let vy_noise = rand_gen.gen_range(-0.01..0.01);
             let w_noise = rand_gen.gen_range(-0.01..0.01);
 
-            self.vel.0 += vx_noise;
-            self.vel.1 += vy_noise;
-            self.vel.2 += w_noise;
+            vel.0 += vx_noise;
+            vel.1 += vy_noise;
+            vel.2 += w_noise;
+        }
+
+        self.cmd_vel = vel;
+
+        // Without acceleration limits and actuator dynamics the robot follows the
+        // command instantly
+        if self.limits.max_linear_accel.is_none()
+            && self.limits.max_angular_accel.is_none()
+            && self.limits.max_steering_rate.is_none()
+            && self.actuator.is_none()
+        {
+            self.vel = vel;
         }
     }
 
-    pub fn next(&mut self) -> (f32, f32, f32) {
-        match self.drive_type {
-            DriveType::Differential => {
-                let theta = normalise(self.pose.2 + self.vel.2 * DT);
-                let x = self.pose.0 + self.vel.0 * theta.cos() * DT;
-                let y = self.pose.1 + self.vel.0 * theta.sin() * DT;
+    /// Stops the robot immediately, ignoring the acceleration limits and actuator dynamics.
+    pub fn stop(&mut self) {
+        self.cmd_vel = (0.0, 0.0, 0.0);
+        self.vel = (0.0, 0.0, 0.0);
 
-                return (x, y, theta);
+        if let Some(actuator) = self.actuator.as_mut() {
+            actuator.reset();
+        }
+    }
+
+    /// Turns the velocity away from an obstacle in the direction of `normal`, keeping
+    /// `restitution` of the speed towards it. Robots that can not move sideways
+    /// back off instead. The new velocity holds until the next command.
+    pub fn bounce(&mut self, normal: (f32, f32), restitution: f32) {
+        let (sin, cos) = self.pose.2.sin_cos();
+        let mut vel = self.vel;
+
+        if self.kinematics.is_holonomic() {
+            let mut world = (vel.0 * cos - vel.1 * sin, vel.0 * sin + vel.1 * cos);
+            let towards = world.0 * normal.0 + world.1 * normal.1;
+            if towards > 0.0 {
+                world.0 -= (1.0 + restitution) * towards * normal.0;
+                world.1 -= (1.0 + restitution) * towards * normal.1;
             }
+            vel.0 = world.0 * cos + world.1 * sin;
+            vel.1 = -world.0 * sin + world.1 * cos;
+        } else if vel.0 * (cos * normal.0 + sin * normal.1) > 0.0 {
+            vel.0 = -restitution * vel.0;
+        }
+
+        self.cmd_vel = vel;
+        self.vel = vel;
+        if let Some(actuator) = self.actuator.as_mut() {
+            actuator.hold(vel);
+        }
+    }
 
-            DriveType::Omnidrive => {
-                let theta = normalise(self.pose.2 + self.vel.2 * DT);
+    /// Clamps the linear and angular velocity to the limits of the robot.
+    fn clamp_velocity(&self, vel: (f32, f32, f32)) -> (f32, f32, f32) {
+        let mut vel = vel;
 
-                let c_theta = theta.cos();
-                let s_theta = theta.sin();
+        if let Some(max) = usable_limit(self.limits.max_linear_vel) {
+            if self.kinematics.is_holonomic() {
+                let speed = (vel.0 * vel.0 + vel.1 * vel.1).sqrt();
+                if speed > max {
+                    vel.0 *= max / speed;
+                    vel.1 *= max / speed;
+                }
+            } else {
+                vel.0 = vel.0.clamp(-max, max);
+            }
+        }
+        if let Some(max) = usable_limit(self.limits.max_angular_vel) {
+            vel.2 = vel.2.clamp(-max, max);
+        }
+        if let Some(max) = usable_limit(self.limits.max_steering_angle) {
+            if self.kinematics.is_steered() {
+                vel.1 = vel.1.clamp(-max, max);
+            }
+        }
 
-                let x = self.pose.0 + (self.vel.0 * c_theta - self.vel.1 * s_theta) * DT;
-                let y = self.pose.1 + (self.vel.0 * s_theta + self.vel.1 * c_theta) * DT;
+        return vel;
+    }
 
-                return (x, y, theta);
+    /// Moves the velocity towards `target` by at most `dt` seconds of acceleration,
+    /// or of steering rate for steered drive types.
+    fn accelerate(&mut self, target: (f32, f32, f32), dt: f32) {
+        let step = |current: f32, target: f32, max_accel: Option<f32>| match usable_limit(max_accel)
+        {
+            Some(a) => current + (target - current).clamp(-a * dt, a * dt),
+            None => target,
+        };
+
+        if self.kinematics.is_holonomic() {
+            match usable_limit(self.limits.max_linear_accel) {
+                Some(a) => {
+                    let dx = target.0 - self.vel.0;
+                    let dy = target.1 - self.vel.1;
+                    let dv = (dx * dx + dy * dy).sqrt();
+                    let scale = if dv > a * dt { a * dt / dv } else { 1.0 };
+                    self.vel.0 += dx * scale;
+                    self.vel.1 += dy * scale;
+                }
+                None => {
+                    self.vel.0 = target.0;
+                    self.vel.1 = target.1;
+                }
             }
+        } else {
+            self.vel.0 = step(self.vel.0, target.0, self.limits.max_linear_accel);
 
-            DriveType::Ackermann => {
-                // Ackermann Controller
-                // Control input is (vd, delta, 0.0)
-                // We have to convert this to CoM velocities.
-                // In most of the literature, Bicycle Kinematics is derived using the Rear axle.
-                // We continue the same derivation, but apply the resultant control to the CoM.
-                let vd = self.vel.0;
-                let steer = self.vel.1;
-                let l = self.get_bounds().1;
-
-                // Calculations of the Rear axle
-                let theta_dot = vd * steer.tan() / l; // theta_dot = v * tan(delta) / L
-                let x_dot = vd * self.pose.2.cos(); // x_dot = vcos(theta)
-                let y_dot = vd * self.pose.2.sin(); // y_dot = vsin(theta)
-
-                // Project them to the CoM. We get them by differentiating the following equations
-                /*
-                 *  x_com = x + dcos(theta)
-                 *  y_com = y + dsin(theta)
-                 *
-                 *  x_dot_com = d(x_com)/dt = x_dot - d * sin(theta) * theta_dot
-                 *  y_dot_com = d(y_com)/dt = y_dot + d * cos(theta) * theta_dot
-                 */
-                let d = 0.5 * l;
-                let xdot_com = x_dot - d * self.pose.2.sin() * theta_dot;
-                let ydot_com = y_dot + d * self.pose.2.cos() * theta_dot;
-                let theta_dot_com = theta_dot;
-
-                let theta = normalise(self.pose.2 + theta_dot_com * DT);
-                let x = self.pose.0 + xdot_com * DT;
-                let y = self.pose.1 + ydot_com * DT;
-
-                return (x, y, theta);
+            // The second component is the steering angle or unused
+            if self.kinematics.is_steered() {
+                self.vel.1 = step(self.vel.1, target.1, self.limits.max_steering_rate);
+            } else {
+                self.vel.1 = target.1;
             }
+        }
+        self.vel.2 = step(self.vel.2, target.2, self.limits.max_angular_accel);
+    }
+
+    /// Pose of the robot after one tick.
+    pub fn next(&mut self) -> (f32, f32, f32) {
+        return self.next_by(DT);
+    }
+
+    /// Pose of the robot after `dt` seconds. Updates the velocity on the way.
+    pub fn next_by(&mut self, dt: f32) -> (f32, f32, f32) {
+        // The motors respond to the command first, then the limits apply
+        let target = match self.actuator.as_mut() {
+            Some(actuator) => actuator.update(self.cmd_vel, self.vel, dt),
+            None => self.cmd_vel,
+        };
+        self.accelerate(target, dt);
+        // Noise is added after clamping the command, so the limits are enforced again
+        self.vel = self.clamp_velocity(self.vel);
+
+        // Encoders count the turns of the wheels, not the noisy motion over the ground
+        if let Some(wheels) = self.wheels.as_mut() {
+            wheels.update(self.vel, dt);
+        }
 
-            DriveType::Forklift => {
-                // Foklift Controller
-                // Control input is (vd, delta, 0.0)
-                // We have to convert this to CoM velocities.
-
-                // Get the elocity of driving wheel and steering angle
-                let vd = self.vel.0;
-                let steer = self.vel.1;
-
-                // Get the length between axles
-                let l = self.get_bounds().1;
-
-                // Calculations of the Rear axle
-                // Velocity of rear axle = vd * cos(delta). We get this by projecting
-                // the velocity of front axle onto the real axle
-                let vr = vd * steer.cos();
-                let theta_dot = vr * steer.tan() / l; // theta_dot = v * tan(delta) / L
-                let x_dot = vr * self.pose.2.cos(); // x_dot = vcos(theta)
-                let y_dot = vr * self.pose.2.sin(); // y_dot = vsin(theta)
-
-                // Project them to the CoM. We get them by differentiating the following equations
-                /*
-                 *  x_com = x + dcos(theta)
-                 *  y_com = y + dsin(theta)
-                 *
-                 *  x_dot_com = d(x_com)/dt = x_dot - d * sin(theta) * theta_dot
-                 *  y_dot_com = d(y_com)/dt = y_dot + d * cos(theta) * theta_dot
-                 */
-                let d = 0.5 * l;
-                let xdot_com = x_dot - d * self.pose.2.sin() * theta_dot;
-                let ydot_com = y_dot + d * self.pose.2.cos() * theta_dot;
-                let theta_dot_com = theta_dot;
-
-                let theta = normalise(self.pose.2 + theta_dot_com * DT);
-                let x = self.pose.0 + xdot_com * DT;
-                let y = self.pose.1 + ydot_com * DT;
-
-                return (x, y, theta);
+        let vel = self.apply_noise(self.vel);
+        let state = KinematicState {
+            pose: self.pose,
+            wheelbase: self.get_bounds().1,
+            width: match self.shape {
+                Footprint::Circular(b) => 2.0 * b.radius,
+                Footprint::Rectangular(c) => 2.0 * c.half_extents.y,
+            },
+        };
+        let pose = self.kinematics.integrate(&state, vel, dt);
+
+        // Being pushed only moves the robot, it does not turn it
+        return match self.body.as_mut() {
+            Some(body) => {
+                let pushed = (body.vel.0, body.vel.1, 0.0);
+                body.apply_friction(dt, 0.0);
+                integrate_body(pose, pushed, dt)
             }
+            None => pose,
+        };
+    }
+
+    /// The velocity the robot actually moves with, after the noise models.
+    fn apply_noise(&mut self, vel: (f32, f32, f32)) -> (f32, f32, f32) {
+        let noise = match self.noise {
+            Some(noise) => noise,
+            None => return vel,
+        };
+
+        // A robot at rest stays at rest
+        if vel == (0.0, 0.0, 0.0) {
+            return vel;
         }
+
+        let rng = &mut self.rng;
+        let mut value = |v: f32, sigma: f32, proportional: f32, bias: f32, scale_error: f32| {
+            let sigma = sigma + proportional * v.abs();
+            return v * (1.0 + scale_error) + bias + sigma * rng.gaussian();
+        };
+
+        let n = &noise;
+        let mut noisy = (
+            value(
+                vel.0,
+                n.sigma.0,
+                n.proportional.0,
+                n.bias.0,
+                n.scale_error.0,
+            ),
+            value(
+                vel.1,
+                n.sigma.1,
+                n.proportional.1,
+                n.bias.1,
+                n.scale_error.1,
+            ),
+            value(
+                vel.2,
+                n.sigma.2,
+                n.proportional.2,
+                n.bias.2,
+                n.scale_error.2,
+            ),
+        );
+        noisy.2 += noise.yaw_drift * vel.0;
+
+        return noisy;
     }
 
+    /// Drives the robot to `next`, pulling its trailers along.
     pub fn step(&mut self, next: &(f32, f32, f32)) {
-        self.pose = *next;
+        let headings = pulled_headings(&self.trailers, self.pose, *next);
+        for (trailer, heading) in self.trailers.iter_mut().zip(headings) {
+            trailer.heading = heading;
+        }
+
+        self.move_to(next);
+    }
+
+    /// Puts the robot at `pose` without driving there. Trailers keep their angles.
+    pub fn place(&mut self, pose: &(f32, f32, f32)) {
+        let turn = pose.2 - self.pose.2;
+        for trailer in self.trailers.iter_mut() {
+            trailer.heading = normalise(trailer.heading + turn);
+        }
+
+        self.move_to(pose);
+    }
+
+    // Moves the robot, its trailers and its sensors to `pose`
+    fn move_to(&mut self, pose: &(f32, f32, f32)) {
+        self.pose = *pose;
+        self.attach_trailers();
 
         for lidar in self.lidar.iter_mut() {
-            lidar.translate_to(*next)
+            lidar.translate_to(*pose)
         }
     }
 
-    pub fn sense(&self, collidables: &Vec<Box<dyn Collidable>>) -> LiDARMsg {
-        return self.lidar[0].sense(collidables);
+    pub fn sense(&self, collidables: &Vec<Box<dyn Collidable>>) -> XironResult<LiDARMsg> {
+        let lidar = match self.lidar.first() {
+            Some(lidar) => lidar,
+            None => return Err(XironError::NoSensor(self.id.clone())),
+        };
+
+        // Sensor noise is derived from the robot's generator and its pose, so that
+        // seeded simulations produce the same scans.
+        let seed = self.rng.state()
+            ^ ((self.pose.0.to_bits() as u64) << 32)
+            ^ (self.pose.1.to_bits() as u64);
+
+        if self.has_fault(Fault::LidarFailure) {
+            // A failed LiDAR sees nothing. Report every reading at max range.
+            return Ok(lidar.sense_with_seed(&Vec::new(), seed));
+        }
+        return Ok(lidar.sense_with_seed(collidables, seed));
     }
 
-    pub fn update_from_config(&mut self, config: &RobotConfig) {
-        self.pose = config.pose;
+    /// Reseeds the noise of this robot.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = SimRng::new(seed);
+    }
+
+    pub fn inject_fault(&mut self, fault: Fault) {
+        if !self.has_fault(fault) {
+            self.faults.push(fault);
+        }
 
-        // Stepping will set the LiDar position also
-        self.step(&(self.pose.0, self.pose.1, self.pose.2));
+        if fault == Fault::MotorFailure {
+            self.stop();
+        }
+    }
+
+    pub fn clear_fault(&mut self, fault: Fault) {
+        self.faults.retain(|f| *f != fault);
+    }
+
+    /// Commands the speeds of the wheels in rad/s, in the order of `WheelLayout`.
+    /// They are converted to a velocity command, which then applies as usual.
+    pub fn control_wheels(&mut self, speeds: &[f32]) -> XironResult<()> {
+        let wheels = match &self.wheels {
+            Some(wheels) => wheels,
+            None => return Err(XironError::NoWheels(self.id.clone())),
+        };
+
+        match wheels.layout.to_velocity(&wheels.config, speeds) {
+            Some(vel) => {
+                self.control(vel);
+                return Ok(());
+            }
+            None => {
+                return Err(XironError::InvalidCommand(format!(
+                    "Robot {} has {} wheels, got {} wheel speeds",
+                    self.id,
+                    wheels.layout.num_wheels(),
+                    speeds.len()
+                )))
+            }
+        }
+    }
+
+    /// Ticks counted by the wheel encoders since the robot was created.
+    pub fn wheel_ticks(&self) -> XironResult<Vec<i64>> {
+        match &self.wheels {
+            Some(wheels) => return Ok(wheels.ticks()),
+            None => return Err(XironError::NoWheels(self.id.clone())),
+        }
+    }
+
+    pub fn has_fault(&self, fault: Fault) -> bool {
+        return self.faults.contains(&fault);
+    }
+
+    pub fn update_from_config(&mut self, config: &RobotConfig) {
+        // Placing will set the LiDar position also
+        self.place(&config.pose);
     }
 
     pub fn into_config(&self) -> RobotConfig {
@@ -296,18 +748,41 @@ impl Robot {
 
         RobotConfig {
             id: self.id.clone(),
+            model: self.model.clone(),
             pose: self.pose,
             vel: self.vel,
             lidar: lidar,
             footprint: extents,
-            drive_type: self.drive_type,
+            drive_type: self.drive_type.clone(),
             add_noise: self.add_noise,
+            limits: self.limits,
+            actuator: self.actuator.as_ref().map(|a| a.config),
+            noise: self.noise,
+            wheels: self.wheels.as_ref().map(|w| w.config),
+            skid_steer: self.skid_steer,
+            trailers: self
+                .trailers
+                .iter()
+                .zip(self.trailer_angles())
+                .map(|(trailer, angle)| TrailerConfig {
+                    angle,
+                    ..trailer.config.clone()
+                })
+                .collect(),
+            body: self.body.map(|b| b.config),
+            collision_response: self.collision_response,
         }
     }
 }
 
 impl Drawable for Robot {
     fn draw(&self, tf: fn((f32, f32)) -> (f32, f32)) {
+        let headings: Vec<f32> = self.trailers.iter().map(|t| t.heading).collect();
+        let poses = trailer_poses(&self.trailers, self.pose, &headings);
+        for (trailer, (hitch, _)) in self.trailers.iter().zip(poses) {
+            trailer.draw(hitch, tf);
+        }
+
         match self.shape {
             Footprint::Circular(b) => {
                 let r = b.radius;
@@ -498,12 +973,11 @@ impl GuiObject for Robot {
     }
 
     fn modify_position(&mut self, x: f32, y: f32) {
-        self.pose.0 = x;
-        self.pose.1 = y;
+        self.place(&(x, y, self.pose.2));
     }
 
     fn modify_rotation(&mut self, angle: f32) {
-        self.pose.2 = angle;
+        self.place(&(self.pose.0, self.pose.1, angle));
     }
 }
 
@@ -41,14 +41,23 @@ impl LiDAR {
         }
     }
 
+    /// Sets the field of view and the number of readings.
+    pub fn with_range(mut self, angle_min: f32, angle_max: f32, num_readings: i32) -> LiDAR {
+        self.angle_min = angle_min;
+        self.angle_max = angle_max;
+        self.num_readings = num_readings;
+        self.translate_to(self.pose);
+        self
+    }
+
     pub fn translate_to(&mut self, new_pose: (f32, f32, f32)) {
         let mut rays = Vec::new();
         let dtheta = (self.angle_max - self.angle_min) / self.num_readings as f32;
         for dt in 0..self.num_readings {
             let theta = self.angle_min + dt as f32 * dtheta;
-            let angle = self.pose.2 + theta as f32;
+            let angle = new_pose.2 + theta as f32;
             let ray = Ray::new(
-                Point::new(self.pose.0, self.pose.1),
+                Point::new(new_pose.0, new_pose.1),
                 Vector::new(angle.cos(), angle.sin()),
             );
 
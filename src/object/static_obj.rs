@@ -4,9 +4,12 @@ use parry2d::shape::Cuboid;
 
 use crate::{
     behaviour::traits::{Collidable, Drawable, GuiObject},
+    parser::{BodyConfig, StaticObjConfig},
     prelude::traits::Genericbject,
 };
 
+use super::rigid_body::RigidBody;
+
 #[derive(Debug, Clone)]
 pub struct StaticObj {
     pub center: (f32, f32),
@@ -14,6 +17,15 @@ pub struct StaticObj {
     pub height: f32,
     pub rotation: f32,
 
+    // Optional name so that scenarios can refer to this object
+    pub id: Option<String>,
+
+    // An open object (e.g. an open door) is not collided with or sensed
+    pub open: bool,
+
+    // A rigid body can be pushed around, anything else stays where it is
+    pub body: Option<RigidBody>,
+
     // collision
     pub shape: Cuboid,
 }
@@ -26,9 +38,52 @@ impl StaticObj {
             height,
             rotation,
 
+            id: None,
+            open: false,
+            body: None,
+
             shape: Cuboid::new(Vector::new(width * 0.5, height * 0.5)),
         }
     }
+
+    /// Creates the object a config describes, including its rigid body.
+    pub fn from_config(config: &StaticObjConfig) -> StaticObj {
+        return StaticObj::new(config.center, config.width, config.height, config.rotation)
+            .with_id(config.id.clone())
+            .with_body(config.body);
+    }
+
+    /// Describes the object as a config entry.
+    pub fn into_config(&self) -> StaticObjConfig {
+        return StaticObjConfig {
+            center: self.center,
+            width: self.width,
+            height: self.height,
+            rotation: self.rotation,
+            id: self.id.clone(),
+            body: self.body.map(|b| b.config),
+        };
+    }
+
+    pub fn with_id(mut self, id: Option<String>) -> StaticObj {
+        self.id = id;
+        self
+    }
+
+    pub fn with_body(mut self, body: Option<BodyConfig>) -> StaticObj {
+        self.body = body.map(RigidBody::new);
+        self
+    }
+
+    /// Whether the object is a rigid body that can be pushed.
+    pub fn is_movable(&self) -> bool {
+        return self.body.is_some();
+    }
+
+    /// Mean distance of the points of the object from its center.
+    pub fn radius(&self) -> f32 {
+        return 0.25 * (self.width + self.height);
+    }
 }
 
 impl Drawable for StaticObj {
@@ -56,6 +111,20 @@ impl Drawable for StaticObj {
         let tf_p3 = tf((x3, y3));
         let tf_p4 = tf((x4, y4));
 
+        if self.open {
+            // Open objects are only drawn as an outline
+            let corners = [tf_p1, tf_p2, tf_p3, tf_p4];
+            for i in 0..corners.len() {
+                let p0 = corners[i];
+                let p1 = corners[(i + 1) % corners.len()];
+                draw_line(p0.0, p0.1, p1.0, p1.1, 2.0, GRAY);
+            }
+            return;
+        }
+
+        // Rigid bodies are brown, so that it is clear what can be pushed
+        let color = if self.is_movable() { BROWN } else { GRAY };
+
         // Draw the body
         draw_triangle(
             Vec2 {
@@ -70,7 +139,7 @@ impl Drawable for StaticObj {
                 x: tf_p3.0,
                 y: tf_p3.1,
             },
-            GRAY,
+            color,
         );
         draw_triangle(
             Vec2 {
@@ -85,7 +154,7 @@ impl Drawable for StaticObj {
                 x: tf_p4.0,
                 y: tf_p4.1,
             },
-            GRAY,
+            color,
         );
     }
 
@@ -188,4 +257,16 @@ impl Genericbject for StaticObj {
     fn get_collidable(&self) -> Box<dyn Collidable> {
         return Box::new(Self::clone(&self));
     }
+
+    fn get_name(&self) -> Option<String> {
+        return self.id.clone();
+    }
+
+    fn is_enabled(&self) -> bool {
+        return !self.open;
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.open = !enabled;
+    }
 }
@@ -0,0 +1,150 @@
+//! Trailers hitched behind a robot, e.g. carts pulled by a tug.
+//!
+//! The first trailer hangs on a hitch behind the center of the robot, every other
+//! one on a hitch behind the axle of the trailer in front of it. A trailer only
+//! turns as far as its hitch pulls it around, so it follows the robot on the
+//! inside of curves and jackknifes when pushed backwards.
+
+use macroquad::prelude::*;
+use parry2d::math::Vector;
+use parry2d::shape::{Ball, Cuboid};
+
+use crate::parameter::RESOLUTION;
+use crate::parser::TrailerConfig;
+use crate::utils::{draw_rotated_rectangle, normalise};
+
+use super::robot::{Footprint, DEFAULT_ROBOT_RADIUS};
+
+#[derive(Debug, Clone)]
+pub struct Trailer {
+    pub config: TrailerConfig,
+    /// Heading in the world frame, pointing from the axle to the hitch
+    pub heading: f32,
+    /// Pose of the center of the footprint
+    pub pose: (f32, f32, f32),
+    pub shape: Footprint,
+}
+
+impl Trailer {
+    pub fn new(config: TrailerConfig, heading: f32) -> Trailer {
+        let shape = match config.footprint.len() {
+            2 => Footprint::Rectangular(Cuboid {
+                half_extents: Vector::new(config.footprint[0] * 0.5, config.footprint[1] * 0.5),
+            }),
+            1 => Footprint::Circular(Ball {
+                radius: config.footprint[0],
+            }),
+            _ => Footprint::Circular(Ball {
+                radius: DEFAULT_ROBOT_RADIUS,
+            }),
+        };
+
+        return Trailer {
+            config,
+            heading,
+            pose: (0.0, 0.0, heading),
+            shape,
+        };
+    }
+
+    pub fn draw(&self, hitch: (f32, f32), tf: fn((f32, f32)) -> (f32, f32)) {
+        match self.shape {
+            Footprint::Circular(b) => {
+                let tf_pos = tf((self.pose.0, self.pose.1));
+                draw_circle(tf_pos.0, tf_pos.1, b.radius / RESOLUTION, DARKGRAY);
+            }
+            Footprint::Rectangular(c) => {
+                draw_rotated_rectangle(
+                    (self.pose.0, self.pose.1),
+                    (c.half_extents.x, c.half_extents.y),
+                    self.pose.2,
+                    DARKGRAY,
+                    tf,
+                );
+            }
+        }
+
+        // Drawbar from the axle to the hitch
+        let axle = behind(hitch, self.heading, self.config.length);
+        let tf_hitch = tf(hitch);
+        let tf_axle = tf(axle);
+        draw_line(tf_hitch.0, tf_hitch.1, tf_axle.0, tf_axle.1, 2.0, BLACK);
+        draw_circle(tf_hitch.0, tf_hitch.1, 3.0, RED);
+    }
+}
+
+/// Hitch point and pose of the center of a trailer.
+pub type TrailerPlacement = ((f32, f32), (f32, f32, f32));
+
+// The point `distance` behind `point` when facing `heading`
+fn behind(point: (f32, f32), heading: f32, distance: f32) -> (f32, f32) {
+    return (
+        point.0 - distance * heading.cos(),
+        point.1 - distance * heading.sin(),
+    );
+}
+
+/// Hitch points and center poses of the trailers behind a robot at `pose`, when
+/// the trailers have the given headings.
+pub fn trailer_poses(
+    trailers: &[Trailer],
+    pose: (f32, f32, f32),
+    headings: &[f32],
+) -> Vec<TrailerPlacement> {
+    let mut poses = Vec::with_capacity(trailers.len());
+    let mut front = pose;
+    for (trailer, heading) in trailers.iter().zip(headings) {
+        let hitch = behind((front.0, front.1), front.2, trailer.config.hitch_offset);
+        let center = behind(hitch, *heading, 0.5 * trailer.config.length);
+        let axle = behind(hitch, *heading, trailer.config.length);
+
+        poses.push((hitch, (center.0, center.1, *heading)));
+        front = (axle.0, axle.1, *heading);
+    }
+    return poses;
+}
+
+/// Headings of the trailers after the robot moved from `start` to `end`.
+pub fn pulled_headings(
+    trailers: &[Trailer],
+    start: (f32, f32, f32),
+    end: (f32, f32, f32),
+) -> Vec<f32> {
+    let mut headings = Vec::with_capacity(trailers.len());
+    let mut front_start = start;
+    let mut front_end = end;
+    for trailer in trailers.iter() {
+        let length = trailer.config.length;
+        let hitch_start = behind(
+            (front_start.0, front_start.1),
+            front_start.2,
+            trailer.config.hitch_offset,
+        );
+        let hitch_end = behind(
+            (front_end.0, front_end.1),
+            front_end.2,
+            trailer.config.hitch_offset,
+        );
+
+        // The hitch moves in a straight line, along which the angle phi between
+        // the trailer and the direction of motion decays as tan(phi / 2) ~ exp(-s / L)
+        let dx = hitch_end.0 - hitch_start.0;
+        let dy = hitch_end.1 - hitch_start.1;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let heading = if distance > 1e-6 && length > 0.0 {
+            let direction = dy.atan2(dx);
+            let phi = normalise(trailer.heading - direction);
+            let phi = 2.0 * ((0.5 * phi).tan() * (-distance / length).exp()).atan();
+            normalise(direction + phi)
+        } else {
+            trailer.heading
+        };
+        headings.push(heading);
+
+        let axle_start = behind(hitch_start, trailer.heading, length);
+        let axle_end = behind(hitch_end, heading, length);
+        front_start = (axle_start.0, axle_start.1, trailer.heading);
+        front_end = (axle_end.0, axle_end.1, heading);
+    }
+    return headings;
+}
@@ -26,9 +26,9 @@ impl Wall {
 
 impl Drawable for Wall {
     fn draw(&self, tf: fn((f32, f32)) -> (f32, f32)) {
-        for i in 0..self.coords.len() - 1 {
-            let c1 = self.coords[i];
-            let c2 = self.coords[i + 1];
+        for pair in self.coords.windows(2) {
+            let c1 = pair[0];
+            let c2 = pair[1];
 
             let c1_tfed = tf(c1);
             let c2_tfed = tf(c2);
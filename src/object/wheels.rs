@@ -0,0 +1,135 @@
+//! Wheel speed commands and wheel encoders of differential and mecanum robots.
+//!
+//! Wheel speeds are converted to the velocity command of the kinematic model of
+//! the robot, so limits, actuator dynamics and noise apply to them as well.
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+use crate::parser::WheelConfig;
+
+use super::kinematics::KinematicModel;
+
+/// How the wheels of a robot are arranged.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WheelLayout {
+    /// Left and right wheel
+    Differential,
+    /// Front left, front right, rear left and rear right mecanum wheel
+    Mecanum,
+}
+
+impl WheelLayout {
+    /// Layout of a robot moved by `model`. Holonomic robots have mecanum wheels.
+    /// Steered robots can not be driven by wheel speeds.
+    pub fn for_model(model: &dyn KinematicModel) -> Option<WheelLayout> {
+        if model.is_steered() {
+            return None;
+        }
+        if model.is_holonomic() {
+            return Some(WheelLayout::Mecanum);
+        }
+        return Some(WheelLayout::Differential);
+    }
+
+    pub fn num_wheels(&self) -> usize {
+        match self {
+            WheelLayout::Differential => 2,
+            WheelLayout::Mecanum => 4,
+        }
+    }
+
+    /// Velocity `(vx, vy, w)` of a robot whose wheels turn at `speeds` in rad/s.
+    /// `None` if the number of speeds does not match the layout.
+    pub fn to_velocity(&self, config: &WheelConfig, speeds: &[f32]) -> Option<(f32, f32, f32)> {
+        if speeds.len() != self.num_wheels() {
+            return None;
+        }
+
+        let r = config.radius;
+        match self {
+            WheelLayout::Differential => {
+                let (left, right) = (speeds[0], speeds[1]);
+                let v = r * (left + right) / 2.0;
+                let w = r * (right - left) / config.track_width;
+                return Some((v, 0.0, w));
+            }
+            WheelLayout::Mecanum => {
+                let (fl, fr, rl, rr) = (speeds[0], speeds[1], speeds[2], speeds[3]);
+                let k = lever(config);
+                let vx = r * (fl + fr + rl + rr) / 4.0;
+                let vy = r * (-fl + fr + rl - rr) / 4.0;
+                let w = r * (-fl + fr - rl + rr) / (4.0 * k);
+                return Some((vx, vy, w));
+            }
+        }
+    }
+
+    /// Speeds of the wheels in rad/s that move a robot with `vel`.
+    pub fn to_wheel_speeds(&self, config: &WheelConfig, vel: (f32, f32, f32)) -> Vec<f32> {
+        let r = config.radius;
+        match self {
+            WheelLayout::Differential => {
+                let half_track = 0.5 * config.track_width;
+                return vec![
+                    (vel.0 - vel.2 * half_track) / r,
+                    (vel.0 + vel.2 * half_track) / r,
+                ];
+            }
+            WheelLayout::Mecanum => {
+                let k = lever(config);
+                return vec![
+                    (vel.0 - vel.1 - k * vel.2) / r,
+                    (vel.0 + vel.1 + k * vel.2) / r,
+                    (vel.0 + vel.1 - k * vel.2) / r,
+                    (vel.0 - vel.1 + k * vel.2) / r,
+                ];
+            }
+        }
+    }
+}
+
+// Sum of the half wheelbase and half track width of a mecanum robot
+fn lever(config: &WheelConfig) -> f32 {
+    return 0.5 * (config.wheelbase + config.track_width);
+}
+
+/// Encoders on the wheels of a robot. They count how far every wheel turned,
+/// including the slip of the wheels on the ground.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WheelEncoder {
+    pub config: WheelConfig,
+    pub layout: WheelLayout,
+
+    // Rotation of every wheel in radians since the robot was created
+    angles: Vec<f64>,
+}
+
+impl WheelEncoder {
+    pub fn new(config: WheelConfig, layout: WheelLayout) -> WheelEncoder {
+        return WheelEncoder {
+            config,
+            layout,
+            angles: vec![0.0; layout.num_wheels()],
+        };
+    }
+
+    /// Turns the wheels as far as moving with `vel` for `dt` seconds takes.
+    pub fn update(&mut self, vel: (f32, f32, f32), dt: f32) {
+        let grip = 1.0 - self.config.slip.clamp(0.0, 0.99);
+        let speeds = self.layout.to_wheel_speeds(&self.config, vel);
+        for (angle, speed) in self.angles.iter_mut().zip(speeds) {
+            *angle += (speed / grip * dt) as f64;
+        }
+    }
+
+    /// Ticks counted by every encoder, in the order of the wheel speeds.
+    pub fn ticks(&self) -> Vec<i64> {
+        let ticks_per_radian = self.config.ticks_per_revolution as f64 / TAU;
+        return self
+            .angles
+            .iter()
+            .map(|angle| (angle * ticks_per_radian).floor() as i64)
+            .collect();
+    }
+}
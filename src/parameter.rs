@@ -7,5 +7,18 @@ pub const HEIGHT: f32 = (YLIMS.1 - YLIMS.0) / RESOLUTION;
 
 pub const DATA_SEND_FREQ: f64 = 10.0;
 
+// Readings of a LiDAR that hit nothing are reported at this range
+pub const LIDAR_MAX_RANGE: f32 = 20.0;
+
+// Acceleration due to gravity in m/s², for the friction of bodies with the floor
+pub const GRAVITY: f32 = 9.81;
+
+// Seconds between checks of the world file for changes
+pub const RELOAD_CHECK_PERIOD: f64 = 1.0;
+
+// Simulation seconds between snapshots of the timeline, and how many are kept
+pub const SNAPSHOT_PERIOD: f32 = 0.1;
+pub const SNAPSHOT_HISTORY_LENGTH: usize = 600;
+
 // TODO: xlims and ylims should scale accrding to the height and width of the Window
 // and not the other way around?
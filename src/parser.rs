@@ -1,37 +1,475 @@
 use serde::{Deserialize, Serialize};
-use serde_yaml;
 
+use crate::error::XironResult;
 use crate::prelude::DriveType;
+use crate::validation::load_config;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct WallConfig {
     pub endpoints: Vec<(f32, f32)>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct StaticObjConfig {
     pub center: (f32, f32),
     pub width: f32,
     pub height: f32,
     pub rotation: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Makes the object a rigid body that robots and other bodies can push
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<BodyConfig>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Limits on the velocity of a robot and how fast it may change. Limits that are
+/// not set are not enforced.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct LimitsConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_linear_vel: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_angular_vel: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_linear_accel: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_angular_accel: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_steering_angle: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_steering_rate: Option<f32>,
+}
+
+impl LimitsConfig {
+    pub fn is_unlimited(&self) -> bool {
+        return *self == LimitsConfig::default();
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum ActuatorOrder {
+    #[default]
+    First,
+    Second,
+}
+
+/// How the motors of a robot respond to velocity commands. Robots without it
+/// follow their commands instantly.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ActuatorConfig {
+    #[serde(default)]
+    pub order: ActuatorOrder,
+    /// Time constant of the response in seconds. 0 follows the command instantly.
+    pub time_constant: f32,
+    /// Damping ratio of a second order response. 1 is critically damped.
+    #[serde(default = "default_damping")]
+    pub damping: f32,
+    /// Commands smaller than this are treated as 0
+    #[serde(default)]
+    pub deadband: f32,
+    /// Ticks between a command and the motors reacting to it
+    #[serde(default)]
+    pub latency: usize,
+}
+
+fn default_damping() -> f32 {
+    return 1.0;
+}
+
+/// Noise on the velocity a robot moves with, drawn every tick. The velocity
+/// reported to clients stays free of it, so odometry drifts from the true pose.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct NoiseConfig {
+    /// Standard deviation of Gaussian noise on each value of the velocity
+    #[serde(default)]
+    pub sigma: (f32, f32, f32),
+    /// Standard deviation of Gaussian noise relative to each value, e.g. 0.05 for 5 %
+    #[serde(default)]
+    pub proportional: (f32, f32, f32),
+    /// Constant offset of each value
+    #[serde(default)]
+    pub bias: (f32, f32, f32),
+    /// Constant relative error of each value, e.g. 0.02 for wheels 2 % larger than assumed
+    #[serde(default)]
+    pub scale_error: (f32, f32, f32),
+    /// Angular velocity per linear speed in rad/m, as caused by mismatched wheel radii
+    #[serde(default)]
+    pub yaw_drift: f32,
+}
+
+/// Wheels of a differential or mecanum robot. Robots need them to take wheel
+/// speed commands and to publish wheel encoder ticks.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct WheelConfig {
+    /// Radius of the wheels in meters
+    pub radius: f32,
+    /// Distance between the left and right wheels in meters
+    pub track_width: f32,
+    /// Distance between the front and rear wheels of mecanum robots in meters
+    #[serde(default)]
+    pub wheelbase: f32,
+    /// Encoder ticks per revolution of a wheel
+    #[serde(default = "default_ticks_per_revolution")]
+    pub ticks_per_revolution: u32,
+    /// Slip ratio of the wheels. The wheels turn `1 / (1 - slip)` times as far as
+    /// the robot moves, so encoder odometry overestimates the distance travelled.
+    #[serde(default)]
+    pub slip: f32,
+}
+
+fn default_ticks_per_revolution() -> u32 {
+    return 1024;
+}
+
+/// Tracks of a SkidSteer robot. By default the tracks span the width of the
+/// footprint and do not slip.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct SkidSteerConfig {
+    /// Lateral distance of the ICR of the left track from the center in meters.
+    /// Defaults to half the width of the footprint. Larger values turn slower.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icr_left: Option<f32>,
+    /// Lateral distance of the ICR of the right track from the center in meters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icr_right: Option<f32>,
+    /// Longitudinal position of the ICR of the robot in meters, positive ahead of
+    /// the center. The robot slides sideways by this times its angular velocity.
+    #[serde(default)]
+    pub icr_offset: f32,
+    /// Longitudinal slip ratio of the left track
+    #[serde(default)]
+    pub slip_left: f32,
+    /// Longitudinal slip ratio of the right track
+    #[serde(default)]
+    pub slip_right: f32,
+}
+
+/// A trailer hitched behind a robot or behind the trailer in front of it.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct TrailerConfig {
+    /// Distance of the hitch behind the center of the robot, or behind the axle of
+    /// the trailer in front, in meters
+    pub hitch_offset: f32,
+    /// Distance from the hitch to the axle of the trailer in meters
+    pub length: f32,
+    /// Radius, or length and width, of the trailer. Centered between hitch and axle.
+    pub footprint: Vec<f32>,
+    /// Angle of the trailer relative to the vehicle in front of it in rad
+    #[serde(default)]
+    pub angle: f32,
+}
+
+/// Mass and material of something that can be pushed around.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct BodyConfig {
+    /// Mass in kg
+    pub mass: f32,
+    /// Coefficient of friction with the floor. A sliding body slows down by
+    /// `friction * 9.81` m/s every second.
+    #[serde(default = "default_friction")]
+    pub friction: f32,
+    /// Share of the approach speed a body bounces back with, from 0 to 1
+    #[serde(default)]
+    pub restitution: f32,
+}
+
+fn default_friction() -> f32 {
+    return 0.5;
+}
+
+/// What a robot does when it hits a wall, a closed object or a robot it can not push.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum CollisionResponse {
+    /// Stops at the contact until it gets a new command
+    #[default]
+    Stop,
+    /// Slides along the obstacle with what is left of its motion
+    Slide,
+    /// Bounces off the obstacle
+    Bounce,
+}
+
+/// How the motion of the world is simulated.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct PhysicsConfig {
+    /// Integration steps per tick. Collisions are checked after every substep, so
+    /// more substeps keep fast robots from passing through thin walls.
+    #[serde(default = "default_substeps")]
+    pub substeps: usize,
+    /// Response of robots that do not set their own
+    #[serde(default)]
+    pub collision_response: CollisionResponse,
+    /// Share of the speed into an obstacle a robot bounces back with
+    #[serde(default = "default_restitution")]
+    pub restitution: f32,
+}
+
+fn default_substeps() -> usize {
+    return 1;
+}
+
+fn default_restitution() -> f32 {
+    return 0.5;
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        return PhysicsConfig {
+            substeps: default_substeps(),
+            collision_response: CollisionResponse::default(),
+            restitution: default_restitution(),
+        };
+    }
+}
+
+impl PhysicsConfig {
+    pub fn is_default(&self) -> bool {
+        return *self == PhysicsConfig::default();
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct RobotConfig {
     pub id: String,
+    /// Name of the robot model this robot was created from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
     pub pose: (f32, f32, f32),
     pub vel: (f32, f32, f32),
     pub lidar: bool,
     pub footprint: Vec<f32>,
     pub drive_type: DriveType,
     pub add_noise: bool,
+    #[serde(default, skip_serializing_if = "LimitsConfig::is_unlimited")]
+    pub limits: LimitsConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actuator: Option<ActuatorConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise: Option<NoiseConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wheels: Option<WheelConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skid_steer: Option<SkidSteerConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trailers: Vec<TrailerConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<BodyConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collision_response: Option<CollisionResponse>,
 }
 
+/// A named robot definition that robots in the config can refer to.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RobotModelConfig {
+    pub name: String,
+    pub footprint: Vec<f32>,
+    pub drive_type: DriveType,
+    #[serde(default)]
+    pub lidar: bool,
+    #[serde(default)]
+    pub add_noise: bool,
+    #[serde(default, skip_serializing_if = "LimitsConfig::is_unlimited")]
+    pub limits: LimitsConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actuator: Option<ActuatorConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise: Option<NoiseConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wheels: Option<WheelConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skid_steer: Option<SkidSteerConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trailers: Vec<TrailerConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<BodyConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collision_response: Option<CollisionResponse>,
+}
+
+/// Robots refer to a model by name and override only the fields they set.
+/// Robots without a model have to set everything.
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "RawConfig", into = "RawConfig")]
 pub struct Config {
+    pub robot_models: Vec<RobotModelConfig>,
     pub robots: Vec<RobotConfig>,
     pub walls: Vec<WallConfig>,
     pub static_objects: Vec<StaticObjConfig>,
+    pub physics: PhysicsConfig,
+}
+
+impl Config {
+    pub fn get_robot_model(&self, name: &str) -> Option<&RobotModelConfig> {
+        return self.robot_models.iter().find(|m| m.name == name);
+    }
+}
+
+// A robot as written in the file. Fields that are not set come from the model.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct RawRobotConfig {
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    pose: (f32, f32, f32),
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vel: Option<(f32, f32, f32)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lidar: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    footprint: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    drive_type: Option<DriveType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    add_noise: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limits: Option<LimitsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    actuator: Option<ActuatorConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    noise: Option<NoiseConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wheels: Option<WheelConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    skid_steer: Option<SkidSteerConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trailers: Option<Vec<TrailerConfig>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<BodyConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    collision_response: Option<CollisionResponse>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct RawConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    robot_models: Vec<RobotModelConfig>,
+    // Files that only add to others, e.g. a building shell, may leave these out
+    #[serde(default)]
+    robots: Vec<RawRobotConfig>,
+    #[serde(default)]
+    walls: Vec<WallConfig>,
+    #[serde(default)]
+    static_objects: Vec<StaticObjConfig>,
+    #[serde(default, skip_serializing_if = "PhysicsConfig::is_default")]
+    physics: PhysicsConfig,
+}
+
+impl From<RawConfig> for Config {
+    fn from(raw: RawConfig) -> Self {
+        let mut robots = Vec::new();
+        for robot in raw.robots.into_iter() {
+            // Unknown models are reported by validation. Until then, such a robot
+            // only gets what it sets itself.
+            let model = robot
+                .model
+                .as_ref()
+                .and_then(|name| raw.robot_models.iter().find(|m| m.name == *name));
+
+            robots.push(RobotConfig {
+                id: robot.id,
+                pose: robot.pose,
+                vel: robot.vel.unwrap_or((0.0, 0.0, 0.0)),
+                lidar: robot.lidar.or(model.map(|m| m.lidar)).unwrap_or(false),
+                footprint: robot
+                    .footprint
+                    .or(model.map(|m| m.footprint.clone()))
+                    .unwrap_or_default(),
+                drive_type: robot
+                    .drive_type
+                    .or(model.map(|m| m.drive_type.clone()))
+                    .unwrap_or_default(),
+                add_noise: robot
+                    .add_noise
+                    .or(model.map(|m| m.add_noise))
+                    .unwrap_or(false),
+                limits: robot.limits.or(model.map(|m| m.limits)).unwrap_or_default(),
+                actuator: robot.actuator.or(model.and_then(|m| m.actuator)),
+                noise: robot.noise.or(model.and_then(|m| m.noise)),
+                wheels: robot.wheels.or(model.and_then(|m| m.wheels)),
+                skid_steer: robot.skid_steer.or(model.and_then(|m| m.skid_steer)),
+                trailers: robot
+                    .trailers
+                    .or(model.map(|m| m.trailers.clone()))
+                    .unwrap_or_default(),
+                body: robot.body.or(model.and_then(|m| m.body)),
+                collision_response: robot
+                    .collision_response
+                    .or(model.and_then(|m| m.collision_response)),
+                model: robot.model,
+            });
+        }
+
+        return Config {
+            robot_models: raw.robot_models,
+            robots,
+            walls: raw.walls,
+            static_objects: raw.static_objects,
+            physics: raw.physics,
+        };
+    }
+}
+
+impl From<Config> for RawConfig {
+    fn from(config: Config) -> Self {
+        let mut robots = Vec::new();
+        for robot in config.robots.iter() {
+            let model = robot
+                .model
+                .as_ref()
+                .and_then(|name| config.get_robot_model(name));
+
+            // Only write what differs from the model
+            let raw = match model {
+                Some(m) => RawRobotConfig {
+                    id: robot.id.clone(),
+                    model: robot.model.clone(),
+                    pose: robot.pose,
+                    vel: Some(robot.vel).filter(|v| *v != (0.0, 0.0, 0.0)),
+                    lidar: Some(robot.lidar).filter(|l| *l != m.lidar),
+                    footprint: Some(robot.footprint.clone()).filter(|f| *f != m.footprint),
+                    drive_type: Some(robot.drive_type.clone()).filter(|d| *d != m.drive_type),
+                    add_noise: Some(robot.add_noise).filter(|n| *n != m.add_noise),
+                    limits: Some(robot.limits).filter(|l| *l != m.limits),
+                    actuator: robot.actuator.filter(|a| Some(*a) != m.actuator),
+                    noise: robot.noise.filter(|n| Some(*n) != m.noise),
+                    wheels: robot.wheels.filter(|w| Some(*w) != m.wheels),
+                    skid_steer: robot.skid_steer.filter(|k| Some(*k) != m.skid_steer),
+                    trailers: Some(robot.trailers.clone()).filter(|t| *t != m.trailers),
+                    body: robot.body.filter(|b| Some(*b) != m.body),
+                    collision_response: robot
+                        .collision_response
+                        .filter(|c| Some(*c) != m.collision_response),
+                },
+                None => RawRobotConfig {
+                    id: robot.id.clone(),
+                    model: robot.model.clone(),
+                    pose: robot.pose,
+                    vel: Some(robot.vel),
+                    lidar: Some(robot.lidar),
+                    footprint: Some(robot.footprint.clone()),
+                    drive_type: Some(robot.drive_type.clone()),
+                    add_noise: Some(robot.add_noise),
+                    limits: Some(robot.limits).filter(|l| !l.is_unlimited()),
+                    actuator: robot.actuator,
+                    noise: robot.noise,
+                    wheels: robot.wheels,
+                    skid_steer: robot.skid_steer,
+                    trailers: Some(robot.trailers.clone()).filter(|t| !t.is_empty()),
+                    body: robot.body,
+                    collision_response: robot.collision_response,
+                },
+            };
+            robots.push(raw);
+        }
+
+        return RawConfig {
+            robot_models: config.robot_models,
+            robots,
+            walls: config.walls,
+            static_objects: config.static_objects,
+            physics: config.physics,
+        };
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -56,18 +494,16 @@ pub struct PositionArray {
     pub positions: Vec<Position>,
 }
 
-pub fn get_config_from_file(path: String) -> Option<Config> {
-    let file_result = std::fs::File::open(path);
-    match file_result {
-        Ok(file) => {
-            let config: Config =
-                serde_yaml::from_reader(file).expect("Couldn't read config file. Rewrite properly");
+/// Loads and validates the config. Every problem found is returned in
+/// `XironError::InvalidConfig`.
+pub fn get_config_from_file(path: String) -> XironResult<Config> {
+    let config = load_config(&path)?;
+    return Ok(config);
+}
 
-            return Some(config);
-        }
-        Err(e) => {
-            println!("Error in opening file: {}", e);
-            return None;
-        }
-    }
+/// Writes the config to `path` as YAML, replacing the file if it exists.
+pub fn save_config_to_file(config: &Config, path: &str) -> XironResult<()> {
+    let f = std::fs::File::create(path)?;
+    serde_yaml::to_writer(f, config)?;
+    return Ok(());
 }
@@ -1,11 +1,21 @@
 pub use crate::algorithms::*;
 pub use crate::behaviour::*;
 pub use crate::camera_handler::*;
+pub use crate::entity::*;
+pub use crate::error::*;
+pub use crate::experiment::*;
+pub use crate::generator::*;
 pub use crate::gui_interface::*;
 pub use crate::handler::*;
+pub use crate::include::*;
+pub use crate::metrics::*;
 pub use crate::object::*;
 pub use crate::parameter::*;
 pub use crate::parser::*;
+pub use crate::scenario::*;
+pub use crate::session::*;
+pub use crate::snapshot::*;
 pub use crate::utils::*;
+pub use crate::validation::*;
 
 pub use crate::comms::*;
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::object::Fault;
+use crate::parser::{RobotConfig, StaticObjConfig};
+use crate::scenario::criteria::CriterionConfig;
+
+/// Length of a scenario in seconds if it does not specify a `duration`.
+pub const DEFAULT_SCENARIO_DURATION: f32 = 60.0;
+
+/// Condition that has to hold for a conditional event to fire.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConditionConfig {
+    /// Robot `robot` is within `radius` of `center`.
+    RobotInRegion {
+        robot: String,
+        center: (f32, f32),
+        radius: f32,
+    },
+    /// Robots `robot` and `other` are closer than `distance` to each other.
+    RobotsWithin {
+        robot: String,
+        other: String,
+        distance: f32,
+    },
+}
+
+/// Action executed when an event fires.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActionConfig {
+    SpawnRobot {
+        robot: RobotConfig,
+    },
+    RemoveRobot {
+        id: String,
+    },
+    SpawnObstacle {
+        object: StaticObjConfig,
+    },
+    RemoveObstacle {
+        id: String,
+    },
+    MoveObstacle {
+        id: String,
+        center: (f32, f32),
+        #[serde(default)]
+        rotation: f32,
+    },
+    TeleportRobot {
+        id: String,
+        pose: (f32, f32, f32),
+    },
+    /// Sends a goal to the robot. If `drive` is set, the simulator drives the
+    /// robot to the goal itself instead of waiting for a client to do it.
+    SendGoal {
+        id: String,
+        goal: (f32, f32, f32),
+        #[serde(default)]
+        drive: bool,
+    },
+    ToggleDoor {
+        id: String,
+    },
+    InjectFault {
+        id: String,
+        fault: Fault,
+    },
+    ClearFault {
+        id: String,
+        fault: Fault,
+    },
+}
+
+/// A single entry in the scenario timeline. An event fires once, as soon as
+/// the sim time has reached `at` and the `when` condition holds. An event with
+/// neither fires on the first tick.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EventConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<ConditionConfig>,
+    pub action: ActionConfig,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScenarioConfig {
+    /// Path to the world config. Relative paths are resolved from the scenario file.
+    pub world: String,
+    /// Length of the run in seconds. Only used when running headless.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f32>,
+    #[serde(default)]
+    pub events: Vec<EventConfig>,
+    #[serde(default)]
+    pub criteria: Vec<CriterionConfig>,
+}
+
+impl ScenarioConfig {
+    pub fn get_duration(&self) -> f32 {
+        return self.duration.unwrap_or(DEFAULT_SCENARIO_DURATION);
+    }
+}
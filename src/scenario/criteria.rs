@@ -0,0 +1,296 @@
+use serde::{Deserialize, Serialize};
+
+use crate::handler::SimulationHandler;
+
+/// Success criteria of a scenario. They are evaluated against the simulation every tick.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CriterionConfig {
+    /// Robot has to be within `radius` of `center` before `within` seconds.
+    ReachRegion {
+        robot: String,
+        center: (f32, f32),
+        radius: f32,
+        within: f32,
+    },
+    /// The robot (or any robot, if not given) must never collide.
+    NoCollisions {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        robot: Option<String>,
+    },
+    /// The robot (or every robot, if not given) must keep more than `distance`
+    /// from all objects and other robots.
+    MinClearance {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        robot: Option<String>,
+        distance: f32,
+    },
+}
+
+impl std::fmt::Display for CriterionConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CriterionConfig::ReachRegion {
+                robot,
+                center,
+                radius,
+                within,
+            } => write!(
+                f,
+                "{} reaches ({:.2}, {:.2}) ± {:.2} m within {:.1} s",
+                robot, center.0, center.1, radius, within
+            ),
+            CriterionConfig::NoCollisions { robot } => match robot {
+                Some(robot) => write!(f, "{} does not collide", robot),
+                None => write!(f, "no collisions"),
+            },
+            CriterionConfig::MinClearance { robot, distance } => match robot {
+                Some(robot) => write!(f, "{} keeps clearance > {:.2} m", robot, distance),
+                None => write!(f, "all robots keep clearance > {:.2} m", distance),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CriterionStatus {
+    Pending,
+    Passed,
+    Failed(String),
+}
+
+/// Evaluates the criteria of a scenario and keeps track of their status.
+pub struct ScenarioEvaluator {
+    pub criteria: Vec<CriterionConfig>,
+    pub status: Vec<CriterionStatus>,
+}
+
+impl ScenarioEvaluator {
+    pub fn new(criteria: Vec<CriterionConfig>) -> ScenarioEvaluator {
+        let status = vec![CriterionStatus::Pending; criteria.len()];
+        ScenarioEvaluator { criteria, status }
+    }
+
+    /// Evaluates all pending criteria against the current state of the simulation.
+    /// Should be called after every step.
+    pub fn update(&mut self, sh: &SimulationHandler) {
+        let time = sh.get_time();
+
+        for i in 0..self.criteria.len() {
+            if self.status[i] != CriterionStatus::Pending {
+                continue;
+            }
+
+            match &self.criteria[i] {
+                CriterionConfig::ReachRegion {
+                    robot,
+                    center,
+                    radius,
+                    within,
+                } => {
+                    if let Some(pose) = sh
+                        .get_robot_handler(robot)
+                        .and_then(|handler| sh.get_pose(&handler).ok())
+                    {
+                        let dx = pose.0 - center.0;
+                        let dy = pose.1 - center.1;
+                        if (dx * dx + dy * dy).sqrt() <= *radius {
+                            self.status[i] = CriterionStatus::Passed;
+                            continue;
+                        }
+                    }
+
+                    if time > *within {
+                        self.status[i] = CriterionStatus::Failed(format!(
+                            "{} did not reach the region in time",
+                            robot
+                        ));
+                    }
+                }
+                CriterionConfig::NoCollisions { robot } => {
+                    for (id, pose) in sh.get_collisions() {
+                        if robot.is_none() || robot.as_ref() == Some(id) {
+                            self.status[i] = CriterionStatus::Failed(format!(
+                                "{} collided at ({:.2}, {:.2}) at t = {:.3} s",
+                                id, pose.0, pose.1, time
+                            ));
+                            break;
+                        }
+                    }
+                }
+                CriterionConfig::MinClearance { robot, distance } => {
+                    for (id, handler) in sh.robot_handlers() {
+                        if robot.is_some() && robot.as_ref() != Some(&id) {
+                            continue;
+                        }
+
+                        let clearance = match sh.clearance(&handler) {
+                            Ok(clearance) => clearance,
+                            Err(_) => continue,
+                        };
+                        if clearance <= *distance {
+                            self.status[i] = CriterionStatus::Failed(format!(
+                                "{} had clearance {:.3} m at t = {:.3} s",
+                                id, clearance, time
+                            ));
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Resolves the criteria that are still pending once the run is over.
+    /// Criteria that have to hold throughout the run pass, goals that were not reached fail.
+    pub fn finish(&mut self) {
+        for i in 0..self.criteria.len() {
+            if self.status[i] != CriterionStatus::Pending {
+                continue;
+            }
+
+            self.status[i] = match &self.criteria[i] {
+                CriterionConfig::ReachRegion { robot, .. } => {
+                    CriterionStatus::Failed(format!("{} did not reach the region", robot))
+                }
+                CriterionConfig::NoCollisions { .. } => CriterionStatus::Passed,
+                CriterionConfig::MinClearance { .. } => CriterionStatus::Passed,
+            };
+        }
+    }
+
+    /// True if some criterion has already failed.
+    pub fn any_failed(&self) -> bool {
+        return self
+            .status
+            .iter()
+            .any(|s| matches!(s, CriterionStatus::Failed(_)));
+    }
+
+    /// True once no criterion is pending anymore.
+    pub fn all_decided(&self) -> bool {
+        return self.status.iter().all(|s| *s != CriterionStatus::Pending);
+    }
+
+    pub fn passed(&self) -> bool {
+        return self.all_decided() && !self.any_failed();
+    }
+
+    /// Human readable report of all criteria.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for (criterion, status) in self.criteria.iter().zip(self.status.iter()) {
+            let line = match status {
+                CriterionStatus::Pending => format!("  [PENDING] {}\n", criterion),
+                CriterionStatus::Passed => format!("  [PASS]    {}\n", criterion),
+                CriterionStatus::Failed(reason) => {
+                    format!("  [FAIL]    {}: {}\n", criterion, reason)
+                }
+            };
+            report.push_str(&line);
+        }
+        return report;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::robot::Robot;
+    use crate::object::wall::Wall;
+
+    // A robot at the origin driving along x with a wall across its path at x = 1
+    fn world(speed: f32) -> SimulationHandler {
+        let mut sh = SimulationHandler::new();
+        let robot = Robot::from_id_and_pose("robot0".to_string(), (0.0, 0.0, 0.0), 0.2);
+        let (_, handler) = sh.add_robot(robot).unwrap();
+        sh.add_wall(Wall::new(vec![(1.0, -1.0), (1.0, 1.0)]))
+            .unwrap();
+        sh.control(&handler, (speed, 0.0, 0.0)).unwrap();
+        return sh;
+    }
+
+    fn run(sh: &mut SimulationHandler, evaluator: &mut ScenarioEvaluator, ticks: usize) {
+        for _ in 0..ticks {
+            sh.step();
+            evaluator.update(sh);
+        }
+    }
+
+    #[test]
+    fn reach_region_passes_once_inside() {
+        let mut sh = world(1.0);
+        let mut evaluator = ScenarioEvaluator::new(vec![CriterionConfig::ReachRegion {
+            robot: "robot0".to_string(),
+            center: (0.5, 0.0),
+            radius: 0.1,
+            within: 2.0,
+        }]);
+
+        run(&mut sh, &mut evaluator, 60);
+        assert_eq!(evaluator.status[0], CriterionStatus::Passed);
+        assert!(evaluator.passed());
+    }
+
+    #[test]
+    fn reach_region_fails_after_the_deadline() {
+        let mut sh = world(0.0);
+        let mut evaluator = ScenarioEvaluator::new(vec![CriterionConfig::ReachRegion {
+            robot: "robot0".to_string(),
+            center: (0.5, 0.0),
+            radius: 0.1,
+            within: 0.5,
+        }]);
+
+        run(&mut sh, &mut evaluator, 20);
+        assert_eq!(evaluator.status[0], CriterionStatus::Pending);
+        run(&mut sh, &mut evaluator, 20);
+        assert!(evaluator.any_failed());
+    }
+
+    #[test]
+    fn collisions_and_clearance_fail_at_the_wall() {
+        let mut sh = world(1.0);
+        let mut evaluator = ScenarioEvaluator::new(vec![
+            CriterionConfig::NoCollisions { robot: None },
+            CriterionConfig::NoCollisions {
+                robot: Some("robot1".to_string()),
+            },
+            CriterionConfig::MinClearance {
+                robot: Some("robot0".to_string()),
+                distance: 0.3,
+            },
+        ]);
+
+        run(&mut sh, &mut evaluator, 120);
+        assert!(matches!(evaluator.status[0], CriterionStatus::Failed(_)));
+        assert_eq!(evaluator.status[1], CriterionStatus::Pending);
+        assert!(matches!(evaluator.status[2], CriterionStatus::Failed(_)));
+    }
+
+    #[test]
+    fn finish_passes_invariants_and_fails_goals() {
+        let mut sh = world(0.0);
+        let mut evaluator = ScenarioEvaluator::new(vec![
+            CriterionConfig::NoCollisions { robot: None },
+            CriterionConfig::MinClearance {
+                robot: None,
+                distance: 0.1,
+            },
+            CriterionConfig::ReachRegion {
+                robot: "robot0".to_string(),
+                center: (5.0, 5.0),
+                radius: 0.1,
+                within: 10.0,
+            },
+        ]);
+
+        run(&mut sh, &mut evaluator, 10);
+        assert!(!evaluator.all_decided());
+        evaluator.finish();
+        assert_eq!(evaluator.status[0], CriterionStatus::Passed);
+        assert_eq!(evaluator.status[1], CriterionStatus::Passed);
+        assert!(matches!(evaluator.status[2], CriterionStatus::Failed(_)));
+        assert!(!evaluator.passed());
+    }
+}
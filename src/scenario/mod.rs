@@ -0,0 +1,26 @@
+pub mod config;
+pub mod criteria;
+pub mod runner;
+
+pub use config::*;
+pub use criteria::*;
+pub use runner::*;
+
+use std::path::Path;
+
+use crate::error::XironResult;
+
+/// Reads the scenario at `path`. Its world is resolved relative to the scenario file.
+pub fn get_scenario_from_file(path: String) -> XironResult<ScenarioConfig> {
+    let file = std::fs::File::open(&path)?;
+    let mut scenario = serde_yaml::from_reader::<_, ScenarioConfig>(file)?;
+
+    // Worlds are referenced relative to the scenario file
+    let world = Path::new(&scenario.world);
+    if world.is_relative() {
+        if let Some(dir) = Path::new(&path).parent() {
+            scenario.world = dir.join(world).to_string_lossy().to_string();
+        }
+    }
+    return Ok(scenario);
+}
@@ -0,0 +1,556 @@
+use std::collections::HashMap;
+
+use crate::algorithms::controller::ProportionalController;
+use crate::error::{XironError, XironResult};
+use crate::handler::{RobotHandler, SimulationHandler};
+use crate::metrics::MetricsCollector;
+use crate::object::{Robot, StaticObj};
+use crate::scenario::config::*;
+use crate::scenario::criteria::ScenarioEvaluator;
+
+/// Executes the timeline of a scenario against a `SimulationHandler`.
+/// `update` should be called once per simulation step.
+pub struct ScenarioRunner {
+    pub scenario: ScenarioConfig,
+
+    // Simulation time each event fired at
+    fired_at: Vec<Option<f32>>,
+
+    // Robots that are driven to a goal by the simulator
+    controllers: HashMap<String, (ProportionalController, (f32, f32, f32))>,
+}
+
+impl ScenarioRunner {
+    pub fn new(scenario: ScenarioConfig) -> ScenarioRunner {
+        let fired_at = vec![None; scenario.events.len()];
+        ScenarioRunner {
+            scenario,
+            fired_at,
+            controllers: HashMap::new(),
+        }
+    }
+
+    /// Rewinds the timeline. Should be called whenever the world is reset.
+    pub fn restart(&mut self) {
+        self.fired_at = vec![None; self.scenario.events.len()];
+        self.controllers.clear();
+    }
+
+    /// Rewinds the timeline to `time`, e.g. after an earlier snapshot was restored.
+    /// Events that fired at or after `time` fire again. Robots that were sent a
+    /// goal before `time` are driven to it again.
+    pub fn rewind_to(&mut self, time: f32, sh: &SimulationHandler) {
+        self.controllers.clear();
+
+        for i in 0..self.fired_at.len() {
+            match self.fired_at[i] {
+                Some(t) if t < time => {
+                    if let ActionConfig::SendGoal {
+                        id,
+                        goal,
+                        drive: true,
+                    } = self.scenario.events[i].action.clone()
+                    {
+                        if let Some(handler) = sh.get_robot_handler(&id) {
+                            self.add_controller(&id, handler, goal);
+                        }
+                    }
+                }
+                _ => self.fired_at[i] = None,
+            }
+        }
+    }
+
+    /// True once every event of the scenario has fired.
+    pub fn is_finished(&self) -> bool {
+        return self.fired_at.iter().all(|f| f.is_some());
+    }
+
+    /// Fires all events that are due and drives the robots that were sent a goal.
+    /// Returns the actions that were fired in this tick, each with whether it could
+    /// be applied. Actions that failed are not fired again.
+    pub fn update(&mut self, sh: &mut SimulationHandler) -> Vec<(ActionConfig, XironResult<()>)> {
+        let mut fired_actions = Vec::new();
+
+        for i in 0..self.scenario.events.len() {
+            if self.fired_at[i].is_some() {
+                continue;
+            }
+
+            let event = self.scenario.events[i].clone();
+            let time_reached = match event.at {
+                Some(t) => sh.get_time() >= t,
+                None => true,
+            };
+            let condition_holds = match &event.when {
+                Some(condition) => Self::condition_holds(condition, sh),
+                None => true,
+            };
+
+            if time_reached && condition_holds {
+                self.fired_at[i] = Some(sh.get_time());
+                let result = self.apply(&event.action, sh);
+                fired_actions.push((event.action, result));
+            }
+        }
+
+        self.drive_to_goals(sh);
+
+        return fired_actions;
+    }
+
+    fn condition_holds(condition: &ConditionConfig, sh: &SimulationHandler) -> bool {
+        match condition {
+            ConditionConfig::RobotInRegion {
+                robot,
+                center,
+                radius,
+            } => match sh
+                .get_robot_handler(robot)
+                .and_then(|handler| sh.get_pose(&handler).ok())
+            {
+                Some(pose) => {
+                    let dx = pose.0 - center.0;
+                    let dy = pose.1 - center.1;
+                    return (dx * dx + dy * dy).sqrt() <= *radius;
+                }
+                None => return false,
+            },
+            ConditionConfig::RobotsWithin {
+                robot,
+                other,
+                distance,
+            } => match (sh.get_robot_handler(robot), sh.get_robot_handler(other)) {
+                (Some(h1), Some(h2)) => {
+                    let (p1, p2) = match (sh.get_pose(&h1), sh.get_pose(&h2)) {
+                        (Ok(p1), Ok(p2)) => (p1, p2),
+                        _ => return false,
+                    };
+                    let dx = p1.0 - p2.0;
+                    let dy = p1.1 - p2.1;
+                    return (dx * dx + dy * dy).sqrt() <= *distance;
+                }
+                _ => return false,
+            },
+        }
+    }
+
+    fn apply(&mut self, action: &ActionConfig, sh: &mut SimulationHandler) -> XironResult<()> {
+        return match action {
+            ActionConfig::SpawnRobot { robot } => Robot::from_config(robot)
+                .and_then(|robot| sh.add_robot(robot))
+                .map(|_| ()),
+            ActionConfig::RemoveRobot { id } => {
+                self.controllers.remove(id);
+                sh.remove_robot(id)
+            }
+            ActionConfig::SpawnObstacle { object } => {
+                sh.add_static_obj(StaticObj::from_config(object));
+                Ok(())
+            }
+            ActionConfig::RemoveObstacle { id } => sh.remove_static_obj(id),
+            ActionConfig::MoveObstacle {
+                id,
+                center,
+                rotation,
+            } => sh.move_static_obj(id, *center, *rotation),
+            ActionConfig::TeleportRobot { id, pose } => match sh.get_robot_handler(id) {
+                Some(handler) => sh.teleport_robot(&handler, *pose),
+                None => Err(XironError::RobotNotFound(id.clone())),
+            },
+            ActionConfig::SendGoal { id, goal, drive } => match sh.get_robot_handler(id) {
+                Some(handler) => {
+                    if *drive {
+                        self.add_controller(id, handler, *goal);
+                    }
+                    Ok(())
+                }
+                None => Err(XironError::RobotNotFound(id.clone())),
+            },
+            ActionConfig::ToggleDoor { id } => sh.toggle_door(id),
+            ActionConfig::InjectFault { id, fault } => match sh.get_robot_handler(id) {
+                Some(handler) => match sh.get_robot_mut(&handler) {
+                    Some(robot) => {
+                        robot.inject_fault(*fault);
+                        Ok(())
+                    }
+                    None => Err(XironError::RobotNotFound(id.clone())),
+                },
+                None => Err(XironError::RobotNotFound(id.clone())),
+            },
+            ActionConfig::ClearFault { id, fault } => match sh.get_robot_handler(id) {
+                Some(handler) => match sh.get_robot_mut(&handler) {
+                    Some(robot) => {
+                        robot.clear_fault(*fault);
+                        Ok(())
+                    }
+                    None => Err(XironError::RobotNotFound(id.clone())),
+                },
+                None => Err(XironError::RobotNotFound(id.clone())),
+            },
+        };
+    }
+
+    fn add_controller(&mut self, id: &str, handler: RobotHandler, goal: (f32, f32, f32)) {
+        let mut controller = ProportionalController::new(handler, 0.1, 0.1);
+        controller.set_path(vec![goal]);
+        self.controllers.insert(id.to_string(), (controller, goal));
+    }
+
+    fn drive_to_goals(&mut self, sh: &mut SimulationHandler) {
+        let mut reached = Vec::new();
+
+        for (name, (controller, goal)) in self.controllers.iter_mut() {
+            // The robot may have been spawned again under the same name, which
+            // gives it a new handler, so look it up every tick
+            let handler = match sh.get_robot_handler(name) {
+                Some(handler) => handler,
+                None => {
+                    reached.push(name.clone());
+                    continue;
+                }
+            };
+            controller.robot = handler;
+
+            let pose = match sh.get_pose(&handler) {
+                Ok(pose) => pose,
+                Err(_) => {
+                    reached.push(name.clone());
+                    continue;
+                }
+            };
+            let dx = goal.0 - pose.0;
+            let dy = goal.1 - pose.1;
+            if (dx * dx + dy * dy).sqrt() < controller.dtolerance {
+                let _ = sh.control(&handler, (0.0, 0.0, 0.0));
+                reached.push(name.clone());
+                continue;
+            }
+
+            let (v, w) = controller.control(&pose);
+            let _ = sh.control(&handler, (v, 0.0, w));
+        }
+
+        for name in reached {
+            self.controllers.remove(&name);
+        }
+    }
+}
+
+/// Result of running a scenario headless.
+pub struct ScenarioRun {
+    pub evaluator: ScenarioEvaluator,
+    pub metrics: MetricsCollector,
+    pub sim_time: f32,
+    /// Events that could not be applied, with the simulation time they fired at
+    pub errors: Vec<(f32, XironError)>,
+}
+
+/// Runs the scenario without a GUI until its duration is over or the outcome of
+/// every criterion is known. Fails if the world could not be loaded.
+pub fn run_scenario_headless(scenario: ScenarioConfig) -> XironResult<ScenarioRun> {
+    let (mut sh, _) = SimulationHandler::from_file(scenario.world.clone())?;
+    return Ok(run_scenario(&mut sh, scenario));
+}
+
+/// Runs the scenario on an already loaded simulation. The `world` of the
+/// scenario is ignored.
+pub fn run_scenario(sh: &mut SimulationHandler, scenario: ScenarioConfig) -> ScenarioRun {
+    let mut evaluator = ScenarioEvaluator::new(scenario.criteria.clone());
+    let duration = scenario.get_duration();
+    let mut runner = ScenarioRunner::new(scenario);
+    let mut errors = Vec::new();
+
+    while sh.get_time() < duration {
+        for (_, result) in runner.update(sh) {
+            if let Err(e) = result {
+                errors.push((sh.get_time(), e));
+            }
+        }
+        sh.step();
+        evaluator.update(sh);
+
+        if evaluator.any_failed() || evaluator.all_decided() {
+            break;
+        }
+    }
+    evaluator.finish();
+
+    return ScenarioRun {
+        evaluator,
+        metrics: sh.get_metrics().clone(),
+        sim_time: sh.get_time(),
+        errors,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::Fault;
+
+    const ROBOT: &str = "{id: robot0, pose: [0.0, 0.0, 0.0], vel: [0.0, 0.0, 0.0], \
+        lidar: false, footprint: [0.2], drive_type: Differential, add_noise: false}";
+    const BOX: &str = "{id: box, center: [1.0, 0.0], width: 0.2, height: 1.0, rotation: 0.0}";
+
+    fn runner(events: &str) -> ScenarioRunner {
+        let yaml = format!("world: world.yaml\nevents:\n{}", events);
+        return ScenarioRunner::new(serde_yaml::from_str(&yaml).unwrap());
+    }
+
+    fn run(runner: &mut ScenarioRunner, sh: &mut SimulationHandler, ticks: usize) -> usize {
+        let mut fired = 0;
+        for _ in 0..ticks {
+            fired += runner.update(sh).len();
+            sh.step();
+        }
+        return fired;
+    }
+
+    fn pose(sh: &SimulationHandler, id: &str) -> (f32, f32, f32) {
+        return sh.get_pose(&sh.get_robot_handler(id).unwrap()).unwrap();
+    }
+
+    fn has_fault(sh: &SimulationHandler, id: &str, fault: Fault) -> bool {
+        let robot = sh.get_robot(&sh.get_robot_handler(id).unwrap()).unwrap();
+        return robot.has_fault(fault);
+    }
+
+    fn blocked(sh: &SimulationHandler, id: &str, pose: (f32, f32, f32)) -> bool {
+        return sh
+            .collision_status_at(&sh.get_robot_handler(id).unwrap(), &pose)
+            .unwrap();
+    }
+
+    fn obstacle_center(sh: &SimulationHandler, id: &str) -> Option<(f32, f32)> {
+        return sh
+            .to_config()
+            .static_objects
+            .iter()
+            .find(|obj| obj.id.as_deref() == Some(id))
+            .map(|obj| obj.center);
+    }
+
+    #[test]
+    fn timed_events_fire_once_their_time_is_reached() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(&format!(
+            "- {{at: 0.25, action: {{type: spawn_robot, robot: {}}}}}",
+            ROBOT
+        ));
+
+        assert_eq!(run(&mut runner, &mut sh, 10), 0);
+        assert!(sh.get_robot_handler("robot0").is_none());
+        assert!(!runner.is_finished());
+
+        assert_eq!(run(&mut runner, &mut sh, 20), 1);
+        assert!(sh.get_robot_handler("robot0").is_some());
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    fn actions_that_fail_are_returned_as_errors() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(&format!(
+            "- {{action: {{type: spawn_robot, robot: {}}}}}
+- {{at: 0.1, action: {{type: spawn_robot, robot: {}}}}}
+- {{at: 0.1, action: {{type: remove_robot, id: robot1}}}}",
+            ROBOT, ROBOT
+        ));
+
+        assert!(runner.update(&mut sh)[0].1.is_ok());
+        for _ in 0..20 {
+            sh.step();
+        }
+        let fired = runner.update(&mut sh);
+        assert!(matches!(fired[0].1, Err(XironError::RobotExists(_))));
+        assert!(matches!(fired[1].1, Err(XironError::RobotNotFound(_))));
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    fn conditional_events_wait_for_their_condition() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(&format!(
+            "- {{action: {{type: spawn_robot, robot: {}}}}}
+- {{at: 0.1, action: {{type: send_goal, id: robot0, goal: [2.0, 0.0, 0.0], drive: true}}}}
+- {{when: {{type: robot_in_region, robot: robot0, center: [1.0, 0.0], radius: 0.2}},
+   action: {{type: spawn_obstacle, object: {}}}}}",
+            ROBOT, "{id: marker, center: [5.0, 5.0], width: 0.2, height: 0.2, rotation: 0.0}"
+        ));
+
+        run(&mut runner, &mut sh, 10);
+        assert!(obstacle_center(&sh, "marker").is_none());
+
+        let mut ticks = 0;
+        while obstacle_center(&sh, "marker").is_none() && ticks < 600 {
+            run(&mut runner, &mut sh, 1);
+            ticks += 1;
+        }
+        // The robot is seen in the region when the event fires, then moves one more tick
+        assert!(pose(&sh, "robot0").0 > 0.75 && pose(&sh, "robot0").0 < 1.25);
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    fn robots_within_fires_when_robots_meet() {
+        let mut sh = SimulationHandler::new();
+        let other = ROBOT
+            .replace("robot0", "robot1")
+            .replace("[0.0, 0.0, 0.0], vel", "[1.0, 0.0, 0.0], vel");
+        let mut runner = runner(&format!(
+            "- {{action: {{type: spawn_robot, robot: {}}}}}
+- {{action: {{type: spawn_robot, robot: {}}}}}
+- {{when: {{type: robots_within, robot: robot0, other: robot1, distance: 0.5}},
+   action: {{type: remove_robot, id: robot1}}}}",
+            ROBOT, other
+        ));
+
+        run(&mut runner, &mut sh, 5);
+        assert!(sh.get_robot_handler("robot1").is_some());
+
+        let handler = sh.get_robot_handler("robot0").unwrap();
+        sh.teleport_robot(&handler, (0.7, 0.0, 0.0)).unwrap();
+        run(&mut runner, &mut sh, 1);
+        assert!(sh.get_robot_handler("robot1").is_none());
+    }
+
+    #[test]
+    fn robot_actions_spawn_teleport_and_remove() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(&format!(
+            "- {{action: {{type: spawn_robot, robot: {}}}}}
+- {{at: 0.1, action: {{type: teleport_robot, id: robot0, pose: [2.0, 1.0, 0.5]}}}}
+- {{at: 0.2, action: {{type: remove_robot, id: robot0}}}}",
+            ROBOT
+        ));
+
+        run(&mut runner, &mut sh, 1);
+        assert_eq!(pose(&sh, "robot0"), (0.0, 0.0, 0.0));
+        run(&mut runner, &mut sh, 8);
+        assert_eq!(pose(&sh, "robot0"), (2.0, 1.0, 0.5));
+        run(&mut runner, &mut sh, 8);
+        assert!(sh.get_robot_handler("robot0").is_none());
+    }
+
+    #[test]
+    fn obstacle_actions_spawn_move_and_remove() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(&format!(
+            "- {{action: {{type: spawn_obstacle, object: {}}}}}
+- {{at: 0.1, action: {{type: move_obstacle, id: box, center: [3.0, 2.0]}}}}
+- {{at: 0.2, action: {{type: remove_obstacle, id: box}}}}",
+            BOX
+        ));
+
+        run(&mut runner, &mut sh, 1);
+        assert_eq!(obstacle_center(&sh, "box"), Some((1.0, 0.0)));
+        run(&mut runner, &mut sh, 8);
+        assert_eq!(obstacle_center(&sh, "box"), Some((3.0, 2.0)));
+        run(&mut runner, &mut sh, 8);
+        assert_eq!(obstacle_center(&sh, "box"), None);
+    }
+
+    #[test]
+    fn toggle_door_opens_and_closes_an_obstacle() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(&format!(
+            "- {{action: {{type: spawn_robot, robot: {}}}}}
+- {{action: {{type: spawn_obstacle, object: {}}}}}
+- {{at: 0.1, action: {{type: toggle_door, id: box}}}}
+- {{at: 0.2, action: {{type: toggle_door, id: box}}}}",
+            ROBOT, BOX
+        ));
+
+        run(&mut runner, &mut sh, 1);
+        assert!(blocked(&sh, "robot0", (1.0, 0.0, 0.0)));
+        run(&mut runner, &mut sh, 8);
+        assert!(!blocked(&sh, "robot0", (1.0, 0.0, 0.0)));
+        run(&mut runner, &mut sh, 8);
+        assert!(blocked(&sh, "robot0", (1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn send_goal_drives_the_robot_to_the_goal() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(&format!(
+            "- {{action: {{type: spawn_robot, robot: {}}}}}
+- {{action: {{type: send_goal, id: robot0, goal: [1.0, 1.0, 0.0], drive: true}}}}",
+            ROBOT
+        ));
+
+        run(&mut runner, &mut sh, 1200);
+        let pose = pose(&sh, "robot0");
+        assert!(((pose.0 - 1.0).powi(2) + (pose.1 - 1.0).powi(2)).sqrt() < 0.2);
+    }
+
+    #[test]
+    fn fault_actions_inject_and_clear_faults() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(&format!(
+            "- {{action: {{type: spawn_robot, robot: {}}}}}
+- {{at: 0.1, action: {{type: inject_fault, id: robot0, fault: MotorFailure}}}}
+- {{at: 0.2, action: {{type: clear_fault, id: robot0, fault: MotorFailure}}}}",
+            ROBOT
+        ));
+
+        run(&mut runner, &mut sh, 1);
+        assert!(!has_fault(&sh, "robot0", Fault::MotorFailure));
+        run(&mut runner, &mut sh, 8);
+        assert!(has_fault(&sh, "robot0", Fault::MotorFailure));
+        run(&mut runner, &mut sh, 8);
+        assert!(!has_fault(&sh, "robot0", Fault::MotorFailure));
+    }
+
+    #[test]
+    fn events_on_missing_entities_fire_without_effect() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(
+            "- {action: {type: remove_robot, id: ghost}}
+- {action: {type: toggle_door, id: ghost}}
+- {action: {type: send_goal, id: ghost, goal: [1.0, 0.0, 0.0], drive: true}}",
+        );
+
+        assert_eq!(run(&mut runner, &mut sh, 2), 3);
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    fn restart_fires_every_event_again() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(&format!(
+            "- {{action: {{type: spawn_obstacle, object: {}}}}}",
+            BOX
+        ));
+
+        assert_eq!(run(&mut runner, &mut sh, 3), 1);
+        runner.restart();
+        assert!(!runner.is_finished());
+        assert_eq!(run(&mut runner, &mut sh, 3), 1);
+    }
+
+    #[test]
+    fn rewind_fires_later_events_again_and_keeps_earlier_goals() {
+        let mut sh = SimulationHandler::new();
+        let mut runner = runner(&format!(
+            "- {{action: {{type: spawn_robot, robot: {}}}}}
+- {{at: 0.1, action: {{type: send_goal, id: robot0, goal: [3.0, 0.0, 0.0], drive: true}}}}
+- {{at: 0.5, action: {{type: spawn_obstacle, object: {}}}}}",
+            ROBOT, "{id: marker, center: [5.0, 5.0], width: 0.2, height: 0.2, rotation: 0.0}"
+        ));
+
+        run(&mut runner, &mut sh, 40);
+        assert!(runner.is_finished());
+
+        // The goal was sent before the time rewound to, the obstacle after it
+        runner.rewind_to(0.3, &sh);
+        assert!(!runner.is_finished());
+        assert!(runner.controllers.contains_key("robot0"));
+        assert_eq!(run(&mut runner, &mut sh, 1), 1);
+
+        runner.rewind_to(0.05, &sh);
+        assert!(runner.controllers.is_empty());
+        assert_eq!(run(&mut runner, &mut sh, 1), 2);
+        assert!(runner.controllers.contains_key("robot0"));
+    }
+}
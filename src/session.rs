@@ -0,0 +1,233 @@
+//! Worlds hosted by one server process.
+//!
+//! Every session has its own `SimulationHandler`, and with it its own config,
+//! clock and robots. Clients talk to a session through the channels the comm
+//! server returned for its world.
+
+use crossbeam::channel::Sender;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::comms::{
+    collision_msg::VectorMsg, pose_msg::PositionMsg, twist_msg::LinearMsg, CollisionMsg,
+    CommChannels, CommResponse, CommResponseError, PoseMsg, TwistMsg, WheelCommandMsg,
+    WheelEncoderMsg,
+};
+use crate::error::{XironError, XironResult};
+use crate::handler::SimulationHandler;
+use crate::parameter::{DATA_SEND_FREQ, DT};
+use crate::utils::LoopRateHandler;
+
+/// Sets the velocity of the robot the twist message is meant for.
+pub fn apply_twist(sh: &mut SimulationHandler, twist_msg: &TwistMsg) -> XironResult<()> {
+    let handler = match sh.get_robot_handler(&twist_msg.robot_id) {
+        Some(handler) => handler,
+        None => return Err(XironError::RobotNotFound(twist_msg.robot_id.clone())),
+    };
+    let linear = twist_msg.linear.unwrap_or_default();
+    return sh.control(&handler, (linear.x, linear.y, twist_msg.angular));
+}
+
+/// Sets the wheel speeds of the robot the wheel command is meant for.
+pub fn apply_wheel_command(
+    sh: &mut SimulationHandler,
+    wheel_msg: &WheelCommandMsg,
+) -> XironResult<()> {
+    let handler = match sh.get_robot_handler(&wheel_msg.robot_id) {
+        Some(handler) => handler,
+        None => return Err(XironError::RobotNotFound(wheel_msg.robot_id.clone())),
+    };
+    return sh.control_wheels(&handler, &wheel_msg.speeds);
+}
+
+/// Sends what the robots hit in the last step. Called after every step, so that
+/// no collision is missed between two publishes of the robot states.
+pub fn publish_collision_events(
+    sh: &SimulationHandler,
+    sender: &Sender<Result<CommResponse, CommResponseError>>,
+) {
+    for event in sh.get_collision_events() {
+        let collision_msg = CollisionMsg {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            robot_id: event.robot_id.clone(),
+            other_id: event.other_name.clone().unwrap_or_default(),
+            point: Some(VectorMsg {
+                x: event.point.0,
+                y: event.point.1,
+            }),
+            normal: Some(VectorMsg {
+                x: event.normal.0,
+                y: event.normal.1,
+            }),
+            sim_time: event.time,
+        };
+        match sender.send(Ok(CommResponse::Collision(collision_msg))) {
+            Ok(_) => {}
+            Err(e) => {
+                println!("Got error when sending collision via channel {}", e);
+            }
+        }
+    }
+}
+
+/// Sends the pose, the velocity, the scan of every robot with a LiDAR and the
+/// encoder ticks of every robot with wheels.
+pub fn publish_robot_states(
+    sh: &SimulationHandler,
+    sender: &Sender<Result<CommResponse, CommResponseError>>,
+) {
+    for (robot_name, robot) in sh.robot_handlers() {
+        let pose = match sh.get_pose(&robot) {
+            Ok(pose) => pose,
+            Err(_) => continue,
+        };
+        let pose_msg = PoseMsg {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            robot_id: robot_name.clone(),
+            position: Some(PositionMsg {
+                x: pose.0,
+                y: pose.1,
+            }),
+            orientation: pose.2,
+            trailer_angles: sh.get_trailer_angles(&robot).unwrap_or_default(),
+        };
+
+        match sender.send(Ok(CommResponse::Pose(pose_msg))) {
+            Ok(_) => {}
+            Err(e) => {
+                println!("Got error when sending pose via channel {}", e);
+            }
+        }
+
+        // The velocity after limits, which may differ from what the client commanded
+        if let Ok(vel) = sh.get_velocity(&robot) {
+            let twist_msg = TwistMsg {
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+                robot_id: robot_name.clone(),
+                linear: Some(LinearMsg { x: vel.0, y: vel.1 }),
+                angular: vel.2,
+            };
+            match sender.send(Ok(CommResponse::Twist(twist_msg))) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("Got error when sending velocity via channel {}", e);
+                }
+            }
+        }
+        if let Ok(encoder) = sh.get_wheel_encoder(&robot) {
+            let encoder_msg = WheelEncoderMsg {
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+                robot_id: robot_name.clone(),
+                ticks: encoder.ticks(),
+                ticks_per_revolution: encoder.config.ticks_per_revolution,
+            };
+            match sender.send(Ok(CommResponse::WheelEncoder(encoder_msg))) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("Got error when sending encoder ticks via channel {}", e);
+                }
+            }
+        }
+        // Robots without a LiDAR only publish their pose
+        match sh.sense(&robot) {
+            Ok(scan) => {
+                let scan_resp = CommResponse::from((scan, robot_name.clone()));
+                match sender.send(Ok(scan_resp)) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        println!("Got error when sending scan via channel {}", e);
+                    }
+                }
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// One named world of a server, along with the channels to its clients.
+pub struct Session {
+    pub name: String,
+    pub sh: SimulationHandler,
+    channels: CommChannels,
+    last_sent_time: Option<Instant>,
+}
+
+impl Session {
+    pub fn new(name: String, sh: SimulationHandler, channels: CommChannels) -> Session {
+        return Session {
+            name,
+            sh,
+            channels,
+            last_sent_time: None,
+        };
+    }
+
+    /// Applies the messages clients sent since the last call.
+    pub fn handle_messages(&mut self) {
+        while let Ok(message) = self.channels.1.try_recv() {
+            match message {
+                Ok(CommResponse::Reset(_)) => {
+                    println!("[{}] Resetting the simulation", self.name);
+                    if let Err(e) = self.sh.reset() {
+                        println!("[{}] Could not reset world: {}", self.name, e);
+                    }
+                }
+                Ok(CommResponse::Twist(twist_msg)) => {
+                    if let Err(e) = apply_twist(&mut self.sh, &twist_msg) {
+                        println!("[{}] Could not control robot: {}", self.name, e);
+                    }
+                }
+                Ok(CommResponse::WheelCommand(wheel_msg)) => {
+                    if let Err(e) = apply_wheel_command(&mut self.sh, &wheel_msg) {
+                        println!("[{}] Could not control robot: {}", self.name, e);
+                    }
+                }
+                // Ignore any other type.
+                Ok(_) => {}
+                Err(e) => {
+                    println!(
+                        "[{}] Error in recieving from Websocket: {}",
+                        self.name, e.reason
+                    );
+                }
+            }
+        }
+    }
+
+    /// Handles messages, steps the simulation once and publishes the state of
+    /// the robots at `DATA_SEND_FREQ`.
+    pub fn update(&mut self) {
+        self.handle_messages();
+        self.sh.step();
+        publish_collision_events(&self.sh, &self.channels.0);
+
+        let send_message = match self.last_sent_time {
+            Some(t_last) => t_last.elapsed().as_secs_f64() > 1.0 / DATA_SEND_FREQ,
+            None => true,
+        };
+        if send_message {
+            self.last_sent_time = Some(Instant::now());
+            publish_robot_states(&self.sh, &self.channels.0);
+        }
+    }
+
+    /// Runs the session in real time until the process exits.
+    pub fn run(mut self) {
+        let mut rate = LoopRateHandler::new(1.0 / DT as f64);
+        loop {
+            self.update();
+            rate.sleep();
+        }
+    }
+}
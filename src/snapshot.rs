@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::error::XironResult;
+use crate::handler::SimulationHandler;
+use crate::object::actuator::Actuator;
+use crate::object::robot::{Fault, Robot};
+use crate::object::sensors::LiDAR;
+use crate::object::static_obj::StaticObj;
+use crate::object::wheels::WheelEncoder;
+use crate::parameter::DT;
+use crate::parser::{PhysicsConfig, RobotConfig, RobotModelConfig, StaticObjConfig, WallConfig};
+use crate::utils::SimRng;
+
+/// Field of view and resolution of a LiDAR.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct LiDARSnapshot {
+    pub angle_min: f32,
+    pub angle_max: f32,
+    pub num_readings: i32,
+}
+
+/// Everything about a robot that its config does not capture.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RobotSnapshot {
+    pub config: RobotConfig,
+    pub cmd_vel: (f32, f32, f32),
+    pub faults: Vec<Fault>,
+    pub rng_state: u64,
+    pub lidar: Vec<LiDARSnapshot>,
+    /// Pending commands and response of the motors, if they have dynamics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actuator: Option<Actuator>,
+    /// Ticks counted by the wheel encoders, if the robot has wheels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wheels: Option<WheelEncoder>,
+    /// Headings of the trailers in the world frame, front to back
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trailer_headings: Vec<f32>,
+    /// Velocity the robot was pushed with, if other robots can push it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push_vel: Option<(f32, f32, f32)>,
+}
+
+impl RobotSnapshot {
+    pub fn from_robot(robot: &Robot) -> RobotSnapshot {
+        return RobotSnapshot {
+            config: robot.into_config(),
+            cmd_vel: robot.cmd_vel,
+            faults: robot.faults.clone(),
+            rng_state: robot.rng.state(),
+            lidar: robot
+                .lidar
+                .iter()
+                .map(|l| LiDARSnapshot {
+                    angle_min: l.angle_min,
+                    angle_max: l.angle_max,
+                    num_readings: l.num_readings,
+                })
+                .collect(),
+            actuator: robot.actuator.clone(),
+            wheels: robot.wheels.clone(),
+            trailer_headings: robot.trailers.iter().map(|t| t.heading).collect(),
+            push_vel: robot.body.map(|b| b.vel),
+        };
+    }
+
+    pub fn to_robot(&self) -> XironResult<Robot> {
+        let mut robot = Robot::from_config(&self.config)?;
+
+        robot.vel = self.config.vel;
+        robot.cmd_vel = self.cmd_vel;
+        robot.faults = self.faults.clone();
+        robot.rng = SimRng::new(self.rng_state);
+        if let Some(actuator) = &self.actuator {
+            robot.actuator = Some(actuator.clone());
+        }
+        if let Some(wheels) = &self.wheels {
+            robot.wheels = Some(wheels.clone());
+        }
+        if let (Some(body), Some(vel)) = (robot.body.as_mut(), self.push_vel) {
+            body.vel = vel;
+        }
+        // Adding up the relative angles of the config may round, the headings are exact
+        for (trailer, heading) in robot.trailers.iter_mut().zip(self.trailer_headings.iter()) {
+            trailer.heading = *heading;
+        }
+        robot.place(&self.config.pose);
+        robot.lidar = self
+            .lidar
+            .iter()
+            .map(|l| {
+                LiDAR::new(self.config.pose).with_range(l.angle_min, l.angle_max, l.num_readings)
+            })
+            .collect();
+
+        return Ok(robot);
+    }
+}
+
+/// A static object along with whether it is open. Scenarios can move, open and
+/// close objects at runtime.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StaticObjSnapshot {
+    pub config: StaticObjConfig,
+    pub open: bool,
+    /// Velocity of a rigid body in the world frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vel: Option<(f32, f32, f32)>,
+}
+
+impl StaticObjSnapshot {
+    pub fn from_static_obj(obj: &StaticObj) -> StaticObjSnapshot {
+        return StaticObjSnapshot {
+            config: StaticObjConfig {
+                center: obj.center,
+                width: obj.width,
+                height: obj.height,
+                rotation: obj.rotation,
+                id: obj.id.clone(),
+                body: obj.body.map(|b| b.config),
+            },
+            open: obj.open,
+            vel: obj.body.map(|b| b.vel),
+        };
+    }
+
+    pub fn to_static_obj(&self) -> StaticObj {
+        let mut obj = StaticObj::from_config(&self.config);
+        obj.open = self.open;
+        if let (Some(body), Some(vel)) = (obj.body.as_mut(), self.vel) {
+            body.vel = vel;
+        }
+        return obj;
+    }
+}
+
+/// The full state of a simulation at one moment. Restoring it and stepping gives
+/// the same results as stepping the simulation it was taken from.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Snapshot {
+    /// Simulation time in seconds
+    pub time: f32,
+    /// Seed the noise of new robots is derived from, if any
+    pub seed: Option<u64>,
+    pub robot_models: Vec<RobotModelConfig>,
+    #[serde(default)]
+    pub physics: PhysicsConfig,
+    pub robots: Vec<RobotSnapshot>,
+    pub walls: Vec<WallConfig>,
+    pub static_objects: Vec<StaticObjSnapshot>,
+}
+
+impl Snapshot {
+    /// Writes the snapshot to `path` as YAML.
+    pub fn save(&self, path: &str) -> XironResult<()> {
+        let data = serde_yaml::to_string(self)?;
+        std::fs::write(path, data)?;
+        return Ok(());
+    }
+
+    /// Reads a snapshot written by `save`.
+    pub fn load(path: &str) -> XironResult<Snapshot> {
+        let data = std::fs::read_to_string(path)?;
+        return Ok(serde_yaml::from_str(&data)?);
+    }
+}
+
+/// Snapshots of the recent past of a simulation, taken every `period` seconds of
+/// simulation time. The oldest snapshot is dropped once `capacity` is reached.
+#[derive(Clone, Debug)]
+pub struct SnapshotHistory {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+    period: f32,
+}
+
+impl SnapshotHistory {
+    pub fn new(capacity: usize, period: f32) -> SnapshotHistory {
+        return SnapshotHistory {
+            snapshots: VecDeque::new(),
+            capacity,
+            period,
+        };
+    }
+
+    /// Takes a snapshot if `period` passed since the last one. If the simulation
+    /// went back in time, e.g. because an older snapshot was restored, the
+    /// snapshots after that moment are dropped first.
+    pub fn record(&mut self, sh: &SimulationHandler) {
+        let time = sh.get_time();
+        while self.snapshots.back().is_some_and(|s| s.time > time) {
+            self.snapshots.pop_back();
+        }
+
+        if let Some(last) = self.snapshots.back() {
+            // Half a step of slack, so that rounding does not skip a snapshot
+            if time - last.time + 0.5 * DT < self.period {
+                return;
+            }
+        }
+
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(sh.snapshot());
+    }
+
+    /// The snapshot at `index`, the oldest being 0.
+    pub fn get(&self, index: usize) -> Option<&Snapshot> {
+        return self.snapshots.get(index);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.snapshots.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.snapshots.is_empty();
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
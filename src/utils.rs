@@ -1,5 +1,7 @@
 use crate::parameter::{HEIGHT, WIDTH};
+use ::rand::RngCore;
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
 
 use std::thread;
@@ -153,3 +155,105 @@ impl LoopRateHandler {
         self._last_slept_time = None;
     }
 }
+
+/// Small seedable random number generator (SplitMix64) used for all noise in the
+/// simulation. Unlike the generators in `rand`, its state can be saved and restored.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> SimRng {
+        SimRng { state: seed }
+    }
+
+    /// Generator seeded from the thread local generator of `rand`.
+    pub fn from_entropy() -> SimRng {
+        SimRng::new(::rand::thread_rng().next_u64())
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// Sample of the standard normal distribution, using the Box-Muller transform.
+    pub fn gaussian(&mut self) -> f32 {
+        // Uniform samples from 24 random bits. u1 is in (0, 1] so that its log is finite.
+        let scale = 1.0 / (1u64 << 24) as f32;
+        let u1 = 1.0 - (self.next_u64() >> 40) as f32 * scale;
+        let u2 = (self.next_u64() >> 40) as f32 * scale;
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+}
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ::rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Stable hash of a string (FNV-1a), used to derive per robot seeds.
+pub fn hash_str(value: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in value.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_continues_from_a_restored_state() {
+        let mut rng = SimRng::new(42);
+        rng.next_u64();
+        rng.gaussian();
+
+        let mut restored = SimRng::new(rng.state());
+        let expected: Vec<u64> = (0..10).map(|_| rng.next_u64()).collect();
+        let actual: Vec<u64> = (0..10).map(|_| restored.next_u64()).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn rng_state_survives_serialization() {
+        let mut rng = SimRng::new(7);
+        rng.next_u32();
+
+        let text = serde_yaml::to_string(&rng).unwrap();
+        let mut restored: SimRng = serde_yaml::from_str(&text).unwrap();
+        assert_eq!(restored, rng);
+        assert_eq!(restored.gaussian(), rng.gaussian());
+    }
+
+    #[test]
+    fn rng_seeds_give_different_sequences() {
+        let mut a = SimRng::new(1);
+        let mut b = SimRng::new(2);
+        assert_ne!(a.next_u64(), b.next_u64());
+    }
+}
@@ -0,0 +1,781 @@
+//! Validation of world configs with precise error locations.
+
+use std::fmt::Display;
+
+use crate::behaviour::traits::Collidable;
+use crate::include::ConfigSource;
+use crate::object::{kinematic_model_names, DriveType, Robot, StaticObj, Wall, WheelLayout};
+use crate::parser::{
+    ActuatorConfig, BodyConfig, Config, LimitsConfig, NoiseConfig, SkidSteerConfig, TrailerConfig,
+    WheelConfig,
+};
+
+/// A problem found in a config file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigError {
+    pub file: String,
+    /// 1-based line of the offending entry, if it could be located.
+    pub line: Option<usize>,
+    /// 1-based column of the offending entry, if it could be located.
+    pub column: Option<usize>,
+    /// Path of the offending field, e.g. `robots[2].footprint`. Empty if unknown.
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        if !self.path.is_empty() {
+            write!(f, ": {}", self.path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+fn path_to_string(path: &[PathSegment]) -> String {
+    let mut out = String::new();
+    for segment in path.iter() {
+        match segment {
+            PathSegment::Key(key) => {
+                if !out.is_empty() {
+                    out.push('.');
+                }
+                out.push_str(key);
+            }
+            PathSegment::Index(i) => out.push_str(&format!("[{}]", i)),
+        }
+    }
+    return out;
+}
+
+fn key(name: &str) -> PathSegment {
+    PathSegment::Key(name.to_string())
+}
+
+/// Reads, parses and validates the config at `path`, including every file it includes.
+pub fn load_config(path: &str) -> Result<Config, Vec<ConfigError>> {
+    return load_config_with_overrides(path, &[]);
+}
+
+/// Like `load_config`, but sets the fields in `overrides` after all includes are merged.
+/// Each override is a dot separated key, e.g. `robots.robot0.pose`, and a YAML value.
+pub fn load_config_with_overrides(
+    path: &str,
+    overrides: &[(String, String)],
+) -> Result<Config, Vec<ConfigError>> {
+    let mut source = ConfigSource::from_file(path)?;
+
+    let mut errors = Vec::new();
+    for (key, value) in overrides.iter() {
+        match source.apply_override(key, value) {
+            Ok(_) => {}
+            Err(message) => errors.push(ConfigError {
+                file: path.to_string(),
+                line: None,
+                column: None,
+                path: key.clone(),
+                message,
+            }),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    // Parsing the text of a single file keeps the location of type errors
+    let parsed = match (source.single_source(), overrides.is_empty()) {
+        (Some(text), true) => serde_yaml::from_str::<Config>(text),
+        _ => serde_yaml::from_value::<Config>(source.value.clone()),
+    };
+    let config = match parsed {
+        Ok(config) => config,
+        Err(e) => {
+            let location = e.location();
+            return Err(vec![ConfigError {
+                file: path.to_string(),
+                line: location.as_ref().map(|l| l.line()),
+                column: location.as_ref().map(|l| l.column()),
+                path: "".to_string(),
+                message: e.to_string(),
+            }]);
+        }
+    };
+
+    let errors = validate_config(&config, &source);
+    if errors.is_empty() {
+        return Ok(config);
+    }
+    return Err(errors);
+}
+
+/// Checks a parsed config for mistakes that YAML parsing does not catch.
+/// `source` is what the config was parsed from and is used to locate the errors.
+pub fn validate_config(config: &Config, source: &ConfigSource) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+    let mut error = |path: Vec<PathSegment>, message: String| {
+        // Fields that are not written in the file are reported at their parent
+        let (mut file, mut location) = source.locate(&path);
+        for len in (1..path.len()).rev() {
+            if location.is_some() {
+                break;
+            }
+            (file, location) = source.locate(&path[..len]);
+        }
+        errors.push(ConfigError {
+            file,
+            line: location.map(|l| l.0),
+            column: location.map(|l| l.1),
+            path: path_to_string(&path),
+            message,
+        });
+    };
+
+    for (i, model) in config.robot_models.iter().enumerate() {
+        let model_path = vec![key("robot_models"), PathSegment::Index(i)];
+
+        if let Some(j) = config.robot_models[..i]
+            .iter()
+            .position(|m| m.name == model.name)
+        {
+            let mut path = model_path.clone();
+            path.push(key("name"));
+            error(
+                path,
+                format!(
+                    "Duplicate robot model {}, already defined by robot_models[{}]",
+                    model.name, j
+                ),
+            );
+        }
+
+        let mut footprint_path = model_path.clone();
+        footprint_path.push(key("footprint"));
+        match footprint_problem(&model.footprint) {
+            Some(message) => error(footprint_path, message),
+            None => {}
+        }
+
+        if model.drive_type.kinematic_model().is_none() {
+            let mut path = model_path.clone();
+            path.push(key("drive_type"));
+            error(path, unknown_drive_type(&model.drive_type));
+        }
+
+        for (field, message) in limits_problems(&model.limits) {
+            let mut path = model_path.clone();
+            path.push(key("limits"));
+            path.push(key(field));
+            error(path, message);
+        }
+
+        if let Some(message) = model.actuator.as_ref().and_then(actuator_problem) {
+            let mut path = model_path.clone();
+            path.push(key("actuator"));
+            error(path, message);
+        }
+
+        if let Some(message) = model.noise.as_ref().and_then(noise_problem) {
+            let mut path = model_path.clone();
+            path.push(key("noise"));
+            error(path, message);
+        }
+
+        if let Some(message) = model
+            .wheels
+            .as_ref()
+            .and_then(|w| wheels_problem(w, &model.drive_type))
+        {
+            let mut path = model_path.clone();
+            path.push(key("wheels"));
+            error(path, message);
+        }
+
+        if let Some(message) = model
+            .skid_steer
+            .as_ref()
+            .and_then(|k| skid_steer_problem(k, &model.drive_type))
+        {
+            let mut path = model_path.clone();
+            path.push(key("skid_steer"));
+            error(path, message);
+        }
+
+        for (k, trailer) in model.trailers.iter().enumerate() {
+            if let Some(message) = trailer_problem(trailer) {
+                let mut path = model_path.clone();
+                path.push(key("trailers"));
+                path.push(PathSegment::Index(k));
+                error(path, message);
+            }
+        }
+
+        if let Some(message) = model.body.as_ref().and_then(body_problem) {
+            let mut path = model_path.clone();
+            path.push(key("body"));
+            error(path, message);
+        }
+    }
+
+    let robots_key = || key("robots");
+
+    for (i, robot) in config.robots.iter().enumerate() {
+        let robot_path = vec![robots_key(), PathSegment::Index(i)];
+
+        if let Some(j) = config.robots[..i].iter().position(|r| r.id == robot.id) {
+            let mut path = robot_path.clone();
+            path.push(key("id"));
+            error(
+                path,
+                format!(
+                    "Duplicate robot id {}, already used by robots[{}]",
+                    robot.id, j
+                ),
+            );
+        }
+
+        let model = match &robot.model {
+            Some(name) => match config.get_robot_model(name) {
+                Some(model) => Some(model),
+                None => {
+                    let mut path = robot_path.clone();
+                    path.push(key("model"));
+                    error(path, format!("Unknown robot model {}", name));
+                    // Everything else of this robot depends on the model
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        let limit_problems = limits_problems(&robot.limits);
+        // Limits inherited from the model are reported with the model
+        if model.is_none_or(|m| m.limits != robot.limits) {
+            for (field, message) in limit_problems.iter() {
+                let mut path = robot_path.clone();
+                path.push(key("limits"));
+                path.push(key(field));
+                error(path, message.clone());
+            }
+        }
+
+        // A drive type inherited from the model is reported with the model
+        if robot.drive_type.kinematic_model().is_none()
+            && model.is_none_or(|m| m.drive_type != robot.drive_type)
+        {
+            let mut path = robot_path.clone();
+            path.push(key("drive_type"));
+            error(path, unknown_drive_type(&robot.drive_type));
+        }
+
+        if let Some(message) = robot.actuator.as_ref().and_then(actuator_problem) {
+            if model.is_none_or(|m| m.actuator != robot.actuator) {
+                let mut path = robot_path.clone();
+                path.push(key("actuator"));
+                error(path, message);
+            }
+        }
+
+        if let Some(message) = robot.noise.as_ref().and_then(noise_problem) {
+            if model.is_none_or(|m| m.noise != robot.noise) {
+                let mut path = robot_path.clone();
+                path.push(key("noise"));
+                error(path, message);
+            }
+        }
+
+        if let Some(message) = robot
+            .wheels
+            .as_ref()
+            .and_then(|w| wheels_problem(w, &robot.drive_type))
+        {
+            let inherited =
+                model.is_some_and(|m| m.wheels == robot.wheels && m.drive_type == robot.drive_type);
+            if !inherited {
+                let mut path = robot_path.clone();
+                path.push(key("wheels"));
+                error(path, message);
+            }
+        }
+
+        if let Some(message) = robot
+            .skid_steer
+            .as_ref()
+            .and_then(|k| skid_steer_problem(k, &robot.drive_type))
+        {
+            let inherited = model.is_some_and(|m| {
+                m.skid_steer == robot.skid_steer && m.drive_type == robot.drive_type
+            });
+            if !inherited {
+                let mut path = robot_path.clone();
+                path.push(key("skid_steer"));
+                error(path, message);
+            }
+        }
+
+        if model.is_none_or(|m| m.trailers != robot.trailers) {
+            for (k, trailer) in robot.trailers.iter().enumerate() {
+                if let Some(message) = trailer_problem(trailer) {
+                    let mut path = robot_path.clone();
+                    path.push(key("trailers"));
+                    path.push(PathSegment::Index(k));
+                    error(path, message);
+                }
+            }
+        }
+
+        if let Some(message) = robot.body.as_ref().and_then(body_problem) {
+            if model.is_none_or(|m| m.body != robot.body) {
+                let mut path = robot_path.clone();
+                path.push(key("body"));
+                error(path, message);
+            }
+        }
+
+        let mut footprint_path = robot_path.clone();
+        footprint_path.push(key("footprint"));
+        if model.is_none() && robot.footprint.is_empty() {
+            error(
+                footprint_path,
+                "Robot needs either a footprint or a model".to_string(),
+            );
+            continue;
+        }
+        match footprint_problem(&robot.footprint) {
+            Some(message) => {
+                // A footprint inherited from the model is reported with the model
+                if model.is_none_or(|m| m.footprint != robot.footprint) {
+                    error(footprint_path, message);
+                }
+                // The remaining checks need a valid footprint
+                continue;
+            }
+            None => {}
+        }
+
+        // Robots with invalid limits can not be built
+        if !limit_problems.is_empty() {
+            continue;
+        }
+
+        // Robots that can not be built were reported above
+        let Ok(robot_obj) = Robot::from_config(robot) else {
+            continue;
+        };
+        for (j, wall) in config.walls.iter().enumerate() {
+            if wall.endpoints.len() >= 2
+                && robot_obj.distance_at(&Wall::new(wall.endpoints.clone()), &robot.pose) <= 0.0
+            {
+                let mut path = robot_path.clone();
+                path.push(key("pose"));
+                error(
+                    path,
+                    format!("Robot {} is spawned inside walls[{}]", robot.id, j),
+                );
+            }
+        }
+        for (j, obj) in config.static_objects.iter().enumerate() {
+            if obj.width > 0.0 && obj.height > 0.0 {
+                let static_obj = StaticObj::new(obj.center, obj.width, obj.height, obj.rotation);
+                if robot_obj.distance_at(&static_obj, &robot.pose) <= 0.0 {
+                    let mut path = robot_path.clone();
+                    path.push(key("pose"));
+                    error(
+                        path,
+                        format!("Robot {} is spawned inside static_objects[{}]", robot.id, j),
+                    );
+                }
+            }
+        }
+    }
+
+    for (i, wall) in config.walls.iter().enumerate() {
+        if wall.endpoints.len() < 2 {
+            error(
+                vec![key("walls"), PathSegment::Index(i), key("endpoints")],
+                format!(
+                    "A wall needs at least 2 endpoints, got {}",
+                    wall.endpoints.len()
+                ),
+            );
+        }
+    }
+
+    for (i, obj) in config.static_objects.iter().enumerate() {
+        if !positive(obj.width) {
+            error(
+                vec![key("static_objects"), PathSegment::Index(i), key("width")],
+                format!("Width must be positive, got {}", obj.width),
+            );
+        }
+        if !positive(obj.height) {
+            error(
+                vec![key("static_objects"), PathSegment::Index(i), key("height")],
+                format!("Height must be positive, got {}", obj.height),
+            );
+        }
+        if let Some(message) = obj.body.as_ref().and_then(body_problem) {
+            error(
+                vec![key("static_objects"), PathSegment::Index(i), key("body")],
+                message,
+            );
+        }
+    }
+
+    if config.physics.substeps < 1 {
+        error(
+            vec![key("physics"), key("substeps")],
+            "Substeps must be at least 1, got 0".to_string(),
+        );
+    }
+    if !(0.0..=1.0).contains(&config.physics.restitution) {
+        error(
+            vec![key("physics"), key("restitution")],
+            format!(
+                "Restitution must be between 0 and 1, got {}",
+                config.physics.restitution
+            ),
+        );
+    }
+
+    return errors;
+}
+
+fn unknown_drive_type(drive_type: &DriveType) -> String {
+    return format!(
+        "Unknown drive type {}. Known drive types: {}",
+        drive_type,
+        kinematic_model_names().join(", ")
+    );
+}
+
+/// Describes every limit that is not a positive number, by field.
+fn limits_problems(limits: &LimitsConfig) -> Vec<(&'static str, String)> {
+    let fields = [
+        ("max_linear_vel", limits.max_linear_vel),
+        ("max_angular_vel", limits.max_angular_vel),
+        ("max_linear_accel", limits.max_linear_accel),
+        ("max_angular_accel", limits.max_angular_accel),
+        ("max_steering_angle", limits.max_steering_angle),
+        ("max_steering_rate", limits.max_steering_rate),
+    ];
+
+    let mut problems = Vec::new();
+    for (field, limit) in fields {
+        if let Some(limit) = limit {
+            if !(limit.is_finite() && positive(limit)) {
+                problems.push((field, format!("Limit must be positive, got {}", limit)));
+            }
+        }
+    }
+    return problems;
+}
+
+/// Describes what is wrong with the actuator, if anything.
+fn actuator_problem(actuator: &ActuatorConfig) -> Option<String> {
+    if !non_negative(actuator.time_constant) {
+        return Some(format!(
+            "Time constant must not be negative, got {}",
+            actuator.time_constant
+        ));
+    }
+    if !non_negative(actuator.damping) {
+        return Some(format!(
+            "Damping must not be negative, got {}",
+            actuator.damping
+        ));
+    }
+    if !non_negative(actuator.deadband) {
+        return Some(format!(
+            "Deadband must not be negative, got {}",
+            actuator.deadband
+        ));
+    }
+    return None;
+}
+
+/// Describes what is wrong with the noise, if anything.
+fn noise_problem(noise: &NoiseConfig) -> Option<String> {
+    let deviations = [noise.sigma, noise.proportional];
+    if deviations
+        .iter()
+        .any(|d| !(non_negative(d.0) && non_negative(d.1) && non_negative(d.2)))
+    {
+        return Some("Standard deviations must not be negative".to_string());
+    }
+    return None;
+}
+
+/// Describes what is wrong with the wheels of a robot with this drive type, if anything.
+fn wheels_problem(wheels: &WheelConfig, drive_type: &DriveType) -> Option<String> {
+    if !positive(wheels.radius) {
+        return Some(format!(
+            "Wheel radius must be positive, got {}",
+            wheels.radius
+        ));
+    }
+    if !positive(wheels.track_width) {
+        return Some(format!(
+            "Track width must be positive, got {}",
+            wheels.track_width
+        ));
+    }
+    if !non_negative(wheels.wheelbase) {
+        return Some(format!(
+            "Wheelbase must not be negative, got {}",
+            wheels.wheelbase
+        ));
+    }
+    if wheels.ticks_per_revolution == 0 {
+        return Some("Ticks per revolution must be positive, got 0".to_string());
+    }
+    if !fraction(wheels.slip) {
+        return Some(format!(
+            "Slip must be at least 0 and below 1, got {}",
+            wheels.slip
+        ));
+    }
+    if let Some(model) = drive_type.kinematic_model() {
+        if WheelLayout::for_model(&*model).is_none() {
+            return Some(format!(
+                "Robots with drive type {} can not be driven by wheel speeds",
+                drive_type
+            ));
+        }
+    }
+    return None;
+}
+
+/// Describes what is wrong with the tracks of a robot with this drive type, if anything.
+fn skid_steer_problem(skid_steer: &SkidSteerConfig, drive_type: &DriveType) -> Option<String> {
+    if *drive_type != DriveType::SkidSteer {
+        return Some(format!(
+            "Only SkidSteer robots have tracks, this robot is {}",
+            drive_type
+        ));
+    }
+    let icrs = [skid_steer.icr_left, skid_steer.icr_right];
+    if icrs.iter().flatten().any(|icr| !positive(*icr)) {
+        return Some("ICR distances of the tracks must be positive".to_string());
+    }
+    let slips = [skid_steer.slip_left, skid_steer.slip_right];
+    if slips.iter().any(|slip| !fraction(*slip)) {
+        return Some("Slip must be at least 0 and below 1".to_string());
+    }
+    return None;
+}
+
+/// Describes what is wrong with the trailer, if anything.
+fn trailer_problem(trailer: &TrailerConfig) -> Option<String> {
+    if !non_negative(trailer.hitch_offset) {
+        return Some(format!(
+            "Hitch offset must not be negative, got {}",
+            trailer.hitch_offset
+        ));
+    }
+    if !positive(trailer.length) {
+        return Some(format!(
+            "Trailer length must be positive, got {}",
+            trailer.length
+        ));
+    }
+    return footprint_problem(&trailer.footprint);
+}
+
+/// Describes what is wrong with the rigid body, if anything.
+fn body_problem(body: &BodyConfig) -> Option<String> {
+    if !positive(body.mass) {
+        return Some(format!("Mass must be positive, got {}", body.mass));
+    }
+    if !non_negative(body.friction) {
+        return Some(format!(
+            "Friction must not be negative, got {}",
+            body.friction
+        ));
+    }
+    if !(0.0..=1.0).contains(&body.restitution) {
+        return Some(format!(
+            "Restitution must be between 0 and 1, got {}",
+            body.restitution
+        ));
+    }
+    return None;
+}
+
+// The checks below treat NaN as out of range
+
+/// Whether `x` is above zero.
+fn positive(x: f32) -> bool {
+    return x > 0.0;
+}
+
+/// Whether `x` is zero or above.
+fn non_negative(x: f32) -> bool {
+    return x >= 0.0;
+}
+
+/// Whether `x` is in `[0, 1)`, like a slip ratio.
+fn fraction(x: f32) -> bool {
+    return (0.0..1.0).contains(&x);
+}
+
+/// Describes what is wrong with the footprint, if anything.
+fn footprint_problem(footprint: &[f32]) -> Option<String> {
+    if footprint.len() != 1 && footprint.len() != 2 {
+        return Some(format!(
+            "Footprint needs 1 value (radius) or 2 values (width, height), got {}",
+            footprint.len()
+        ));
+    }
+    if footprint.iter().any(|v| !positive(*v)) {
+        return Some("Footprint sizes must be positive".to_string());
+    }
+    return None;
+}
+
+/// Column of the first non-space character and whether the line is a list item.
+fn indentation(line: &str) -> Option<(usize, bool)> {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+    let is_item = trimmed == "-" || trimmed.starts_with("- ");
+    return Some((line.len() - trimmed.len(), is_item));
+}
+
+/// Column and name of the mapping key on this line, looking through list item dashes.
+fn key_of(line: &str) -> Option<(usize, &str)> {
+    let mut column = line.len() - line.trim_start().len();
+    let mut rest = line.trim_start();
+    while rest.starts_with("- ") {
+        let after = rest[2..].trim_start();
+        column += rest.len() - after.len();
+        rest = after;
+    }
+
+    let end = rest.find(':')?;
+    let name = rest[..end].trim_matches(|c| c == '"' || c == '\'');
+    if name.is_empty() || name.contains(' ') {
+        return None;
+    }
+    return Some((column, name));
+}
+
+/// Finds the 1-based line and column of the entry at `path` in a block style YAML document.
+pub fn locate(source: &str, path: &[PathSegment]) -> Option<(usize, usize)> {
+    let lines: Vec<&str> = source.lines().collect();
+    let (mut start, mut end) = (0, lines.len());
+    let mut found = None;
+
+    for segment in path.iter() {
+        match segment {
+            PathSegment::Key(name) => {
+                // Keys of this mapping are at the column of the first key in the block
+                let level = (start..end).find_map(|l| key_of(lines[l]))?.0;
+                let line = (start..end)
+                    .find(|l| key_of(lines[*l]).is_some_and(|(c, k)| c == level && k == name))?;
+                found = Some((line + 1, level + 1));
+
+                // Children are indented deeper, or are list items at the same column
+                start = line + 1;
+                end = (start..end)
+                    .find(|l| match indentation(lines[*l]) {
+                        Some((column, is_item)) => column < level || (column == level && !is_item),
+                        None => false,
+                    })
+                    .unwrap_or(end);
+            }
+            PathSegment::Index(index) => {
+                let dash = (start..end)
+                    .filter_map(|l| indentation(lines[l]).filter(|i| i.1).map(|i| i.0))
+                    .min()?;
+                let items: Vec<usize> = (start..end)
+                    .filter(|l| indentation(lines[*l]) == Some((dash, true)))
+                    .collect();
+                let line = *items.get(*index)?;
+                found = Some((line + 1, dash + 1));
+
+                start = line;
+                end = items.get(index + 1).copied().unwrap_or(end);
+            }
+        }
+    }
+
+    return found;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+# A world
+robots:
+  - id: robot0
+    pose: [0.0, 0.0, 0.0]
+    lidar:
+      range: 5.0
+  - id: robot1
+    pose: [1.0, 0.0, 0.0]
+walls:
+- endpoints:
+  - [0.0, 0.0]
+  - [1.0, 0.0]
+";
+
+    #[test]
+    fn locates_top_level_keys() {
+        assert_eq!(locate(SOURCE, &[key("robots")]), Some((2, 1)));
+        assert_eq!(locate(SOURCE, &[key("walls")]), Some((9, 1)));
+    }
+
+    #[test]
+    fn locates_keys_of_list_items() {
+        let path = [key("robots"), PathSegment::Index(1), key("pose")];
+        assert_eq!(locate(SOURCE, &path), Some((8, 5)));
+
+        let path = [
+            key("robots"),
+            PathSegment::Index(0),
+            key("lidar"),
+            key("range"),
+        ];
+        assert_eq!(locate(SOURCE, &path), Some((6, 7)));
+    }
+
+    #[test]
+    fn locates_nested_list_items() {
+        let path = [
+            key("walls"),
+            PathSegment::Index(0),
+            key("endpoints"),
+            PathSegment::Index(1),
+        ];
+        assert_eq!(locate(SOURCE, &path), Some((12, 3)));
+    }
+
+    #[test]
+    fn missing_entries_are_not_located() {
+        assert_eq!(locate(SOURCE, &[key("static_objects")]), None);
+        assert_eq!(
+            locate(SOURCE, &[key("robots"), PathSegment::Index(2)]),
+            None
+        );
+
+        // Keys of the second robot are not found in the first one
+        let path = [key("robots"), PathSegment::Index(1), key("lidar")];
+        assert_eq!(locate(SOURCE, &path), None);
+    }
+}